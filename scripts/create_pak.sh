@@ -7,6 +7,7 @@ PAK_DIR_NAME="Updater.pak"
 UPDATER_DIR="$DIST_DIR/Tools/tg5040/$PAK_DIR_NAME"
 UPDATER_BINARY="target/aarch64-unknown-linux-gnu/release/nextui-updater-rs"
 LAUNCH_SCRIPT="$UPDATER_DIR/launch.sh"
+CHECK_SCRIPT="$UPDATER_DIR/check.sh"
 ZIP_FILE="nextui-updater-pak.zip"
 
 rm -rf "$DIST_DIR"
@@ -23,13 +24,36 @@ cd \$(dirname "\$0")
 
 while : ; do
 
-./nextui-updater 2>&1 >> logs.txt
+# A self-updated binary gets one chance to reach the UI
+[ -f self-update-pending ] && touch self-update-started
 
-[[ \$? -eq 5 ]] || break
+./nextui-updater "\$@" 2>&1 >> logs.txt
+status=\$?
+
+if [ -f self-update-started ] && [ -f nextui-updater.bak ]; then
+    echo "New updater failed to start, restoring the previous one" >> logs.txt
+    mv -f nextui-updater.bak nextui-updater
+    mv -f self-update-pending self-update-failed
+    rm -f self-update-started
+    continue
+fi
+
+[[ \$status -eq 5 ]] || break
 
 done
 
 EOF
 
+cat > "$CHECK_SCRIPT" <<EOF
+#!/bin/sh
+
+# Updates status.txt without showing anything, for boot or cron hooks
+
+cd \$(dirname "\$0")
+./nextui-updater --check-only >> logs.txt 2>&1
+
+EOF
+chmod +x "$CHECK_SCRIPT"
+
 (cd "$DIST_DIR" && zip -r "../$ZIP_FILE" .)
 (cd "$UPDATER_DIR" && zip -r "../../../../$PAK_DIR_NAME.zip" .)
@@ -0,0 +1,37 @@
+use crate::device;
+
+// Licenses of what's bundled into the updater, shown on the About screen
+pub const NOTICES: [(&str, &str); 10] = [
+    ("NextUI Updater", "MIT"),
+    ("DejaVu fonts", "Bitstream Vera, public domain"),
+    ("SDL2", "zlib"),
+    ("egui", "MIT or Apache-2.0"),
+    ("reqwest", "MIT or Apache-2.0"),
+    ("rustls", "MIT, Apache-2.0 or ISC"),
+    ("webpki-roots", "MPL-2.0"),
+    ("serde", "MIT or Apache-2.0"),
+    ("zip", "MIT"),
+    ("minisign-verify", "MIT"),
+];
+
+// Full license texts for the notices that require them
+pub const LICENSE: &str = include_str!("../LICENSE");
+pub const FONT_LICENSE: &str = include_str!("../res/DejaVu-LICENSE.txt");
+
+// What the About screen shows about this device, probed when it's opened
+#[derive(Clone, Debug, Default)]
+pub struct About {
+    pub device: Option<String>,
+    pub nextui_name: Option<String>,
+    pub free_space: Option<u64>,
+}
+
+impl About {
+    pub fn probe() -> Self {
+        Self {
+            device: device::device_model(),
+            nextui_name: device::installed_name(),
+            free_space: device::disk_space().map(|space| space.free),
+        }
+    }
+}
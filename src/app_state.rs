@@ -1,59 +1,479 @@
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, OnceLock};
+use std::thread::{self, ThreadId};
+use std::time::{Duration, Instant};
 
-use parking_lot::Mutex;
+use log::{debug, warn};
+use parking_lot::{Mutex, MutexGuard};
 
-use crate::github::{Release, ReleaseAndTag, Tag};
+use crate::about::About;
+use crate::cancel::CancellationToken;
+use crate::config::{Config, Source};
+use crate::device::Limitation;
+use crate::error::{Result, UpdaterError};
+use crate::feedback::{Cue, Rumble};
+use crate::github::{Release, ReleaseAndTag, ReleaseDetails, Tag};
+use crate::history::HistoryEntry;
+use crate::i18n::tr;
+use crate::keyboard::TextEntry;
+use crate::release_filter::ReleaseFilter;
+use crate::storage::StorageReport;
+use crate::update::{
+    for_forge, FolderSummary, IntegrityReport, InterruptedExtraction, PendingInstall, Prefetch,
+    ReleaseSource, UpdatePhase,
+};
 
 // Application state shared between UI thread and update thread
 #[derive(Clone)]
 pub enum Progress {
     Indeterminate,
-    Determinate(f32),
+    Determinate(f32, Option<Transfer>),
 }
 
+// Download state shown under the progress bar
+#[derive(Clone, Copy, Debug)]
+pub struct Transfer {
+    pub downloaded: u64,
+    // 0 if the server didn't send a size
+    pub total: u64,
+    // Bytes per second
+    pub speed: f64,
+}
+
+impl Transfer {
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 {
+            0.0
+        } else {
+            (self.downloaded as f64 / self.total as f64) as f32
+        }
+    }
+
+    #[allow(clippy::cast_sign_loss)]
+    pub fn eta(&self) -> Option<Duration> {
+        if self.speed <= 0.0 || self.total < self.downloaded {
+            return None;
+        }
+        Some(Duration::from_secs_f64(
+            (self.total - self.downloaded) as f64 / self.speed,
+        ))
+    }
+}
+
+// How long fetching release information can take before suggesting skipping it
+const FETCH_PATIENCE: Duration = Duration::from_secs(5);
+
+#[allow(clippy::struct_excessive_bools)]
 pub struct AppState {
+    config: Config,
+    init_status: Option<String>,
     submenu: Submenu,
+    submenu_stack: Vec<Submenu>,
+    // Cancelled when leaving the current screen, and the ones it was opened from
+    screen_token: CancellationToken,
+    screen_token_stack: Vec<CancellationToken>,
     current_version: Option<String>,
     nextui_release: Option<Release>,
     nextui_tag: Option<Tag>,
     nextui_releases_and_tags: Option<Vec<ReleaseAndTag>>,
     nextui_releases_and_tags_index: Option<usize>,
     release_selection_menu: bool,
+    // The downgrade warning for the version picked in the selector was accepted
     release_selection_confirmed: bool,
+    release_selection_open: bool,
+    // What to warn about before the update options for the picked version,
+    // worked out when it's picked. None for an upgrade.
+    downgrade_cautions: Option<Vec<String>>,
+    release_filter: ReleaseFilter,
+    release_list_focus: Option<usize>,
+    // What a full update writes, listed when its confirmation screen opens
+    full_update_categories: Vec<String>,
+    tag_lookups: HashSet<String>,
+    release_highlighted_at: Option<Instant>,
+    release_details: HashMap<String, ReleaseDetails>,
+    details_fetch: Option<(String, CancellationToken)>,
+    history: Vec<HistoryEntry>,
+    // Device details for the About screen
+    about: Option<About>,
+    // What a full update is about to write, waiting for the user to confirm
+    extraction_preview: Option<(Vec<FolderSummary>, Sender<bool>)>,
+    // The last install, if it didn't boot cleanly
+    failed_install: Option<PendingInstall>,
+    // The last install, if it stopped partway through extracting
+    interrupted_install: Option<InterruptedExtraction>,
+    // Result of the last installation check
+    integrity_report: Option<IntegrityReport>,
+    // Disk and folder sizes for the storage screen, filled in as they're added up
+    storage_report: Option<StorageReport>,
+    // The asset downloading in the background, in case it's installed
+    prefetch: Option<Prefetch>,
+    // Replaces the selected source's forge, for tests
+    release_source: Option<Arc<dyn ReleaseSource>>,
+    // Why updates can't be applied here, empty if they can
+    limitations: Vec<Limitation>,
+    update_phase: UpdatePhase,
     current_operation: Option<String>,
     progress: Option<Progress>,
     error: Option<String>,
+    // Raised while an update was running, shown once it has stopped
+    queued_error: Option<String>,
+    // Informational message, e.g. where a file was saved. Cleared when changing screens.
+    notice: Option<String>,
     hint: Option<String>,
+    // Asset picked for a release by tag name, instead of matching the source's patterns
+    chosen_asset: Option<(String, String)>,
+    // When the startup checks or a release check began, and a token to give
+    // up on them
+    fetch: Option<(Instant, CancellationToken)>,
+    // Stops whatever the update pipeline is running. A fresh one is made
+    // whenever it starts something, as a child of the fetch token if there is
+    // one so skipping the fetch stops it too.
+    operation_token: CancellationToken,
+    // Sounds waiting for the UI thread to play them
+    cues: Vec<Cue>,
+    // Same for rumbles, since the UI thread owns the controller
+    rumbles: Vec<Rumble>,
+    // What's typed into the command palette
+    command_query: String,
+    // What's typed on the on-screen keyboard, and which setting it's for
+    text_entry: Option<TextEntry>,
+    crash: Option<String>,
     should_quit: bool,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum Submenu {
     NextUI,
+    Menu,
+    Settings,
+    History,
+    ReleaseDetails,
+    Developer,
+    Offline,
+    AlreadyRunning,
+    Crashed,
+    FailedInstall,
+    InterruptedInstall,
+    Logs,
+    CommandPalette,
+    ControllerTest,
+    Controls,
+    Keyboard,
+    AssetPicker,
+    Integrity,
+    Storage,
+    FullUpdate,
+    About,
 }
 
 pub struct AppStateManager {
-    state: Arc<Mutex<AppState>>,
+    state: Arc<Shared>,
+}
+
+// What the UI draws each frame, taken in one go instead of locking the state
+// for every field
+#[derive(Clone)]
+pub struct Snapshot {
+    generation: u64,
+    pub config: Config,
+    pub init_status: Option<String>,
+    pub submenu: Submenu,
+    pub update_phase: UpdatePhase,
+    pub current_operation: Option<String>,
+    pub progress: Option<Progress>,
+    pub error: Option<String>,
+    pub notice: Option<String>,
+    pub hint: Option<String>,
+    pub should_quit: bool,
+    pub current_version: Option<String>,
+    pub nextui_release: Option<Release>,
+    pub nextui_tag: Option<Tag>,
+    pub nextui_releases_and_tags: Option<Vec<ReleaseAndTag>>,
+    pub nextui_releases_and_tags_index: Option<usize>,
+    pub release_selection_menu: bool,
+    pub release_selection_open: bool,
+    // None once the warning is accepted
+    pub downgrade_cautions: Option<Vec<String>>,
+    pub release_filter: ReleaseFilter,
+    pub release_highlighted_at: Option<Instant>,
+    pub limitations: Vec<Limitation>,
+    chosen_asset: Option<(String, String)>,
+    pub full_update_categories: Vec<String>,
+    // What a full update waiting for confirmation is about to write
+    pub extraction_preview: Option<Vec<FolderSummary>>,
+    fetch_started_at: Option<Instant>,
+}
+
+impl Snapshot {
+    pub fn installed_tag_name(&self) -> Option<String> {
+        installed_release(
+            self.current_version.as_ref(),
+            self.nextui_releases_and_tags.as_ref(),
+        )
+        .map(|release| release.tag_name.clone())
+    }
+
+    // Name of the asset picked for this release, if any
+    pub fn chosen_asset(&self, tag_name: &str) -> Option<&str> {
+        self.chosen_asset
+            .as_ref()
+            .filter(|(chosen_tag, _)| chosen_tag == tag_name)
+            .map(|(_, asset_name)| asset_name.as_str())
+    }
+
+    // How long release information has been fetched for, if it can still be
+    // skipped
+    pub fn fetch_skippable(&self) -> Option<Duration> {
+        self.fetch_started_at.map(|started_at| started_at.elapsed())
+    }
+
+    pub fn fetch_slow(&self) -> bool {
+        self.fetch_skippable()
+            .is_some_and(|elapsed| elapsed >= FETCH_PATIENCE)
+    }
+}
+
+// The installed version's release, if it's one of the known ones
+fn installed_release<'a>(
+    current_version: Option<&String>,
+    releases_and_tags: Option<&'a Vec<ReleaseAndTag>>,
+) -> Option<&'a Release> {
+    let current_version = current_version?;
+    releases_and_tags?
+        .iter()
+        .find(|release_and_tag| release_and_tag.matches_commit(current_version))
+        .map(|release_and_tag| &release_and_tag.release)
+}
+
+// Wakes the UI thread when another thread changes the state
+struct Waker {
+    ui_thread: ThreadId,
+    wake: Box<dyn Fn() + Send + Sync>,
+}
+
+struct Shared {
+    state: Mutex<AppState>,
+    // Bumped on every change, so the UI knows when its snapshot is stale
+    generation: AtomicU64,
+    waker: OnceLock<Waker>,
+    // The UI has been woken and hasn't taken a snapshot since, so a burst of
+    // progress updates wakes it once
+    wake_pending: AtomicBool,
+}
+
+impl Shared {
+    fn lock(&self) -> StateGuard<'_> {
+        StateGuard {
+            shared: self,
+            guard: self.state.lock(),
+            changed: false,
+        }
+    }
+
+    fn try_lock_for(&self, timeout: Duration) -> Option<StateGuard<'_>> {
+        Some(StateGuard {
+            shared: self,
+            guard: self.state.try_lock_for(timeout)?,
+            changed: false,
+        })
+    }
+
+    fn changed(&self) {
+        self.generation.fetch_add(1, Ordering::Release);
+        let Some(waker) = self.waker.get() else {
+            return;
+        };
+        // The UI thread's own changes are drawn on its next frame anyway
+        if thread::current().id() != waker.ui_thread
+            && !self.wake_pending.swap(true, Ordering::AcqRel)
+        {
+            (waker.wake)();
+        }
+    }
+}
+
+// Locked state that counts as changed once it's been borrowed mutably
+struct StateGuard<'a> {
+    shared: &'a Shared,
+    guard: MutexGuard<'a, AppState>,
+    changed: bool,
+}
+
+impl Deref for StateGuard<'_> {
+    type Target = AppState;
+
+    fn deref(&self) -> &AppState {
+        &self.guard
+    }
+}
+
+impl DerefMut for StateGuard<'_> {
+    fn deref_mut(&mut self) -> &mut AppState {
+        self.changed = true;
+        &mut self.guard
+    }
+}
+
+impl Drop for StateGuard<'_> {
+    fn drop(&mut self) {
+        if self.changed {
+            self.shared.changed();
+        }
+    }
+}
+
+impl AppState {
+    fn transition(&mut self, next: UpdatePhase) -> Result<()> {
+        if !self.update_phase.can_transition_to(&next) {
+            return Err(UpdaterError::InvalidTransition(
+                self.update_phase.clone(),
+                next,
+            ));
+        }
+        debug!("Update phase: {:?} -> {next:?}", self.update_phase);
+        if next.is_active() && !self.update_phase.is_active() {
+            self.operation_token = self
+                .fetch
+                .as_ref()
+                .map_or_else(CancellationToken::new, |(_, fetch)| fetch.child());
+        }
+        self.update_phase = next;
+        if self.update_phase.is_active() {
+            // Trying again replaces whatever failed before
+            self.error = None;
+        } else {
+            self.current_operation = None;
+            self.progress = None;
+            if let Some(error) = self.queued_error.take() {
+                self.error = Some(error);
+            }
+        }
+        Ok(())
+    }
+
+    // When the running release check or download started, if it can still
+    // be skipped because nothing has been written yet
+    fn skippable_fetch_started_at(&self) -> Option<Instant> {
+        let (started_at, token) = self.fetch.as_ref()?;
+        let fetching = matches!(
+            self.update_phase,
+            UpdatePhase::CheckingRelease | UpdatePhase::Downloading
+        );
+        (fetching && !token.is_cancelled()).then_some(*started_at)
+    }
+
+    // Operations and progress belong to a running phase, so they're dropped
+    // when nothing is running
+    fn show_operation(&mut self, operation: Option<String>) {
+        if operation.is_none() || self.update_phase.is_active() {
+            self.current_operation = operation;
+        } else {
+            debug!("Ignoring operation outside of the update pipeline: {operation:?}");
+        }
+    }
+
+    fn show_progress(&mut self, progress: Option<Progress>) {
+        if progress.is_none() || self.update_phase.is_active() {
+            self.progress = progress;
+        }
+    }
+
+    // The update's failure, or an error from anything else
+    fn error(&self) -> Option<String> {
+        self.update_phase
+            .error()
+            .map(str::to_string)
+            .or_else(|| self.error.clone())
+    }
+
+    // Dismissing a failed update leaves it behind, and shows anything else
+    // that went wrong while it ran
+    fn clear_error(&mut self) {
+        if matches!(self.update_phase, UpdatePhase::Failed { .. }) {
+            if let Err(err) = self.transition(UpdatePhase::Idle) {
+                warn!("{err}");
+            }
+        } else {
+            self.error = None;
+        }
+    }
+
+    // Cancel the current screen's work and start over with a fresh token
+    fn renew_screen_token(&mut self) {
+        self.screen_token.cancel();
+        self.screen_token = CancellationToken::new();
+    }
+
+    fn cancel_screen_tokens(&mut self) {
+        for token in self.screen_token_stack.drain(..) {
+            token.cancel();
+        }
+        self.renew_screen_token();
+    }
 }
 
 impl AppStateManager {
     pub fn new() -> Self {
         Self {
-            state: Arc::new(Mutex::new(AppState {
-                submenu: Submenu::NextUI,
-                current_version: None,
-                nextui_release: None,
-                nextui_tag: None,
-                nextui_releases_and_tags: None,
-                nextui_releases_and_tags_index: None,
-                release_selection_menu: false,
-                release_selection_confirmed: false,
-                current_operation: None,
-                progress: None,
-                error: None,
-                hint: None,
-                should_quit: false,
-            })),
+            state: Arc::new(Shared {
+                state: Mutex::new(AppState {
+                    config: Config::default(),
+                    init_status: None,
+                    submenu: Submenu::NextUI,
+                    submenu_stack: vec![],
+                    screen_token: CancellationToken::new(),
+                    screen_token_stack: vec![],
+                    current_version: None,
+                    nextui_release: None,
+                    nextui_tag: None,
+                    nextui_releases_and_tags: None,
+                    nextui_releases_and_tags_index: None,
+                    release_selection_menu: false,
+                    release_selection_confirmed: false,
+                    release_selection_open: false,
+                    downgrade_cautions: None,
+                    release_filter: ReleaseFilter::default(),
+                    release_list_focus: None,
+                    full_update_categories: vec![],
+                    tag_lookups: HashSet::new(),
+                    release_highlighted_at: None,
+                    release_details: HashMap::new(),
+                    details_fetch: None,
+                    history: vec![],
+                    about: None,
+                    extraction_preview: None,
+                    command_query: String::new(),
+                    text_entry: None,
+                    cues: vec![],
+                    rumbles: vec![],
+                    fetch: None,
+                    operation_token: CancellationToken::new(),
+                    chosen_asset: None,
+                    failed_install: None,
+                    interrupted_install: None,
+                    integrity_report: None,
+                    storage_report: None,
+                    prefetch: None,
+                    release_source: None,
+                    limitations: vec![],
+                    update_phase: UpdatePhase::Idle,
+                    current_operation: None,
+                    progress: None,
+                    error: None,
+                    queued_error: None,
+                    notice: None,
+                    hint: None,
+                    crash: None,
+                    should_quit: false,
+                }),
+                generation: AtomicU64::new(0),
+                waker: OnceLock::new(),
+                wake_pending: AtomicBool::new(false),
+            }),
         }
     }
 
@@ -64,7 +484,70 @@ impl AppStateManager {
         }
     }
 
+    // Called by the UI thread, to be woken when other threads change the state
+    pub fn set_waker(&self, wake: impl Fn() + Send + Sync + 'static) {
+        let waker = Waker {
+            ui_thread: thread::current().id(),
+            wake: Box::new(wake),
+        };
+        if self.state.waker.set(waker).is_err() {
+            warn!("State waker already set");
+        }
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        let state = self.state.lock();
+        self.state.wake_pending.store(false, Ordering::Release);
+        Snapshot {
+            generation: self.state.generation.load(Ordering::Acquire),
+            config: state.config.clone(),
+            init_status: state.init_status.clone(),
+            submenu: state.submenu,
+            update_phase: state.update_phase.clone(),
+            current_operation: state.current_operation.clone(),
+            progress: state.progress.clone(),
+            error: state.error(),
+            notice: state.notice.clone(),
+            hint: state.hint.clone(),
+            should_quit: state.should_quit,
+            current_version: state.current_version.clone(),
+            nextui_release: state.nextui_release.clone(),
+            nextui_tag: state.nextui_tag.clone(),
+            nextui_releases_and_tags: state.nextui_releases_and_tags.clone(),
+            nextui_releases_and_tags_index: state.nextui_releases_and_tags_index,
+            release_selection_menu: state.release_selection_menu,
+            release_selection_open: state.release_selection_open,
+            downgrade_cautions: state
+                .downgrade_cautions
+                .clone()
+                .filter(|_| !state.release_selection_confirmed),
+            release_filter: state.release_filter.clone(),
+            release_highlighted_at: state.release_highlighted_at,
+            limitations: state.limitations.clone(),
+            chosen_asset: state.chosen_asset.clone(),
+            full_update_categories: state.full_update_categories.clone(),
+            extraction_preview: state
+                .extraction_preview
+                .as_ref()
+                .map(|(folders, _)| folders.clone()),
+            fetch_started_at: state.skippable_fetch_started_at(),
+        }
+    }
+
+    // Whether the state changed since the snapshot was taken
+    pub fn changed_since(&self, snapshot: &Snapshot) -> bool {
+        self.state.generation.load(Ordering::Acquire) != snapshot.generation
+    }
+
     // Getter methods
+    pub fn config(&self) -> Config {
+        self.state.lock().config.clone()
+    }
+
+    pub fn init_status(&self) -> Option<String> {
+        self.state.lock().init_status.clone()
+    }
+
     pub fn submenu(&self) -> Submenu {
         self.state.lock().submenu
     }
@@ -73,6 +556,19 @@ impl AppStateManager {
         self.state.lock().should_quit
     }
 
+    pub fn limitations(&self) -> Vec<Limitation> {
+        self.state.lock().limitations.clone()
+    }
+
+    // Limited mode: releases can be browsed, but nothing is installed
+    pub fn limited_mode(&self) -> bool {
+        !self.state.lock().limitations.is_empty()
+    }
+
+    pub fn update_phase(&self) -> UpdatePhase {
+        self.state.lock().update_phase.clone()
+    }
+
     pub fn current_operation(&self) -> Option<String> {
         self.state.lock().current_operation.clone()
     }
@@ -82,13 +578,135 @@ impl AppStateManager {
     }
 
     pub fn error(&self) -> Option<String> {
-        self.state.lock().error.clone()
+        self.state.lock().error()
+    }
+
+    pub fn notice(&self) -> Option<String> {
+        self.state.lock().notice.clone()
     }
 
     pub fn hint(&self) -> Option<String> {
         self.state.lock().hint.clone()
     }
 
+    pub fn extraction_preview(&self) -> Option<Vec<FolderSummary>> {
+        self.state
+            .lock()
+            .extraction_preview
+            .as_ref()
+            .map(|(folders, _)| folders.clone())
+    }
+
+    // Name of the asset picked for this release, if any
+    pub fn chosen_asset(&self, tag_name: &str) -> Option<String> {
+        self.state
+            .lock()
+            .chosen_asset
+            .as_ref()
+            .filter(|(chosen_tag, _)| chosen_tag == tag_name)
+            .map(|(_, asset_name)| asset_name.clone())
+    }
+
+    pub fn set_chosen_asset(&self, chosen_asset: Option<(String, String)>) {
+        self.state.lock().chosen_asset = chosen_asset;
+    }
+
+    pub fn begin_fetch(&self) -> CancellationToken {
+        let token = CancellationToken::new();
+        self.state.lock().fetch = Some((Instant::now(), token.clone()));
+        token
+    }
+
+    pub fn end_fetch(&self) {
+        self.state.lock().fetch = None;
+    }
+
+    // How long release information has been fetched for, if it can still be
+    // skipped because nothing has been written yet
+    pub fn fetch_skippable(&self) -> Option<Duration> {
+        self.state
+            .lock()
+            .skippable_fetch_started_at()
+            .map(|started_at| started_at.elapsed())
+    }
+
+    // Stop waiting on the release information; it's dropped when it arrives
+    pub fn skip_fetch(&self) {
+        let mut state = self.state.lock();
+        if let Some((_, token)) = state.fetch.take() {
+            token.cancel();
+        }
+        if let Err(err) = state.transition(UpdatePhase::Idle) {
+            warn!("{err}");
+        }
+        state.current_operation = None;
+        state.progress = None;
+        let notice = if state.nextui_release.is_some() {
+            tr!("Release check skipped")
+        } else {
+            tr!("Continuing offline")
+        };
+        state.notice = Some(notice.to_string());
+    }
+
+    pub fn push_cue(&self, cue: Cue) {
+        self.state.lock().cues.push(cue);
+    }
+
+    // Checked every frame, so only counts as a change when there were any
+    pub fn take_cues(&self) -> Vec<Cue> {
+        let mut state = self.state.lock();
+        if state.cues.is_empty() {
+            return vec![];
+        }
+        std::mem::take(&mut state.cues)
+    }
+
+    pub fn push_rumble(&self, rumble: Rumble) {
+        self.state.lock().rumbles.push(rumble);
+    }
+
+    pub fn take_rumbles(&self) -> Vec<Rumble> {
+        let mut state = self.state.lock();
+        if state.rumbles.is_empty() {
+            return vec![];
+        }
+        std::mem::take(&mut state.rumbles)
+    }
+
+    pub fn command_query(&self) -> String {
+        self.state.lock().command_query.clone()
+    }
+
+    pub fn failed_install(&self) -> Option<PendingInstall> {
+        self.state.lock().failed_install.clone()
+    }
+
+    pub fn interrupted_install(&self) -> Option<InterruptedExtraction> {
+        self.state.lock().interrupted_install.clone()
+    }
+
+    pub fn integrity_report(&self) -> Option<IntegrityReport> {
+        self.state.lock().integrity_report.clone()
+    }
+
+    pub fn storage_report(&self) -> Option<StorageReport> {
+        self.state.lock().storage_report.clone()
+    }
+
+    pub fn release_source(&self) -> Arc<dyn ReleaseSource> {
+        self.release_source_for(&self.config().source())
+    }
+
+    pub fn release_source_for(&self, source: &Source) -> Arc<dyn ReleaseSource> {
+        let release_source = self.state.lock().release_source.clone();
+        release_source.unwrap_or_else(|| for_forge(&source.forge))
+    }
+
+    pub fn crash(&self) -> Option<String> {
+        self.state.lock().crash.clone()
+    }
+
     pub fn current_version(&self) -> Option<String> {
         self.state.lock().current_version.clone()
     }
@@ -106,7 +724,7 @@ impl AppStateManager {
     }
 
     pub fn nextui_releases_and_tags_index(&self) -> Option<usize> {
-        self.state.lock().nextui_releases_and_tags_index.clone()
+        self.state.lock().nextui_releases_and_tags_index
     }
 
     pub fn release_selection_menu(&self) -> bool {
@@ -117,7 +735,63 @@ impl AppStateManager {
         self.state.lock().release_selection_confirmed
     }
 
+    pub fn release_selection_open(&self) -> bool {
+        self.state.lock().release_selection_open
+    }
+
+    // The downgrade warning to show, until it's accepted
+    pub fn downgrade_cautions(&self) -> Option<Vec<String>> {
+        let state = self.state.lock();
+        if state.release_selection_confirmed {
+            return None;
+        }
+        state.downgrade_cautions.clone()
+    }
+
+    pub fn release_filter(&self) -> ReleaseFilter {
+        self.state.lock().release_filter.clone()
+    }
+
+    pub fn release_details(&self, tag_name: &str) -> Option<ReleaseDetails> {
+        self.state.lock().release_details.get(tag_name).cloned()
+    }
+
+    pub fn details_fetch_running(&self) -> bool {
+        self.state
+            .lock()
+            .details_fetch
+            .as_ref()
+            .is_some_and(|(_, token)| !token.is_cancelled())
+    }
+
+    // Checked between the steps of whatever the update pipeline is running:
+    // downloads, extraction and release checks
+    pub fn operation_token(&self) -> CancellationToken {
+        self.state.lock().operation_token.clone()
+    }
+
+    // Token for background work started from the current screen
+    pub fn screen_token(&self) -> CancellationToken {
+        self.state.lock().screen_token.clone()
+    }
+
+    pub fn history(&self) -> Vec<HistoryEntry> {
+        self.state.lock().history.clone()
+    }
+
+    pub fn about(&self) -> Option<About> {
+        self.state.lock().about.clone()
+    }
+
     // Setter methods
+    pub fn set_config(&self, config: Config) {
+        self.state.lock().config = config;
+    }
+
+    pub fn set_init_status(&self, status: Option<String>) {
+        self.state.lock().init_status = status;
+    }
+
     pub fn set_submenu(&self, submenu: Submenu) {
         self.state.lock().submenu = submenu;
     }
@@ -127,19 +801,107 @@ impl AppStateManager {
     }
 
     pub fn set_current_operation(&self, operation: Option<String>) {
-        self.state.lock().current_operation = operation;
+        self.state.lock().show_operation(operation);
     }
 
     pub fn set_progress(&self, progress: Option<Progress>) {
-        self.state.lock().progress = progress;
+        self.state.lock().show_progress(progress);
     }
 
     pub fn set_error(&self, error: Option<String>) {
-        self.state.lock().error = error;
+        let mut state = self.state.lock();
+        match error {
+            // Not shown next to a running update's progress
+            Some(error) if state.update_phase.is_active() => {
+                debug!("Queueing error until the update stops: {error}");
+                state.queued_error = Some(error);
+            }
+            Some(error) => state.error = Some(error),
+            None => state.clear_error(),
+        }
+    }
+
+    pub fn set_extraction_preview(&self, preview: Option<(Vec<FolderSummary>, Sender<bool>)>) {
+        self.state.lock().extraction_preview = preview;
+    }
+
+    // Go ahead with, or cancel, the update waiting on the extraction preview
+    pub fn answer_extraction_preview(&self, confirmed: bool) {
+        if let Some((_, reply)) = self.state.lock().extraction_preview.take() {
+            let _ = reply.send(confirmed);
+        }
+    }
+
+    pub fn set_command_query(&self, command_query: String) {
+        self.state.lock().command_query = command_query;
+    }
+
+    pub fn text_entry(&self) -> Option<TextEntry> {
+        self.state.lock().text_entry.clone()
+    }
+
+    pub fn set_text_entry(&self, text_entry: Option<TextEntry>) {
+        self.state.lock().text_entry = text_entry;
     }
 
+    pub fn set_failed_install(&self, failed_install: Option<PendingInstall>) {
+        self.state.lock().failed_install = failed_install;
+    }
+
+    pub fn set_interrupted_install(&self, interrupted_install: Option<InterruptedExtraction>) {
+        self.state.lock().interrupted_install = interrupted_install;
+    }
+
+    pub fn set_integrity_report(&self, integrity_report: Option<IntegrityReport>) {
+        self.state.lock().integrity_report = integrity_report;
+    }
+
+    pub fn set_storage_report(&self, storage_report: Option<StorageReport>) {
+        self.state.lock().storage_report = storage_report;
+    }
+
+    // Any other prefetch is cancelled
+    pub fn set_prefetch(&self, prefetch: Prefetch) {
+        if let Some(previous) = self.state.lock().prefetch.replace(prefetch) {
+            previous.cancel();
+        }
+    }
+
+    pub fn take_prefetch(&self) -> Option<Prefetch> {
+        self.state.lock().prefetch.take()
+    }
+
+    pub fn prefetching(&self, tag_name: &str, asset_name: &str) -> bool {
+        self.state
+            .lock()
+            .prefetch
+            .as_ref()
+            .is_some_and(|prefetch| prefetch.is_for(tag_name, asset_name))
+    }
+
+    // Keep the prefetch after leaving the screen it was started from, for
+    // an update to use
+    pub fn claim_prefetch(&self) {
+        if let Some(prefetch) = &self.state.lock().prefetch {
+            prefetch.claim();
+        }
+    }
+
+    pub fn set_release_source(&self, release_source: Arc<dyn ReleaseSource>) {
+        self.state.lock().release_source = Some(release_source);
+    }
+
+    pub fn set_notice(&self, notice: Option<String>) {
+        self.state.lock().notice = notice;
+    }
+
+    // Screens set their hint every frame, which only counts as a change when
+    // it's a different one
     pub fn set_hint(&self, hint: Option<String>) {
-        self.state.lock().hint = hint;
+        let mut state = self.state.lock();
+        if state.hint != hint {
+            state.hint = hint;
+        }
     }
 
     pub fn set_current_version(&self, version: Option<String>) {
@@ -163,55 +925,348 @@ impl AppStateManager {
     }
 
     pub fn set_release_selection_menu(&self, release_selection_menu: bool) {
-        self.state.lock().release_selection_menu = release_selection_menu;
+        let mut state = self.state.lock();
+        // The version selector counts as its own screen for background work
+        if state.release_selection_menu && !release_selection_menu {
+            state.renew_screen_token();
+            state.release_filter = ReleaseFilter::default();
+        }
+        state.release_selection_menu = release_selection_menu;
     }
 
     pub fn set_release_selection_confirmed(&self, release_selection_confirmed: bool) {
         self.state.lock().release_selection_confirmed = release_selection_confirmed;
     }
 
+    // Picking a version asks again about downgrading to it
+    pub fn set_release_selection_open(&self, release_selection_open: bool) {
+        let mut state = self.state.lock();
+        if release_selection_open && !state.release_selection_open {
+            state.release_selection_confirmed = false;
+        }
+        state.release_selection_open = release_selection_open;
+    }
+
+    pub fn set_downgrade_cautions(&self, cautions: Option<Vec<String>>) {
+        self.state.lock().downgrade_cautions = cautions;
+    }
+
+    pub fn set_release_filter(&self, release_filter: ReleaseFilter) {
+        self.state.lock().release_filter = release_filter;
+    }
+
+    pub fn set_release_list_focus(&self, index: Option<usize>) {
+        self.state.lock().release_list_focus = index;
+    }
+
+    pub fn set_full_update_categories(&self, categories: Vec<String>) {
+        self.state.lock().full_update_categories = categories;
+    }
+
+    pub fn set_release_highlighted_at(&self, highlighted_at: Option<Instant>) {
+        self.state.lock().release_highlighted_at = highlighted_at;
+    }
+
+    pub fn set_history(&self, history: Vec<HistoryEntry>) {
+        self.state.lock().history = history;
+    }
+
+    pub fn set_about(&self, about: Option<About>) {
+        self.state.lock().about = about;
+    }
+
+    pub fn set_limitations(&self, limitations: Vec<Limitation>) {
+        self.state.lock().limitations = limitations;
+    }
+
     // Combined operations
-    pub fn start_operation(&self, operation: &str) {
+    // Start fetching details for a release, cancelling any fetch for another
+    // release. Returns None if they're already known or being fetched.
+    pub fn begin_details_fetch(&self, tag_name: &str) -> Option<CancellationToken> {
         let mut state = self.state.lock();
-        state.current_operation = Some(operation.to_string());
-        state.progress = Some(Progress::Indeterminate);
+        if state.release_details.contains_key(tag_name) {
+            return None;
+        }
+        if let Some((fetching, token)) = &state.details_fetch {
+            if fetching == tag_name && !token.is_cancelled() {
+                return None;
+            }
+            token.cancel();
+        }
+        let token = state.screen_token.child();
+        state.details_fetch = Some((tag_name.to_string(), token.clone()));
+        Some(token)
     }
 
-    pub fn start_determinate_operation(&self, operation: &str) {
+    pub fn finish_details_fetch(&self, tag_name: &str, details: Option<ReleaseDetails>) {
         let mut state = self.state.lock();
+        if state
+            .details_fetch
+            .as_ref()
+            .is_some_and(|(fetching, _)| fetching == tag_name)
+        {
+            state.details_fetch = None;
+        }
+        if let Some(details) = details {
+            state.release_details.insert(tag_name.to_string(), details);
+        }
+    }
+
+    // Cancel the details fetch unless it's for the given release
+    pub fn cancel_details_fetch(&self, keep: Option<&str>) {
+        let mut state = self.state.lock();
+        if let Some((fetching, token)) = &state.details_fetch {
+            if keep != Some(fetching.as_str()) {
+                token.cancel();
+                state.details_fetch = None;
+            }
+        }
+    }
+
+    // Returns false if a lookup for this tag is already running
+    pub fn begin_tag_lookup(&self, tag_name: &str) -> bool {
+        self.state.lock().tag_lookups.insert(tag_name.to_string())
+    }
+
+    pub fn finish_tag_lookup(&self, tag_name: &str, tag: Option<&Tag>) {
+        let mut state = self.state.lock();
+        state.tag_lookups.remove(tag_name);
+        if let Some(releases_and_tags) = state.nextui_releases_and_tags.as_mut() {
+            for release_and_tag in releases_and_tags
+                .iter_mut()
+                .filter(|release_and_tag| release_and_tag.release.tag_name == tag_name)
+            {
+                release_and_tag.tag = tag.cloned();
+            }
+        }
+    }
+
+    pub fn take_release_list_focus(&self) -> Option<usize> {
+        self.state.lock().release_list_focus.take()
+    }
+
+    // Move the update pipeline on to its next state, showing the operation
+    pub fn advance(&self, next: UpdatePhase, operation: &str) -> Result<()> {
+        let mut state = self.state.lock();
+        state.transition(next)?;
         state.current_operation = Some(operation.to_string());
-        state.progress = Some(Progress::Determinate(0.0));
+        state.progress = Some(match state.update_phase {
+            UpdatePhase::Downloading | UpdatePhase::Verifying | UpdatePhase::Extracting => {
+                Progress::Determinate(0.0, None)
+            }
+            _ => Progress::Indeterminate,
+        });
+        Ok(())
     }
 
     pub fn update_progress(&self, progress: f32) {
-        self.state.lock().progress = Some(Progress::Determinate(progress));
+        self.state
+            .lock()
+            .show_progress(Some(Progress::Determinate(progress, None)));
+    }
+
+    pub fn update_transfer(&self, transfer: Transfer) {
+        let progress = Progress::Determinate(transfer.fraction(), Some(transfer));
+        self.state.lock().show_progress(Some(progress));
     }
 
     pub fn finish_operation(&self) {
         let mut state = self.state.lock();
+        // Stopped partway through writing the update, which isn't a clean
+        // finish even when it was cancelled
+        let next = if state.update_phase.is_writing() {
+            UpdatePhase::Failed {
+                error: tr!("Update stopped partway, install it again to finish it").to_string(),
+            }
+        } else {
+            UpdatePhase::Idle
+        };
+        if let Err(err) = state.transition(next) {
+            warn!("{err}");
+        }
         state.current_operation = None;
         state.progress = None;
     }
 
     pub fn set_operation_failed(&self, error_msg: &str) {
         let mut state = self.state.lock();
-        state.current_operation = None;
-        state.error = Some(error_msg.to_string());
-        state.progress = None;
+        let failed = UpdatePhase::Failed {
+            error: error_msg.to_string(),
+        };
+        if let Err(err) = state.transition(failed) {
+            warn!("{err}");
+            // Still shown, just not as the update's failure
+            state.current_operation = None;
+            state.progress = None;
+            state.error = Some(error_msg.to_string());
+        }
     }
 
     pub fn clear_error(&self) {
-        self.state.lock().error = None;
+        self.state.lock().clear_error();
     }
 
+    // Open a submenu on top of the current one
     pub fn enter_submenu(&self, submenu: Submenu) {
         let mut state = self.state.lock();
+        let previous = state.submenu;
+        state.submenu_stack.push(previous);
+        let previous_token = std::mem::take(&mut state.screen_token);
+        state.screen_token_stack.push(previous_token);
+        state.submenu = submenu;
+        state.notice = None;
+        state.hint = None;
+    }
+
+    // Go back to the submenu the current one was opened from, cancelling
+    // anything the current one started
+    pub fn leave_submenu(&self) {
+        let mut state = self.state.lock();
+        state.submenu = state.submenu_stack.pop().unwrap_or(Submenu::NextUI);
+        state.screen_token.cancel();
+        state.screen_token = state.screen_token_stack.pop().unwrap_or_default();
+        state.notice = None;
+        state.hint = None;
+    }
+
+    // Switch to a submenu, forgetting how we got here
+    pub fn reset_submenu(&self, submenu: Submenu) {
+        let mut state = self.state.lock();
+        state.submenu_stack.clear();
+        state.cancel_screen_tokens();
         state.submenu = submenu;
+        state.notice = None;
         state.hint = None;
     }
 
-    // Access to inner Arc<Mutex<AppState>> when necessary
-    pub fn inner(&self) -> Arc<Mutex<AppState>> {
-        Arc::clone(&self.state)
+    // Tag name of the installed version, if it's one of the known releases
+    pub fn installed_tag_name(&self) -> Option<String> {
+        self.installed_release().map(|release| release.tag_name)
+    }
+
+    // Whether the latest release isn't what's installed, if both are known.
+    // Never while a version is pinned or the latest one is ignored.
+    pub fn update_available(&self) -> Option<bool> {
+        let state = self.state.lock();
+        if state.config.pinned_version.is_some() {
+            return Some(false);
+        }
+        let current_version = state.current_version.as_ref()?;
+        let tag = state.nextui_tag.as_ref()?;
+        if state.config.ignored(&tag.name) {
+            return Some(false);
+        }
+        Some(!tag.commit.sha.starts_with(current_version.as_str()))
+    }
+
+    // The installed version's release, if it's one of the known ones
+    pub fn installed_release(&self) -> Option<Release> {
+        let state = self.state.lock();
+        installed_release(
+            state.current_version.as_ref(),
+            state.nextui_releases_and_tags.as_ref(),
+        )
+        .cloned()
+    }
+
+    // The lock might be held by the panicking thread, so don't wait on it forever
+    pub fn crash_summary(&self) -> String {
+        let Some(state) = self.state.try_lock_for(Duration::from_millis(100)) else {
+            return "(state locked)".to_string();
+        };
+
+        let mut summary = String::new();
+        let _ = writeln!(summary, "submenu: {:?}", state.submenu);
+        let _ = writeln!(summary, "update phase: {:?}", state.update_phase);
+        let _ = writeln!(summary, "current_version: {:?}", state.current_version);
+        let _ = writeln!(summary, "current_operation: {:?}", state.current_operation);
+        let _ = writeln!(summary, "error: {:?}", state.error());
+        let _ = writeln!(
+            summary,
+            "releases: {:?}",
+            state.nextui_releases_and_tags.as_ref().map(Vec::len)
+        );
+        let _ = writeln!(
+            summary,
+            "release_selection: menu={} confirmed={} open={} index={:?}",
+            state.release_selection_menu,
+            state.release_selection_confirmed,
+            state.release_selection_open,
+            state.nextui_releases_and_tags_index
+        );
+        let _ = write!(summary, "source: {}", state.config.source().repo);
+        summary
+    }
+
+    pub fn report_crash(&self, message: &str) {
+        if let Some(mut state) = self.state.try_lock_for(Duration::from_millis(100)) {
+            state.init_status = None;
+            state.current_operation = None;
+            state.progress = None;
+            state.crash = Some(message.to_string());
+            state.operation_token.cancel();
+            state.submenu_stack.clear();
+            state.cancel_screen_tokens();
+            state.submenu = Submenu::Crashed;
+            state.hint = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errors_wait_for_the_update_to_stop() {
+        let app_state = AppStateManager::new();
+        app_state
+            .advance(UpdatePhase::CheckingRelease, "Checking")
+            .unwrap();
+        app_state.set_error(Some("Failed to save settings".to_string()));
+        assert_eq!(app_state.error(), None);
+
+        app_state.finish_operation();
+        assert_eq!(
+            app_state.error().as_deref(),
+            Some("Failed to save settings")
+        );
+    }
+
+    #[test]
+    fn queued_errors_are_shown_after_the_failure() {
+        let app_state = AppStateManager::new();
+        app_state
+            .advance(UpdatePhase::CheckingRelease, "Checking")
+            .unwrap();
+        app_state.set_error(Some("Failed to save settings".to_string()));
+        app_state.set_operation_failed("Download failed");
+        assert_eq!(app_state.error().as_deref(), Some("Download failed"));
+
+        app_state.clear_error();
+        assert_eq!(
+            app_state.error().as_deref(),
+            Some("Failed to save settings")
+        );
+        app_state.clear_error();
+        assert_eq!(app_state.error(), None);
+    }
+
+    #[test]
+    fn stopping_while_extracting_fails_the_update() {
+        let app_state = AppStateManager::new();
+        for phase in [
+            UpdatePhase::CheckingRelease,
+            UpdatePhase::Downloading,
+            UpdatePhase::Verifying,
+            UpdatePhase::Extracting,
+        ] {
+            app_state.advance(phase, "Updating").unwrap();
+        }
+        app_state.operation_token().cancel();
+        app_state.finish_operation();
+
+        assert!(!app_state.update_phase().is_active());
+        assert!(app_state.error().is_some());
     }
 }
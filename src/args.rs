@@ -0,0 +1,146 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+
+use log::warn;
+
+use crate::app_state::{AppStateManager, Submenu};
+use crate::history;
+
+// Screen to open at startup, for launcher entries that go straight to it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Screen {
+    Versions,
+    Settings,
+    History,
+    Menu,
+    Logs,
+}
+
+impl Screen {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "versions" => Some(Screen::Versions),
+            "settings" => Some(Screen::Settings),
+            "history" => Some(Screen::History),
+            "menu" => Some(Screen::Menu),
+            "logs" => Some(Screen::Logs),
+            _ => None,
+        }
+    }
+
+    // Opened on top of the main screen, so backing out of it ends up there
+    pub fn open(self, app_state: &AppStateManager) {
+        match self {
+            Screen::Versions => app_state.set_release_selection_menu(true),
+            Screen::Settings => app_state.enter_submenu(Submenu::Settings),
+            Screen::History => {
+                app_state.set_history(history::load());
+                app_state.enter_submenu(Submenu::History);
+            }
+            Screen::Menu => app_state.enter_submenu(Submenu::Menu),
+            Screen::Logs => app_state.enter_submenu(Submenu::Logs),
+        }
+    }
+}
+
+// Run without the UI, printing the result, e.g. from a provisioning script
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Command {
+    // Look for a new release
+    Check,
+    // --check-only: the same, printing nothing, for boot or cron hooks that
+    // only want the status file updated
+    CheckOnly,
+    // Install the latest release, or --tag
+    Install,
+}
+
+// Command line flags, e.g. added to the binary's line in launch.sh
+#[derive(Default, Debug)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct Args {
+    // Don't look for a new updater at startup, same as the setting
+    pub no_self_update: bool,
+    // --screen versions|settings|history|menu|logs
+    pub screen: Option<Screen>,
+    // --sdcard <dir>, a directory to use as the SD card, for running on a
+    // desktop
+    pub sdcard: Option<String>,
+    // --simulate, see simulate.rs
+    pub simulate: bool,
+    // check|install|--check-only, see headless.rs
+    pub command: Option<Command>,
+    // Print the command's result as JSON
+    pub json: bool,
+    // Full update instead of a quick one, for commands
+    pub full: bool,
+    // Release to install instead of the latest one
+    pub tag: Option<String>,
+    // Log debug messages too, same as the setting
+    pub verbose: bool,
+    // Logged once logging has started, which needs --sdcard to know where
+    warnings: Vec<String>,
+}
+
+static SCREEN_OPENED: AtomicBool = AtomicBool::new(false);
+
+static ARGS: OnceLock<Args> = OnceLock::new();
+
+pub fn parse() {
+    let mut args = Args::default();
+    let mut flags = std::env::args().skip(1);
+    while let Some(arg) = flags.next() {
+        match arg.as_str() {
+            "--no-self-update" => args.no_self_update = true,
+            "--simulate" => args.simulate = true,
+            "--json" => args.json = true,
+            "--check-only" => args.command = Some(Command::CheckOnly),
+            "--full" => args.full = true,
+            "--verbose" => args.verbose = true,
+            "check" if args.command.is_none() => args.command = Some(Command::Check),
+            "install" if args.command.is_none() => args.command = Some(Command::Install),
+            "--tag" => match flags.next() {
+                Some(tag) => args.tag = Some(tag),
+                None => args.warnings.push("--tag needs a release".to_string()),
+            },
+            "--screen" => {
+                let name = flags.next().unwrap_or_default();
+                args.screen = Screen::parse(&name);
+                if args.screen.is_none() {
+                    args.warnings
+                        .push(format!("Ignoring unknown screen {name:?}"));
+                }
+            }
+            "--sdcard" => match flags.next() {
+                Some(dir) => args.sdcard = Some(dir),
+                None => args.warnings.push("--sdcard needs a directory".to_string()),
+            },
+            _ => args
+                .warnings
+                .push(format!("Ignoring unknown argument {arg:?}")),
+        }
+    }
+    let _ = ARGS.set(args);
+}
+
+pub fn get() -> &'static Args {
+    ARGS.get_or_init(Args::default)
+}
+
+// Running a command instead of the UI
+pub fn headless() -> bool {
+    get().command.is_some()
+}
+
+pub fn log_warnings() {
+    for warning in &get().warnings {
+        warn!("{warning}");
+    }
+}
+
+// The --screen to open, only the first time this is called
+pub fn take_screen() -> Option<Screen> {
+    get()
+        .screen
+        .filter(|_| !SCREEN_OPENED.swap(true, Ordering::Relaxed))
+}
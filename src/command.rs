@@ -0,0 +1,94 @@
+use std::thread;
+
+use crate::app_state::{AppStateManager, Submenu};
+use crate::diagnostics;
+use crate::history;
+use crate::i18n::tr;
+use crate::update::do_nextui_release_check;
+
+// Something the command palette and keyboard shortcuts can do. Meant for
+// testing on desktop; the controller never triggers these directly.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Command {
+    Refresh,
+    Logs,
+    Settings,
+    Menu,
+    History,
+    ExportDiagnostics,
+    Quit,
+}
+
+pub const COMMANDS: [Command; 7] = [
+    Command::Refresh,
+    Command::Logs,
+    Command::Settings,
+    Command::Menu,
+    Command::History,
+    Command::ExportDiagnostics,
+    Command::Quit,
+];
+
+impl Command {
+    pub fn label(self) -> &'static str {
+        match self {
+            Command::Refresh => tr!("Refresh releases"),
+            Command::Logs => tr!("Show logs"),
+            Command::Settings => tr!("Settings"),
+            Command::Menu => tr!("Menu"),
+            Command::History => tr!("Update history"),
+            Command::ExportDiagnostics => tr!("Export diagnostics"),
+            Command::Quit => tr!("Quit"),
+        }
+    }
+
+    // Key that runs the command outside of the palette
+    pub fn shortcut(self) -> Option<char> {
+        match self {
+            Command::Refresh => Some('R'),
+            Command::Logs => Some('L'),
+            Command::Settings => Some('S'),
+            Command::History => Some('H'),
+            Command::Menu | Command::ExportDiagnostics | Command::Quit => None,
+        }
+    }
+
+    pub fn run(self, app_state: &'static AppStateManager) {
+        // Commands picked from the palette replace it
+        if app_state.submenu() == Submenu::CommandPalette {
+            app_state.leave_submenu();
+        }
+
+        match self {
+            Command::Refresh => {
+                if !app_state.update_phase().is_active() {
+                    app_state.clear_error();
+                    thread::spawn(move || do_nextui_release_check(app_state));
+                }
+            }
+            Command::Logs => open(app_state, Submenu::Logs),
+            Command::Settings => open(app_state, Submenu::Settings),
+            Command::Menu => open(app_state, Submenu::Menu),
+            Command::History => {
+                app_state.set_history(history::load());
+                open(app_state, Submenu::History);
+            }
+            Command::ExportDiagnostics => {
+                thread::spawn(move || match diagnostics::export() {
+                    Ok(path) => {
+                        app_state.set_notice(Some(tr!("Saved {path}", path = path.display())));
+                    }
+                    Err(err) => app_state.set_error(Some(tr!("Export failed: {err}", err))),
+                });
+            }
+            Command::Quit => app_state.set_should_quit(true),
+        }
+    }
+}
+
+// Don't stack the same screen twice when a shortcut is pressed on it
+fn open(app_state: &AppStateManager, submenu: Submenu) {
+    if app_state.submenu() != submenu {
+        app_state.enter_submenu(submenu);
+    }
+}
@@ -0,0 +1,431 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use log::warn;
+use parking_lot::Mutex;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{sdcard_root, Result, UPDATER_DATA_DIR};
+
+const CONFIG_FILE: &str = "config.json";
+// Download limits offered in the settings, in KB per second
+const DOWNLOAD_LIMITS: [u64; 5] = [256, 512, 1024, 2048, 5120];
+
+// Where a source's repository is hosted
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Forge {
+    #[default]
+    GitHub,
+    // A Gitea or Forgejo server, e.g. "https://codeberg.org"
+    Gitea {
+        url: String,
+    },
+    // No forge at all, just a JSON file listing the releases
+    Manifest {
+        url: String,
+    },
+}
+
+// An update source: a repository publishing NextUI-compatible releases
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Source {
+    pub name: String,
+    pub repo: String,
+    #[serde(default)]
+    pub forge: Forge,
+    // Regex matched against asset names to pick the quick/full update asset
+    pub quick_asset: String,
+    pub full_asset: String,
+    // Path prefixes extracted by a quick update
+    pub quick_paths: Vec<String>,
+    // Minisign public key (base64) that "<asset>.minisig" signatures are checked against
+    #[serde(default)]
+    pub public_key: Option<String>,
+    // Download URLs tried in order before the forge, with {repo}, {tag} and
+    // {asset} filled in
+    #[serde(default)]
+    pub mirrors: Vec<String>,
+    // Cautions for downgrading past versions that changed something, e.g. a
+    // settings format
+    #[serde(default)]
+    pub migration_notes: Vec<MigrationNote>,
+}
+
+// The "owner/repo" form GitHub and Gitea use, e.g. "LoveRetro/NextUI"
+pub fn is_repo_name(repo: &str) -> bool {
+    let valid_part = |part: &str| {
+        !part.is_empty()
+            && part
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    };
+    repo.split_once('/')
+        .is_some_and(|(owner, name)| valid_part(owner) && valid_part(name))
+}
+
+// Shown when downgrading from `version` or later to a version before it
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MigrationNote {
+    pub version: String,
+    pub note: String,
+}
+
+impl Default for Source {
+    fn default() -> Self {
+        Self {
+            name: "NextUI".to_string(),
+            repo: "LoveRetro/NextUI".to_string(),
+            forge: Forge::GitHub,
+            quick_asset: "base".to_string(),
+            full_asset: "all".to_string(),
+            quick_paths: vec!["MinUI.zip".to_string(), "trimui".to_string()],
+            // NextUI doesn't publish a signing key yet
+            public_key: None,
+            mirrors: vec![],
+            migration_notes: vec![],
+        }
+    }
+}
+
+impl Source {
+    // The pattern for the quick or full update asset. Compiled once per
+    // pattern, since the version selector matches it every frame.
+    pub fn asset_pattern(&self, full: bool) -> std::result::Result<Regex, regex::Error> {
+        static PATTERNS: Mutex<BTreeMap<String, Regex>> = Mutex::new(BTreeMap::new());
+
+        let pattern = if full {
+            &self.full_asset
+        } else {
+            &self.quick_asset
+        };
+        let mut patterns = PATTERNS.lock();
+        if let Some(asset_re) = patterns.get(pattern) {
+            return Ok(asset_re.clone());
+        }
+        let asset_re = Regex::new(pattern)?;
+        patterns.insert(pattern.clone(), asset_re.clone());
+        Ok(asset_re)
+    }
+}
+
+// Which releases are offered: stable ones only, or prereleases too
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Channel {
+    #[default]
+    Stable,
+    Beta,
+}
+
+impl Channel {
+    // Cycled through by the settings button
+    pub fn next(self) -> Self {
+        match self {
+            Channel::Stable => Channel::Beta,
+            Channel::Beta => Channel::Stable,
+        }
+    }
+
+    pub fn includes_prereleases(self) -> bool {
+        self == Channel::Beta
+    }
+}
+
+// Size of text and buttons, for readability on small screens
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TextScale {
+    Small,
+    #[default]
+    Normal,
+    Large,
+}
+
+impl TextScale {
+    // Cycled through by the settings button
+    pub fn next(self) -> Self {
+        match self {
+            TextScale::Small => TextScale::Normal,
+            TextScale::Normal => TextScale::Large,
+            TextScale::Large => TextScale::Small,
+        }
+    }
+}
+
+// Controller button for each action, by the SDL name the controller test
+// shows, e.g. "b" or "leftshoulder". Devices label buttons differently: the
+// one labeled A on a TrimUI Brick is SDL's "b".
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(default)]
+pub struct ButtonMapping {
+    pub select: String,
+    pub back: String,
+    // Opens the version selector
+    pub versions: String,
+    pub menu: String,
+    pub page_up: String,
+    pub page_down: String,
+}
+
+impl Default for ButtonMapping {
+    fn default() -> Self {
+        Self {
+            select: "b".to_string(),
+            back: "a".to_string(),
+            versions: "y".to_string(),
+            menu: "start".to_string(),
+            page_up: "leftshoulder".to_string(),
+            page_down: "rightshoulder".to_string(),
+        }
+    }
+}
+
+// Settings from older versions, mapped onto the ones that replaced them
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct LegacyConfig {
+    // Replaced by the beta channel
+    include_prereleases: bool,
+    channel: Option<Channel>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct Config {
+    pub sources: Vec<Source>,
+    // Index into `sources` of the source currently tracked
+    pub selected_source: usize,
+    // Quiet mode silences sounds, rumble and LEDs regardless of their own settings
+    pub quiet_mode: bool,
+    pub sounds: bool,
+    // Clicks when moving between and pressing buttons, if sounds are on
+    pub navigation_sounds: bool,
+    pub rumble: bool,
+    pub leds: bool,
+    // Refuse to install assets without a valid signature
+    pub require_signatures: bool,
+    // Download updates now, but install them the next time the updater starts
+    pub defer_updates: bool,
+    // The beta channel shows prereleases in the version selector and offers
+    // them as updates
+    pub channel: Channel,
+    // Offer new releases right away, even if they're only rolled out to some devices
+    pub skip_rollout: bool,
+    // Quick update to new stable releases as soon as the updater starts
+    pub auto_update: bool,
+    // Tag of a version to stay on. Newer releases aren't announced while it's set.
+    pub pinned_version: Option<String>,
+    // Tags of releases not to announce, e.g. ones known to be bad on this device
+    pub ignored_versions: Vec<String>,
+    // Show progress as large centered text instead of the bar
+    pub big_progress: bool,
+    pub text_scale: TextScale,
+    // Brighter colors and focus and state markers that don't rely on color
+    pub high_contrast: bool,
+    // Look for a new updater every time it starts
+    pub self_update: bool,
+    // Download only the files that changed when the server allows it
+    pub delta_updates: bool,
+    // Start downloading while an update is being confirmed
+    pub prefetch: bool,
+    // Keep downloaded assets on the SD card, so reinstalling or retrying
+    // doesn't download them again
+    pub download_cache: bool,
+    // Most the cache may hold, in MB. The least recently used downloads are
+    // removed to make room.
+    pub cache_limit: u64,
+    // Cap on download speed in KB per second, e.g. to leave room for others
+    // on the same connection
+    pub download_limit: Option<u64>,
+    // Proxy for all requests, e.g. "socks5://host:1080". The HTTPS_PROXY and
+    // ALL_PROXY environment variables are used if unset.
+    pub proxy: Option<String>,
+    // GitHub personal access token sent with API requests, for a higher rate
+    // limit than anonymous requests get
+    pub github_token: Option<String>,
+    // Accept any TLS certificate, e.g. behind an intercepting proxy. Not
+    // exposed in the settings screen.
+    pub insecure_tls: bool,
+    // Seconds to wait for a connection, and for the next bytes of a response
+    // before giving up on it as stalled. 0 waits forever.
+    pub connect_timeout: u64,
+    pub stall_timeout: u64,
+    // Pages of 100 releases and tags fetched from GitHub, each one an API request
+    pub max_pages: usize,
+    // URL that gets a JSON POST when an update finishes or fails
+    pub webhook: Option<String>,
+    // Language code used for dates and text, e.g. "de". NextUI's language
+    // setting if unset.
+    pub language: Option<String>,
+    pub buttons: ButtonMapping,
+    // Shows the developer screen in the menu. Not exposed in the settings screen.
+    pub developer: bool,
+    // Log debug messages too, e.g. response headers and extracted files. Not
+    // exposed in the settings screen.
+    pub verbose_logging: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            sources: vec![Source::default()],
+            selected_source: 0,
+            quiet_mode: false,
+            sounds: true,
+            navigation_sounds: true,
+            rumble: true,
+            leds: true,
+            require_signatures: false,
+            defer_updates: false,
+            channel: Channel::Stable,
+            skip_rollout: false,
+            auto_update: false,
+            pinned_version: None,
+            ignored_versions: vec![],
+            big_progress: false,
+            text_scale: TextScale::Normal,
+            high_contrast: false,
+            self_update: true,
+            delta_updates: true,
+            prefetch: false,
+            download_cache: true,
+            cache_limit: 1024,
+            download_limit: None,
+            max_pages: 3,
+            proxy: None,
+            github_token: None,
+            webhook: None,
+            insecure_tls: false,
+            connect_timeout: 15,
+            stall_timeout: 30,
+            language: None,
+            buttons: ButtonMapping::default(),
+            developer: false,
+            verbose_logging: false,
+        }
+    }
+}
+
+impl Config {
+    pub fn path() -> PathBuf {
+        PathBuf::from(sdcard_root())
+            .join(UPDATER_DATA_DIR)
+            .join(CONFIG_FILE)
+    }
+
+    // Load the config file, writing out the defaults if there is none yet so
+    // users have something to edit
+    pub fn load() -> Self {
+        let path = Self::path();
+        if let Ok(contents) = std::fs::read_to_string(&path) {
+            Self::parse(&contents).unwrap_or_else(|err| {
+                warn!("Failed to parse {}: {err}", path.display());
+                Self::default()
+            })
+        } else {
+            let config = Self::default();
+            if let Err(err) = config.save() {
+                warn!("Failed to write default config: {err}");
+            }
+            config
+        }
+    }
+
+    fn parse(contents: &str) -> serde_json::Result<Self> {
+        let mut config: Self = serde_json::from_str(contents)?;
+        let legacy: LegacyConfig = serde_json::from_str(contents)?;
+        if legacy.include_prereleases && legacy.channel.is_none() {
+            config.channel = Channel::Beta;
+        }
+        Ok(config)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = Self::path();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    // The currently tracked source, falling back to the first configured one
+    // (or the official repo if the list is empty)
+    pub fn source(&self) -> Source {
+        self.sources
+            .get(self.selected_source)
+            .or(self.sources.first())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    // Track `repo`, adding a source for it with the official asset names if
+    // there's none yet, e.g. for a fork
+    pub fn select_repo(&mut self, repo: &str) {
+        let index = self
+            .sources
+            .iter()
+            .position(|source| source.repo.eq_ignore_ascii_case(repo));
+        self.selected_source = index.unwrap_or_else(|| {
+            self.sources.push(Source {
+                name: repo.to_string(),
+                repo: repo.to_string(),
+                ..Source::default()
+            });
+            self.sources.len() - 1
+        });
+    }
+
+    // Cycled through by the settings button: no limit, then each limit
+    // from slowest to fastest
+    pub fn next_download_limit(&self) -> Option<u64> {
+        match self.download_limit {
+            None => DOWNLOAD_LIMITS.first().copied(),
+            Some(limit) => DOWNLOAD_LIMITS.into_iter().find(|next| *next > limit),
+        }
+    }
+
+    pub fn ignored(&self, tag_name: &str) -> bool {
+        self.ignored_versions
+            .iter()
+            .any(|ignored| ignored == tag_name)
+    }
+
+    pub fn sounds_enabled(&self) -> bool {
+        self.sounds && !self.quiet_mode
+    }
+
+    pub fn navigation_sounds_enabled(&self) -> bool {
+        self.navigation_sounds && self.sounds_enabled()
+    }
+
+    pub fn rumble_enabled(&self) -> bool {
+        self.rumble && !self.quiet_mode
+    }
+
+    pub fn leds_enabled(&self) -> bool {
+        self.leds && !self.quiet_mode
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prerelease_setting_moves_to_the_beta_channel() {
+        let config = Config::parse(r#"{"include_prereleases": true}"#).unwrap();
+        assert_eq!(config.channel, Channel::Beta);
+
+        let config = Config::parse(r#"{"include_prereleases": false}"#).unwrap();
+        assert_eq!(config.channel, Channel::Stable);
+
+        // A channel picked since wins
+        let config =
+            Config::parse(r#"{"include_prereleases": true, "channel": "stable"}"#).unwrap();
+        assert_eq!(config.channel, Channel::Stable);
+    }
+}
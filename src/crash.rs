@@ -0,0 +1,56 @@
+use std::backtrace::Backtrace;
+use std::panic::{self, PanicHookInfo};
+use std::path::PathBuf;
+use std::thread;
+
+use log::error;
+
+use crate::app_state::AppStateManager;
+use crate::{date, sdcard_root, Result, UPDATER_DATA_DIR};
+
+fn panic_message(info: &PanicHookInfo) -> String {
+    let payload = info
+        .payload()
+        .downcast_ref::<&str>()
+        .map(ToString::to_string)
+        .or_else(|| info.payload().downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string());
+    match info.location() {
+        Some(location) => format!("{payload} at {location}"),
+        None => payload,
+    }
+}
+
+fn write_report(report: &str) -> Result<PathBuf> {
+    let dir = PathBuf::from(sdcard_root()).join(UPDATER_DATA_DIR);
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(format!("crash-{}.txt", date::now()));
+    std::fs::write(&path, report)?;
+    Ok(path)
+}
+
+// Write a crash report to the SD card on panic, and tell the UI about it so it
+// can show an error screen instead of disappearing. A panic in the UI thread
+// itself ends the updater instead, see main.
+pub fn install_panic_hook(app_state: &'static AppStateManager) {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let message = panic_message(info);
+        let report = format!(
+            "NextUI Updater {} crashed\n\nThread: {}\nPanic: {message}\n\nState:\n{}\n\nBacktrace:\n{}\n",
+            env!("CARGO_PKG_VERSION"),
+            thread::current().name().unwrap_or("unnamed"),
+            app_state.crash_summary(),
+            Backtrace::force_capture(),
+        );
+        error!("{report}");
+
+        let saved = match write_report(&report) {
+            Ok(path) => format!("Crash report saved to {}", path.display()),
+            Err(err) => format!("Failed to save crash report: {err}"),
+        };
+        app_state.report_crash(&format!("{message}\n{saved}"));
+
+        default_hook(info);
+    }));
+}
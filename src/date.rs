@@ -0,0 +1,302 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Date formatting strings for a language
+struct Locale {
+    code: &'static str,
+    months: [&'static str; 12],
+    // {d} = day, {m} = month name, {y} = year
+    date: &'static str,
+    just_now: &'static str,
+    // {n} = count, {u} = unit
+    ago: &'static str,
+    // (singular, plural) for minute, hour, day, week, month, year
+    units: [(&'static str, &'static str); 6],
+}
+
+const LOCALES: [Locale; 6] = [
+    Locale {
+        code: "en",
+        months: [
+            "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+        ],
+        date: "{m} {d}, {y}",
+        just_now: "just now",
+        ago: "{n} {u} ago",
+        units: [
+            ("minute", "minutes"),
+            ("hour", "hours"),
+            ("day", "days"),
+            ("week", "weeks"),
+            ("month", "months"),
+            ("year", "years"),
+        ],
+    },
+    Locale {
+        code: "de",
+        months: [
+            "Januar", "Februar", "März", "April", "Mai", "Juni", "Juli", "August", "September",
+            "Oktober", "November", "Dezember",
+        ],
+        date: "{d}. {m} {y}",
+        just_now: "gerade eben",
+        ago: "vor {n} {u}",
+        units: [
+            ("Minute", "Minuten"),
+            ("Stunde", "Stunden"),
+            ("Tag", "Tagen"),
+            ("Woche", "Wochen"),
+            ("Monat", "Monaten"),
+            ("Jahr", "Jahren"),
+        ],
+    },
+    Locale {
+        code: "fr",
+        months: [
+            "janvier", "février", "mars", "avril", "mai", "juin", "juillet", "août", "septembre",
+            "octobre", "novembre", "décembre",
+        ],
+        date: "{d} {m} {y}",
+        just_now: "à l'instant",
+        ago: "il y a {n} {u}",
+        units: [
+            ("minute", "minutes"),
+            ("heure", "heures"),
+            ("jour", "jours"),
+            ("semaine", "semaines"),
+            ("mois", "mois"),
+            ("an", "ans"),
+        ],
+    },
+    Locale {
+        code: "es",
+        months: [
+            "enero", "febrero", "marzo", "abril", "mayo", "junio", "julio", "agosto",
+            "septiembre", "octubre", "noviembre", "diciembre",
+        ],
+        date: "{d} de {m} de {y}",
+        just_now: "justo ahora",
+        ago: "hace {n} {u}",
+        units: [
+            ("minuto", "minutos"),
+            ("hora", "horas"),
+            ("día", "días"),
+            ("semana", "semanas"),
+            ("mes", "meses"),
+            ("año", "años"),
+        ],
+    },
+    Locale {
+        code: "it",
+        months: [
+            "gennaio", "febbraio", "marzo", "aprile", "maggio", "giugno", "luglio", "agosto",
+            "settembre", "ottobre", "novembre", "dicembre",
+        ],
+        date: "{d} {m} {y}",
+        just_now: "proprio ora",
+        ago: "{n} {u} fa",
+        units: [
+            ("minuto", "minuti"),
+            ("ora", "ore"),
+            ("giorno", "giorni"),
+            ("settimana", "settimane"),
+            ("mese", "mesi"),
+            ("anno", "anni"),
+        ],
+    },
+    Locale {
+        code: "pt",
+        months: [
+            "janeiro", "fevereiro", "março", "abril", "maio", "junho", "julho", "agosto",
+            "setembro", "outubro", "novembro", "dezembro",
+        ],
+        date: "{d} de {m} de {y}",
+        just_now: "agora mesmo",
+        ago: "há {n} {u}",
+        units: [
+            ("minuto", "minutos"),
+            ("hora", "horas"),
+            ("dia", "dias"),
+            ("semana", "semanas"),
+            ("mês", "meses"),
+            ("ano", "anos"),
+        ],
+    },
+];
+
+fn locale(language: &str) -> &'static Locale {
+    LOCALES
+        .iter()
+        .find(|l| language.starts_with(l.code))
+        .unwrap_or(&LOCALES[0])
+}
+
+// Current time as seconds since the unix epoch
+pub fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs().try_into().unwrap_or(i64::MAX))
+}
+
+// Days since the unix epoch for a civil date (Howard Hinnant's algorithm)
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let mp = (i64::from(month) + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+// Civil date (year, month, day) for a number of days since the unix epoch
+#[allow(clippy::cast_sign_loss)]
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let doe = days - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+// Parse a GitHub timestamp ("2025-03-02T12:34:56Z") into seconds since the unix epoch
+pub fn parse_timestamp(timestamp: &str) -> Option<i64> {
+    let (date, time) = timestamp.trim_end_matches('Z').split_once('T')?;
+
+    let mut date = date.split('-');
+    let year: i64 = date.next()?.parse().ok()?;
+    let month: u32 = date.next()?.parse().ok()?;
+    let day: u32 = date.next()?.parse().ok()?;
+
+    let mut time = time.split(':');
+    let hours: i64 = time.next()?.parse().ok()?;
+    let minutes: i64 = time.next()?.parse().ok()?;
+    let seconds: i64 = time.next().unwrap_or("0").parse().ok()?;
+
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    Some(days_from_civil(year, month, day) * 86400 + hours * 3600 + minutes * 60 + seconds)
+}
+
+// Localized absolute date, e.g. "Mar 2, 2025"
+pub fn format_date(timestamp: i64, language: &str) -> String {
+    let locale = locale(language);
+    let (year, month, day) = civil_from_days(timestamp.div_euclid(86400));
+    locale
+        .date
+        .replace("{d}", &day.to_string())
+        .replace("{m}", locale.months[month as usize - 1])
+        .replace("{y}", &year.to_string())
+}
+
+// Localized relative time, e.g. "3 weeks ago". Returns None if the clock is
+// behind the timestamp, which happens on devices without a set clock.
+pub fn format_relative(timestamp: i64, language: &str) -> Option<String> {
+    let locale = locale(language);
+    let elapsed = now() - timestamp;
+    if elapsed < 0 {
+        return None;
+    }
+
+    let minutes = elapsed / 60;
+    let hours = minutes / 60;
+    let days = hours / 24;
+    let (count, unit) = if minutes < 1 {
+        return Some(locale.just_now.to_string());
+    } else if hours < 1 {
+        (minutes, locale.units[0])
+    } else if days < 1 {
+        (hours, locale.units[1])
+    } else if days < 7 {
+        (days, locale.units[2])
+    } else if days < 30 {
+        (days / 7, locale.units[3])
+    } else if days < 365 {
+        (days / 30, locale.units[4])
+    } else {
+        (days / 365, locale.units[5])
+    };
+
+    Some(
+        locale
+            .ago
+            .replace("{n}", &count.to_string())
+            .replace("{u}", if count == 1 { unit.0 } else { unit.1 }),
+    )
+}
+
+// Absolute date followed by the relative time when available,
+// e.g. "Mar 2, 2025 (3 weeks ago)"
+pub fn format_date_relative(timestamp: i64, language: &str) -> String {
+    let date = format_date(timestamp, language);
+    match format_relative(timestamp, language) {
+        Some(relative) => format!("{date} ({relative})"),
+        None => date,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MARCH_2_2025: i64 = 1_740_918_896;
+
+    #[test]
+    fn github_timestamps_are_parsed() {
+        assert_eq!(parse_timestamp("2025-03-02T12:34:56Z"), Some(MARCH_2_2025));
+        assert_eq!(parse_timestamp("1970-01-01T00:00:00Z"), Some(0));
+        assert_eq!(
+            parse_timestamp("2025-03-02T12:34Z"),
+            Some(MARCH_2_2025 - 56)
+        );
+        assert_eq!(parse_timestamp("2025-13-02T12:34:56Z"), None);
+        assert_eq!(parse_timestamp("2025-03-02"), None);
+        assert_eq!(parse_timestamp("yesterday"), None);
+    }
+
+    #[test]
+    fn civil_dates_round_trip() {
+        for (year, month, day) in [(1970, 1, 1), (1969, 12, 31), (2024, 2, 29), (2025, 3, 1)] {
+            assert_eq!(
+                civil_from_days(days_from_civil(year, month, day)),
+                (year, month, day)
+            );
+        }
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+    }
+
+    #[test]
+    fn dates_are_formatted_for_the_language() {
+        assert_eq!(format_date(MARCH_2_2025, "en"), "Mar 2, 2025");
+        assert_eq!(format_date(MARCH_2_2025, "de"), "2. März 2025");
+        assert_eq!(format_date(MARCH_2_2025, "pt_BR"), "2 de março de 2025");
+        // Unknown languages fall back to English
+        assert_eq!(format_date(MARCH_2_2025, "xx"), "Mar 2, 2025");
+    }
+
+    #[test]
+    fn relative_times_pick_the_largest_unit() {
+        let now = now();
+        assert_eq!(format_relative(now, "en").as_deref(), Some("just now"));
+        assert_eq!(
+            format_relative(now - 3600, "en").as_deref(),
+            Some("1 hour ago")
+        );
+        assert_eq!(
+            format_relative(now - 21 * 86400, "en").as_deref(),
+            Some("3 weeks ago")
+        );
+        assert_eq!(
+            format_relative(now - 2 * 86400, "de").as_deref(),
+            Some("vor 2 Tagen")
+        );
+        // A clock that's behind, e.g. never set
+        assert_eq!(format_relative(now + 3600, "en"), None);
+    }
+}
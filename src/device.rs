@@ -0,0 +1,310 @@
+use std::ffi::CString;
+use std::fmt::{self, Write};
+use std::fs::{self, File};
+use std::io::{self, Write as _};
+use std::mem::MaybeUninit;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use log::warn;
+
+use crate::i18n::tr;
+use crate::{sdcard_overridden, sdcard_root, UPDATER_DATA_DIR};
+
+// Something that keeps updates from working on this system, found at startup
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Limitation {
+    SdNotMounted,
+    SdReadOnly,
+    CantReboot,
+}
+
+impl fmt::Display for Limitation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Limitation::SdNotMounted => tr!("SD card isn't mounted"),
+            Limitation::SdReadOnly => tr!("SD card is read-only or failing"),
+            Limitation::CantReboot => tr!("Can't reboot the device"),
+        })
+    }
+}
+
+// True if the SD card root is a mount point. Assumed mounted if the mount
+// table can't be read.
+fn sd_mounted() -> bool {
+    let Ok(mounts) = fs::read_to_string("/proc/mounts") else {
+        return true;
+    };
+    let root = sdcard_root().trim_end_matches('/');
+    mounts
+        .lines()
+        .filter_map(|line| line.split_whitespace().nth(1))
+        .any(|mount_point| mount_point == root)
+}
+
+// Write a small file to the SD card, sync it and read it back. A card that
+// turned read-only after errors, or that's failing, gets caught here rather
+// than partway through extracting an update.
+pub fn sd_write_test() -> io::Result<()> {
+    let dir = PathBuf::from(sdcard_root()).join(UPDATER_DATA_DIR);
+    let probe = dir.join(".write-test");
+    let result = write_test(&dir, &probe);
+    let _ = fs::remove_file(probe);
+    result
+}
+
+fn write_test(dir: &Path, probe: &Path) -> io::Result<()> {
+    // Not all the same byte, so a card returning zeros or stale data fails
+    let contents: Vec<u8> = (0..4096u32)
+        .map(|index| (index.wrapping_mul(31) % 251) as u8)
+        .collect();
+    fs::create_dir_all(dir)?;
+    let mut file = File::create(probe)?;
+    file.write_all(&contents)?;
+    file.sync_all()?;
+    drop(file);
+    if fs::read(probe)? != contents {
+        return Err(io::Error::other("what was written didn't read back"));
+    }
+    Ok(())
+}
+
+fn sd_writable() -> bool {
+    match sd_write_test() {
+        Ok(()) => true,
+        Err(err) => {
+            warn!("SD card write test failed: {err}");
+            false
+        }
+    }
+}
+
+fn is_root() -> bool {
+    // "Uid: real effective saved fs"
+    fs::read_to_string("/proc/self/status").is_ok_and(|status| {
+        status
+            .lines()
+            .find_map(|line| line.strip_prefix("Uid:"))
+            .and_then(|uids| uids.split_whitespace().nth(1))
+            == Some("0")
+    })
+}
+
+fn find_executable(name: &str) -> Option<PathBuf> {
+    let path = std::env::var("PATH").unwrap_or_else(|_| "/sbin:/bin:/usr/sbin:/usr/bin".into());
+    path.split(':')
+        .map(|dir| Path::new(dir).join(name))
+        .find(|candidate| {
+            fs::metadata(candidate).is_ok_and(|metadata| {
+                metadata.is_file() && metadata.permissions().mode() & 0o111 != 0
+            })
+        })
+}
+
+// Check that updates can actually be applied here, e.g. not when running
+// on a desktop or with the SD card missing
+pub fn limitations() -> Vec<Limitation> {
+    let mut limitations = vec![];
+
+    // A stand-in SD card isn't a mount point, and installing to it is the
+    // point, so there's no reboot to worry about either
+    if sdcard_overridden() {
+        if !sd_writable() {
+            limitations.push(Limitation::SdReadOnly);
+        }
+    } else if !sd_mounted() {
+        limitations.push(Limitation::SdNotMounted);
+    } else if !sd_writable() {
+        limitations.push(Limitation::SdReadOnly);
+    }
+    if !sdcard_overridden() && (find_executable("reboot").is_none() || !is_root()) {
+        limitations.push(Limitation::CantReboot);
+    }
+
+    for limitation in &limitations {
+        warn!("Limited mode: {limitation}");
+    }
+    limitations
+}
+
+// Commit of the installed NextUI, the second line of its version file
+pub fn installed_commit() -> Option<String> {
+    fs::read_to_string(format!("{}.system/version.txt", sdcard_root()))
+        .ok()?
+        .lines()
+        .nth(1)
+        .map(std::borrow::ToOwned::to_owned)
+}
+
+// Name of the installed NextUI build, the first line of its version file
+pub fn installed_name() -> Option<String> {
+    fs::read_to_string(format!("{}.system/version.txt", sdcard_root()))
+        .ok()?
+        .lines()
+        .next()
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(std::borrow::ToOwned::to_owned)
+}
+
+// Size of the SD card and how much of it the updater can still use, in bytes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DiskSpace {
+    pub total: u64,
+    pub free: u64,
+}
+
+#[allow(clippy::useless_conversion)] // Block counts are u32 on 32-bit devices
+pub fn disk_space() -> Option<DiskSpace> {
+    let root = CString::new(sdcard_root()).ok()?;
+    let mut stats = MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: `root` is a valid C string and `stats` is only read after
+    // statvfs reports having filled it in
+    let stats = unsafe {
+        if libc::statvfs(root.as_ptr(), stats.as_mut_ptr()) != 0 {
+            return None;
+        }
+        stats.assume_init()
+    };
+    let block_size = u64::from(stats.f_frsize);
+    Some(DiskSpace {
+        total: u64::from(stats.f_blocks) * block_size,
+        free: u64::from(stats.f_bavail) * block_size,
+    })
+}
+
+// Memory that can be used without swapping, in bytes
+pub fn available_memory() -> Option<u64> {
+    let meminfo = fs::read_to_string("/proc/meminfo").ok()?;
+    let kilobytes: u64 = meminfo
+        .lines()
+        .find_map(|line| line.strip_prefix("MemAvailable:"))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()?;
+    Some(kilobytes * 1024)
+}
+
+fn read_trimmed(path: &str) -> Option<String> {
+    fs::read_to_string(path)
+        .ok()
+        .map(|contents| contents.trim_end_matches('\0').trim().to_string())
+        .filter(|contents| !contents.is_empty())
+}
+
+fn section(report: &mut String, title: &str, contents: Option<&str>) {
+    report.push_str("== ");
+    report.push_str(title);
+    report.push_str(" ==\n");
+    report.push_str(contents.unwrap_or("unknown"));
+    report.push_str("\n\n");
+}
+
+// Device model from the device tree, or the platform NextUI was launched for
+pub fn device_model() -> Option<String> {
+    let model = read_trimmed("/proc/device-tree/model");
+    let platform = std::env::var("PLATFORM").ok();
+    match (model, platform) {
+        (Some(model), Some(platform)) => Some(format!("{model} ({platform})")),
+        (model, platform) => model.or(platform),
+    }
+}
+
+fn firmware_version() -> Option<String> {
+    ["/usr/trimui/etc/version", "/etc/version", "/etc/os-release"]
+        .into_iter()
+        .find_map(read_trimmed)
+}
+
+fn memory() -> Option<String> {
+    let meminfo = fs::read_to_string("/proc/meminfo").ok()?;
+    let lines: Vec<&str> = meminfo
+        .lines()
+        .filter(|line| {
+            [
+                "MemTotal:",
+                "MemFree:",
+                "MemAvailable:",
+                "SwapTotal:",
+                "SwapFree:",
+            ]
+            .iter()
+            .any(|key| line.starts_with(key))
+        })
+        .collect();
+    Some(lines.join("\n"))
+}
+
+// Driver behind each wireless interface, and the kernel's wireless stats
+fn wifi() -> Option<String> {
+    let mut report = String::new();
+    for entry in fs::read_dir("/sys/class/net").ok()?.flatten() {
+        let interface = entry.path();
+        if !interface.join("wireless").exists() {
+            continue;
+        }
+        let driver = fs::read_link(interface.join("device/driver"))
+            .ok()
+            .and_then(|driver| {
+                driver
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+            })
+            .unwrap_or_else(|| "unknown".to_string());
+        let _ = writeln!(
+            report,
+            "{}: driver {driver}",
+            entry.file_name().to_string_lossy()
+        );
+    }
+    if let Some(wireless) = read_trimmed("/proc/net/wireless") {
+        report += &wireless;
+    }
+    Some(report).filter(|report| !report.is_empty())
+}
+
+// Plain text description of the system, for diagnostics bundles
+pub fn environment_report() -> String {
+    let mut report = String::new();
+    section(&mut report, "Updater", Some(env!("CARGO_PKG_VERSION")));
+    section(
+        &mut report,
+        "Kernel",
+        read_trimmed("/proc/version").as_deref(),
+    );
+    section(&mut report, "Firmware", firmware_version().as_deref());
+    section(
+        &mut report,
+        "NextUI",
+        read_trimmed(&format!("{}.system/version.txt", sdcard_root())).as_deref(),
+    );
+    section(&mut report, "Device", device_model().as_deref());
+    section(&mut report, "Memory", memory().as_deref());
+    section(
+        &mut report,
+        "Mounts",
+        read_trimmed("/proc/mounts").as_deref(),
+    );
+    section(&mut report, "Wi-Fi", wifi().as_deref());
+    report
+}
+
+// Stable identifier for this device, e.g. for staged rollouts. Not sent
+// anywhere.
+pub fn device_id() -> String {
+    let serial = fs::read_to_string("/proc/cpuinfo")
+        .ok()
+        .and_then(|cpuinfo| {
+            cpuinfo
+                .lines()
+                .find_map(|line| line.strip_prefix("Serial"))
+                .and_then(|value| value.split(':').nth(1))
+                .map(|serial| serial.trim().to_string())
+        });
+    serial
+        .filter(|serial| !serial.is_empty())
+        .or_else(|| read_trimmed("/etc/machine-id"))
+        .unwrap_or_default()
+}
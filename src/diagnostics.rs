@@ -0,0 +1,60 @@
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use log::info;
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+use crate::error::Result;
+use crate::{date, device, logging, sdcard_root, UPDATER_DATA_DIR};
+
+// Files in the updater data directory that go into the bundle
+fn bundled(name: &str) -> bool {
+    name == "config.json"
+        || name == "history.json"
+        || (name.starts_with("crash-")
+            && Path::new(name)
+                .extension()
+                .is_some_and(|extension| extension == "txt"))
+}
+
+fn add_file(zip: &mut ZipWriter<File>, path: &Path, name: &str) -> Result<()> {
+    let Ok(contents) = fs::read(path) else {
+        return Ok(());
+    };
+    zip.start_file(name, SimpleFileOptions::default())?;
+    zip.write_all(&contents)?;
+    Ok(())
+}
+
+// Zip up logs, settings, history, crash reports and a description of the
+// device for attaching to issue reports. Returns where it was saved.
+pub fn export() -> Result<PathBuf> {
+    let dir = PathBuf::from(sdcard_root()).join(UPDATER_DATA_DIR);
+    fs::create_dir_all(&dir)?;
+    let path = dir.join(format!("diagnostics-{}.zip", date::now()));
+    let mut zip = ZipWriter::new(File::create(&path)?);
+
+    zip.start_file("environment.txt", SimpleFileOptions::default())?;
+    zip.write_all(device::environment_report().as_bytes())?;
+
+    for entry in fs::read_dir(&dir)?.flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if bundled(&name) {
+            add_file(&mut zip, &entry.path(), &name)?;
+        }
+    }
+
+    log::logger().flush();
+    if let Some(log_dir) = logging::log_file_path().parent() {
+        for entry in fs::read_dir(log_dir).into_iter().flatten().flatten() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            add_file(&mut zip, &entry.path(), &format!("logs/{name}"))?;
+        }
+    }
+
+    zip.finish()?;
+    info!("Diagnostics exported to {}", path.display());
+    Ok(path)
+}
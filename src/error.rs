@@ -0,0 +1,122 @@
+use std::io;
+
+use reqwest::StatusCode;
+use thiserror::Error;
+
+use crate::device::Limitation;
+use crate::update::UpdatePhase;
+
+pub type Result<T> = std::result::Result<T, UpdaterError>;
+
+// Everything that can go wrong while checking for, downloading or installing
+// an update
+#[derive(Debug, Error)]
+pub enum UpdaterError {
+    #[error("Network error: {0}")]
+    Network(reqwest::Error),
+    // Connecting took too long, or a response stopped arriving
+    #[error("Connection timed out")]
+    Timeout,
+    #[error("API request failed: {0}")]
+    Api(StatusCode),
+    #[error("API rate limit exceeded")]
+    RateLimited,
+    #[error("Invalid zip file: {0}")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("Corrupted download, please retry")]
+    CorruptDownload,
+    #[error("Invalid signature")]
+    BadSignature,
+    #[error("Release signature can't be checked")]
+    MissingSignature,
+    #[error("Not enough space on the SD card")]
+    NoSpace,
+    // What was written to the SD card couldn't be written or read back
+    #[error("SD card is read-only or failing")]
+    SdCard,
+    #[error("Not enough free memory")]
+    LowMemory,
+    #[error("I/O error: {0}")]
+    Io(io::Error),
+    #[error("Invalid data: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Invalid version: {0}")]
+    Version(#[from] semver::Error),
+    #[error("Invalid asset pattern: {0}")]
+    AssetPattern(#[from] regex::Error),
+    #[error("{0}")]
+    NotFound(&'static str),
+    #[error("Updates are disabled: {0}")]
+    Limited(Limitation),
+    #[error("Invalid update state change: {0:?} -> {1:?}")]
+    InvalidTransition(UpdatePhase, UpdatePhase),
+    // Update manifests and what they list are only fetched over HTTPS
+    #[error("Not an HTTPS URL: {0}")]
+    InsecureUrl(String),
+    #[error("Cancelled")]
+    Cancelled,
+}
+
+impl From<reqwest::Error> for UpdaterError {
+    fn from(err: reqwest::Error) -> Self {
+        if err.is_timeout() {
+            Self::Timeout
+        } else {
+            Self::Network(err)
+        }
+    }
+}
+
+impl From<io::Error> for UpdaterError {
+    fn from(err: io::Error) -> Self {
+        // Reading a response body reports network errors as I/O errors
+        let timed_out = err.kind() == io::ErrorKind::TimedOut
+            || err
+                .get_ref()
+                .and_then(|inner| inner.downcast_ref::<reqwest::Error>())
+                .is_some_and(reqwest::Error::is_timeout);
+        if timed_out {
+            Self::Timeout
+        } else if err.kind() == io::ErrorKind::StorageFull {
+            Self::NoSpace
+        } else {
+            Self::Io(err)
+        }
+    }
+}
+
+impl UpdaterError {
+    // What the user can do about it, shown below the error message
+    pub fn guidance(&self) -> Option<&'static str> {
+        match self {
+            Self::Network(_) | Self::Timeout => Some("Check your Wi-Fi connection and try again"),
+            Self::Api(status) if status.is_server_error() => {
+                Some("The server is having problems, try again later")
+            }
+            Self::Api(StatusCode::NOT_FOUND) => {
+                Some("The release may have been removed, refresh and try again")
+            }
+            Self::RateLimited => Some("Too many requests, wait a few minutes and try again"),
+            Self::Zip(_) => Some("The download may be corrupted, try again"),
+            Self::CorruptDownload => Some("Nothing was changed on the SD card"),
+            Self::BadSignature => Some("The download may have been tampered with"),
+            Self::MissingSignature => Some("Turn off \"Require signatures\" to install it anyway"),
+            Self::NoSpace => Some("Free up space on the SD card and try again"),
+            Self::LowMemory => Some("Restart the device and try again"),
+            Self::SdCard => {
+                Some("Nothing was changed. Reinsert the SD card or check it on a computer")
+            }
+            Self::Io(_) => Some("Check that the SD card isn't damaged or read-only"),
+            Self::InsecureUrl(_) => Some("Use an https:// URL in the update manifest"),
+            _ => None,
+        }
+    }
+
+    // Error message followed by guidance, for the error label
+    pub fn describe(&self, context: &str) -> String {
+        match self.guidance() {
+            Some(guidance) => format!("{context}: {self}\n{guidance}"),
+            None => format!("{context}: {self}"),
+        }
+    }
+}
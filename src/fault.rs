@@ -0,0 +1,188 @@
+use std::fmt;
+
+use log::warn;
+use parking_lot::Mutex;
+
+use crate::error::{Result, UpdaterError};
+
+// Simulated failures for exercising recovery paths on real hardware, set
+// from the developer screen
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Fault {
+    NetworkTimeout,
+    RateLimited,
+    DiskFull,
+    CorruptZip,
+    PowerLoss,
+}
+
+// Where a fault is injected
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Phase {
+    // Any forge API request, e.g. the release check
+    Metadata,
+    // Halfway through a download
+    Download,
+    // Halfway through extracting an archive
+    Extract,
+}
+
+pub const FAULTS: [Fault; 5] = [
+    Fault::NetworkTimeout,
+    Fault::RateLimited,
+    Fault::DiskFull,
+    Fault::CorruptZip,
+    Fault::PowerLoss,
+];
+
+pub const PHASES: [Phase; 3] = [Phase::Metadata, Phase::Download, Phase::Extract];
+
+// Places where a simulated power loss can kill the process, for testing that
+// whatever is left behind can be recovered from. Only compiled in with the
+// "power-loss-hooks" feature (and in tests), and armed with CRASH_AT_VAR set
+// to the point's name, optionally followed by which time it's reached, e.g.
+// "extracted-file:3".
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CrashPoint {
+    // After a staged archive is written, before its marker
+    StagedArchive,
+    // Partway through writing an extracted file
+    PartialFile,
+    // After each extracted file
+    ExtractedFile,
+}
+
+pub const CRASH_AT_VAR: &str = "NEXTUI_UPDATER_CRASH_AT";
+
+static ARMED: Mutex<Option<(Fault, Phase)>> = Mutex::new(None);
+
+pub fn armed() -> Option<(Fault, Phase)> {
+    *ARMED.lock()
+}
+
+pub fn arm(fault: Option<(Fault, Phase)>) {
+    *ARMED.lock() = fault;
+}
+
+// Fail if a fault is armed for this phase. Faults fire once, so retrying
+// afterwards goes through normally.
+pub fn inject(phase: Phase) -> Result<()> {
+    let fault = {
+        let mut armed = ARMED.lock();
+        match *armed {
+            Some((fault, armed_phase)) if armed_phase == phase => {
+                *armed = None;
+                fault
+            }
+            _ => return Ok(()),
+        }
+    };
+
+    warn!("Injecting {fault} during {phase}");
+    match fault {
+        Fault::NetworkTimeout => Err(UpdaterError::Timeout),
+        Fault::RateLimited => Err(UpdaterError::RateLimited),
+        Fault::DiskFull => Err(UpdaterError::NoSpace),
+        Fault::CorruptZip => Err(UpdaterError::Zip(zip::result::ZipError::InvalidArchive(
+            "Injected corruption",
+        ))),
+        Fault::PowerLoss => {
+            log::logger().flush();
+            std::process::abort()
+        }
+    }
+}
+
+#[cfg(any(test, feature = "power-loss-hooks"))]
+pub fn crash_point(point: CrashPoint) {
+    static HITS: Mutex<u32> = Mutex::new(0);
+
+    let Ok(crash_at) = std::env::var(CRASH_AT_VAR) else {
+        return;
+    };
+    let (name, count) = crash_at.split_once(':').unwrap_or((&crash_at, "1"));
+    if name != point.name() {
+        return;
+    }
+    let hits = {
+        let mut hits = HITS.lock();
+        *hits += 1;
+        *hits
+    };
+    if count.parse() == Ok(hits) {
+        warn!("Simulating power loss at {name}");
+        log::logger().flush();
+        std::process::abort()
+    }
+}
+
+#[cfg(not(any(test, feature = "power-loss-hooks")))]
+#[inline]
+pub fn crash_point(_point: CrashPoint) {}
+
+impl CrashPoint {
+    #[cfg_attr(not(any(test, feature = "power-loss-hooks")), allow(dead_code))]
+    fn name(self) -> &'static str {
+        match self {
+            CrashPoint::StagedArchive => "staged-archive",
+            CrashPoint::PartialFile => "partial-file",
+            CrashPoint::ExtractedFile => "extracted-file",
+        }
+    }
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Fault::NetworkTimeout => "Network timeout",
+            Fault::RateLimited => "403 rate limit",
+            Fault::DiskFull => "Disk full",
+            Fault::CorruptZip => "Corrupt zip",
+            Fault::PowerLoss => "Power loss",
+        })
+    }
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Phase::Metadata => "metadata fetch",
+            Phase::Download => "download",
+            Phase::Extract => "extraction",
+        })
+    }
+}
+
+// Running a test in a child process that crashes partway through, so the
+// test itself can check what it left behind
+#[cfg(test)]
+pub mod testing {
+    use std::path::{Path, PathBuf};
+    use std::process::{Command, Stdio};
+
+    use super::CRASH_AT_VAR;
+
+    // Where the child does its work, set only in the child
+    const CHILD_DIR_VAR: &str = "NEXTUI_UPDATER_TEST_DIR";
+
+    // Run `test` (from `module_path!()`) again in a child process with
+    // `crash_at` armed, working in `dir`. Returns whether it crashed.
+    pub fn crash_in_child(module: &str, test: &str, crash_at: &str, dir: &Path) -> bool {
+        // Test names don't include the crate
+        let module = module.split_once("::").map_or(module, |(_, path)| path);
+        let status = Command::new(std::env::current_exe().expect("No test binary"))
+            .args([&format!("{module}::{test}"), "--exact", "--test-threads=1"])
+            .env(CRASH_AT_VAR, crash_at)
+            .env(CHILD_DIR_VAR, dir)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .expect("Failed to run test binary");
+        !status.success()
+    }
+
+    // The directory to work in when running as the child
+    pub fn child_dir() -> Option<PathBuf> {
+        std::env::var_os(CHILD_DIR_VAR).map(PathBuf::from)
+    }
+}
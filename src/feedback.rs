@@ -0,0 +1,117 @@
+use std::f32::consts::TAU;
+
+use log::{debug, warn};
+use sdl2::audio::{AudioQueue, AudioSpecDesired};
+use sdl2::controller::GameController;
+
+const SAMPLE_RATE: i32 = 22050;
+const VOLUME: f32 = 0.25;
+// Navigation plays all the time, so it's kept quieter than results
+const NAVIGATION_VOLUME: f32 = 0.1;
+
+// Something the UI thread should play back, queued from any thread
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cue {
+    Success,
+    Failure,
+    // Focus moved to another button
+    Move,
+    Confirm,
+    Back,
+}
+
+impl Cue {
+    // (frequency in Hz, length in ms) of each tone, played one after another
+    fn tones(self) -> &'static [(f32, u32)] {
+        match self {
+            Cue::Success => &[(660.0, 80), (880.0, 140)],
+            Cue::Failure => &[(330.0, 150), (220.0, 250)],
+            Cue::Move => &[(1200.0, 15)],
+            Cue::Confirm => &[(880.0, 25), (1320.0, 35)],
+            Cue::Back => &[(660.0, 25), (440.0, 35)],
+        }
+    }
+
+    fn volume(self) -> f32 {
+        match self {
+            Cue::Success | Cue::Failure => VOLUME,
+            Cue::Move | Cue::Confirm | Cue::Back => NAVIGATION_VOLUME,
+        }
+    }
+}
+
+// Sine tone with a short fade in and out, so it doesn't click
+fn tone(frequency: f32, duration_ms: u32, volume: f32) -> Vec<i16> {
+    let length = (SAMPLE_RATE as u32 * duration_ms / 1000) as usize;
+    let fade = (length / 10).max(1);
+    (0..length)
+        .map(|index| {
+            let envelope = (index.min(length - index) as f32 / fade as f32).min(1.0);
+            let phase = TAU * frequency * index as f32 / SAMPLE_RATE as f32;
+            (phase.sin() * envelope * volume * f32::from(i16::MAX)) as i16
+        })
+        .collect()
+}
+
+// Plays cues on the default audio device, if there is one
+pub struct Player {
+    queue: Option<AudioQueue<i16>>,
+}
+
+impl Player {
+    pub fn new(sdl_context: &sdl2::Sdl) -> Self {
+        let spec = AudioSpecDesired {
+            freq: Some(SAMPLE_RATE),
+            channels: Some(1),
+            samples: None,
+        };
+        let queue = sdl_context
+            .audio()
+            .and_then(|audio| audio.open_queue::<i16, _>(None, &spec))
+            .map_err(|err| warn!("No audio: {err}"))
+            .ok();
+        if let Some(queue) = &queue {
+            queue.resume();
+        }
+        Self { queue }
+    }
+
+    pub fn play(&self, cue: Cue) {
+        let Some(queue) = &self.queue else {
+            return;
+        };
+        let samples: Vec<i16> = cue
+            .tones()
+            .iter()
+            .flat_map(|&(frequency, duration_ms)| tone(frequency, duration_ms, cue.volume()))
+            .collect();
+        if let Err(err) = queue.queue_audio(&samples) {
+            warn!("Failed to play sound: {err}");
+        }
+    }
+}
+
+// Controller rumble, queued from any thread like cues
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rumble {
+    Success,
+    Failure,
+}
+
+impl Rumble {
+    // (low frequency motor, high frequency motor, length in ms)
+    fn pattern(self) -> (u16, u16, u32) {
+        match self {
+            Rumble::Success => (0x4000, 0x4000, 150),
+            Rumble::Failure => (0xC000, 0x8000, 400),
+        }
+    }
+
+    pub fn play(self, controller: &mut GameController) {
+        let (low, high, duration_ms) = self.pattern();
+        // Not every controller has motors, so this is expected to fail sometimes
+        if let Err(err) = controller.set_rumble(low, high, duration_ms) {
+            debug!("Failed to rumble: {err}");
+        }
+    }
+}
@@ -1,15 +1,58 @@
+use regex::Regex;
 use serde::Deserialize;
 
 #[derive(Deserialize, Clone, Debug)]
 pub struct Asset {
     pub name: String,
+    // API URL, downloads the asset with `Accept: application/octet-stream`
     pub url: String,
+    // In bytes
+    #[serde(default)]
+    pub size: u64,
+    #[serde(default)]
+    pub download_count: u64,
+    // "sha256:<hex>", checked after downloading. GitHub has it for newer
+    // uploads, JSON manifests list it.
+    #[serde(default)]
+    pub digest: Option<String>,
 }
 
 #[derive(Deserialize, Clone, Debug)]
 pub struct Release {
     pub tag_name: String,
+    // RFC 3339, unset for drafts
+    pub published_at: Option<String>,
+    // Release notes in Markdown
+    pub body: Option<String>,
     pub assets: Vec<Asset>,
+    // Drafts only show up with push access to the repo
+    #[serde(default)]
+    pub draft: bool,
+    #[serde(default)]
+    pub prerelease: bool,
+}
+
+impl Release {
+    // Downloads of all assets together, as a rough measure of popularity
+    pub fn download_count(&self) -> u64 {
+        self.assets.iter().map(|asset| asset.download_count).sum()
+    }
+
+    // Assets that can be installed, leaving out signatures
+    pub fn installable_assets(&self) -> Vec<&Asset> {
+        self.assets
+            .iter()
+            .filter(|asset| !asset.name.ends_with(".minisig"))
+            .collect()
+    }
+
+    // The asset an update with this asset pattern would download, if any
+    pub fn asset_for(&self, asset_re: &Regex) -> Option<&Asset> {
+        self.assets
+            .iter()
+            .find(|asset| asset_re.is_match(&asset.name))
+            .or(self.assets.first())
+    }
 }
 
 #[derive(Deserialize, Clone, Debug)]
@@ -23,8 +66,45 @@ pub struct Commit {
     pub sha: String,
 }
 
+#[derive(Deserialize, Clone, Debug)]
+pub struct CommitMessage {
+    pub message: String,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct ComparedCommit {
+    pub sha: String,
+    pub commit: CommitMessage,
+}
+
+// Result of comparing the installed commit with a release
+#[derive(Deserialize, Clone, Debug)]
+pub struct Comparison {
+    pub status: String,
+    pub ahead_by: u64,
+    pub behind_by: u64,
+    #[serde(default)]
+    pub commits: Vec<ComparedCommit>,
+}
+
+// Everything shown on the release details screen
+#[derive(Clone, Debug)]
+pub struct ReleaseDetails {
+    pub release: Release,
+    pub comparison: Option<Comparison>,
+}
+
 #[derive(Clone, Debug)]
 pub struct ReleaseAndTag {
     pub release: Release,
-    pub tag: Tag,
+    // None if the tag wasn't in the fetched tag list and hasn't been looked up yet
+    pub tag: Option<Tag>,
+}
+
+impl ReleaseAndTag {
+    pub fn matches_commit(&self, sha: &str) -> bool {
+        self.tag
+            .as_ref()
+            .is_some_and(|tag| tag.commit.sha.starts_with(sha))
+    }
 }
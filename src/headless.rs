@@ -0,0 +1,141 @@
+use log::info;
+use serde::Serialize;
+
+use crate::app_state::AppStateManager;
+use crate::args::{self, Command};
+use crate::github::Release;
+use crate::network::has_network;
+use crate::simulate;
+use crate::update::{self, do_nextui_release_check, UpdatePhase};
+
+// What a command found or did, printed as JSON with --json
+#[derive(Serialize, Default, Debug)]
+struct Report {
+    command: &'static str,
+    success: bool,
+    // Tag of the installed release, if it's a known one
+    installed_version: Option<String>,
+    installed_commit: Option<String>,
+    latest_version: Option<String>,
+    update_available: Option<bool>,
+    // Release the asset is from, the latest one unless --tag says otherwise
+    target_version: Option<String>,
+    asset: Option<String>,
+    // "up to date", "update available", "unknown", "installed", or "failed"
+    outcome: &'static str,
+    error: Option<String>,
+    // The device has to be rebooted to finish installing
+    reboot_required: bool,
+}
+
+impl Report {
+    fn print(&self, json: bool) {
+        if json {
+            match serde_json::to_string_pretty(self) {
+                Ok(json) => println!("{json}"),
+                Err(err) => eprintln!("Failed to write the report: {err}"),
+            }
+            return;
+        }
+
+        let unknown = || "unknown".to_string();
+        println!(
+            "Installed: {} ({})",
+            self.installed_version.clone().unwrap_or_else(unknown),
+            self.installed_commit.clone().unwrap_or_else(unknown)
+        );
+        println!(
+            "Latest: {}",
+            self.latest_version.clone().unwrap_or_else(unknown)
+        );
+        if let (Some(target), Some(asset)) = (&self.target_version, &self.asset) {
+            println!("Asset: {asset} from {target}");
+        }
+        match &self.error {
+            Some(error) => println!("Failed: {error}"),
+            None => println!("{}", self.outcome),
+        }
+        if self.reboot_required {
+            println!("Reboot to finish installing");
+        }
+    }
+}
+
+// Run a command instead of the UI, returning the exit code: 0 if it
+// succeeded, 1 if not
+pub fn run(app_state: &AppStateManager, command: Command) -> i32 {
+    let args = args::get();
+    // Hooks run early, often before Wi-Fi is up. Leave the status file as
+    // it was rather than record a failed check.
+    if command == Command::CheckOnly && !has_network() {
+        info!("No network connection, skipping check");
+        return 1;
+    }
+    let mut report = Report {
+        command: match command {
+            Command::Check | Command::CheckOnly => "check",
+            Command::Install => "install",
+        },
+        installed_commit: app_state.current_version(),
+        ..Report::default()
+    };
+
+    let result = check(app_state, &mut report).and_then(|release| match command {
+        Command::Check | Command::CheckOnly => {
+            report.outcome = match report.update_available {
+                Some(true) => "update available",
+                Some(false) => "up to date",
+                // The installed version isn't a known release, so there's
+                // nothing to compare the latest one with
+                None => "unknown",
+            };
+            Ok(())
+        }
+        Command::Install => {
+            update::install(app_state, &release, args.full).map_err(|err| err.to_string())?;
+            report.outcome = "installed";
+            report.reboot_required = !simulate::active();
+            Ok(())
+        }
+    });
+
+    report.success = result.is_ok() && report.outcome != "unknown";
+    if let Err(error) = result {
+        report.outcome = "failed";
+        report.error = Some(error);
+    }
+    if command != Command::CheckOnly {
+        report.print(args.json);
+    }
+    i32::from(!report.success)
+}
+
+// Fetch the releases and fill in the versions, returning the release to
+// install: --tag or the latest
+fn check(app_state: &AppStateManager, report: &mut Report) -> Result<Release, String> {
+    do_nextui_release_check(app_state);
+    if let UpdatePhase::Failed { error } = app_state.update_phase() {
+        return Err(error);
+    }
+
+    report.installed_version = app_state.installed_tag_name();
+    let latest = app_state.nextui_release();
+    report.latest_version = latest.as_ref().map(|release| release.tag_name.clone());
+    report.update_available = app_state.update_available();
+
+    let release = match &args::get().tag {
+        Some(tag_name) => app_state
+            .nextui_releases_and_tags()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|release_and_tag| release_and_tag.release)
+            .find(|release| &release.tag_name == tag_name)
+            .ok_or_else(|| format!("No release {tag_name}"))?,
+        None => latest.ok_or("No release found")?,
+    };
+    let asset =
+        update::pick_asset(app_state, &release, args::get().full).map_err(|err| err.to_string())?;
+    report.target_version = Some(release.tag_name.clone());
+    report.asset = Some(asset.name.clone());
+    Ok(release)
+}
@@ -0,0 +1,75 @@
+use std::fmt::Display;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{date, sdcard_root, Result, UPDATER_DATA_DIR};
+
+const HISTORY_FILE: &str = "history.json";
+const MAX_ENTRIES: usize = 200;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Success,
+    Failed(String),
+}
+
+// A single install attempt
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct HistoryEntry {
+    pub timestamp: i64,
+    pub from_version: Option<String>,
+    pub to_version: String,
+    pub asset: String,
+    pub outcome: Outcome,
+}
+
+impl HistoryEntry {
+    pub fn new<T, E: Display>(
+        from_version: Option<String>,
+        to_version: &str,
+        asset: &str,
+        result: &std::result::Result<T, E>,
+    ) -> Self {
+        Self {
+            timestamp: date::now(),
+            from_version,
+            to_version: to_version.to_string(),
+            asset: asset.to_string(),
+            outcome: match result {
+                Ok(_) => Outcome::Success,
+                Err(err) => Outcome::Failed(err.to_string()),
+            },
+        }
+    }
+}
+
+fn path() -> PathBuf {
+    PathBuf::from(sdcard_root())
+        .join(UPDATER_DATA_DIR)
+        .join(HISTORY_FILE)
+}
+
+// All recorded entries, oldest first
+pub fn load() -> Vec<HistoryEntry> {
+    std::fs::read_to_string(path())
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+pub fn record(entry: HistoryEntry) -> Result<()> {
+    let mut entries = load();
+    entries.push(entry);
+    if entries.len() > MAX_ENTRIES {
+        entries.drain(..entries.len() - MAX_ENTRIES);
+    }
+
+    let path = path();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, serde_json::to_string_pretty(&entries)?)?;
+    Ok(())
+}
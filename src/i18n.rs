@@ -0,0 +1,172 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use log::{info, warn};
+use parking_lot::Mutex;
+
+use crate::config::Config;
+use crate::nextui_settings::NextUISettings;
+use crate::{sdcard_root, UPDATER_DATA_DIR};
+
+// Translations are JSON files in lang/ next to the config, e.g.
+// lang/de.json, mapping the English text to the translated one. English is
+// built in, and used for anything a file doesn't translate.
+const LANG_DIR: &str = "lang";
+
+struct Translations {
+    language: String,
+    strings: HashMap<String, String>,
+    // Logged once each, so translators can find what's missing
+    missing: Mutex<HashSet<&'static str>>,
+}
+
+static TRANSLATIONS: OnceLock<Translations> = OnceLock::new();
+
+// The updater config wins, then NextUI's settings, then English
+fn pick_language(config: &Config) -> String {
+    if let Some(language) = config.language.clone().filter(|l| !l.is_empty()) {
+        return language;
+    }
+    NextUISettings::load()
+        .language()
+        .map_or_else(|| "en".to_string(), str::to_string)
+}
+
+fn lang_file(language: &str) -> PathBuf {
+    PathBuf::from(sdcard_root())
+        .join(UPDATER_DATA_DIR)
+        .join(LANG_DIR)
+        .join(format!("{language}.json"))
+}
+
+// Load the language file once the config is known. Text shown before this
+// is in English.
+pub fn init(config: &Config) {
+    let language = pick_language(config);
+    let mut strings = HashMap::new();
+    if !language.starts_with("en") {
+        let path = lang_file(&language);
+        match fs::read_to_string(&path).map(|contents| serde_json::from_str(&contents)) {
+            Ok(Ok(loaded)) => {
+                strings = loaded;
+                info!("Loaded {} {language} strings", strings.len());
+            }
+            Ok(Err(err)) => warn!("Failed to parse {}: {err}", path.display()),
+            Err(err) => warn!("No {language} translation at {}: {err}", path.display()),
+        }
+    }
+    let _ = TRANSLATIONS.set(Translations {
+        language,
+        strings,
+        missing: Mutex::new(HashSet::new()),
+    });
+}
+
+// Language code for text and dates, e.g. "de"
+pub fn language() -> &'static str {
+    TRANSLATIONS
+        .get()
+        .map_or("en", |translations| translations.language.as_str())
+}
+
+// `text` in the current language, or as is if there's no translation
+pub fn translate(text: &'static str) -> &'static str {
+    let Some(translations) = TRANSLATIONS.get() else {
+        return text;
+    };
+    if let Some(translated) = translations.strings.get(text) {
+        return translated;
+    }
+    if !translations.strings.is_empty() && translations.missing.lock().insert(text) {
+        info!("No {} translation for {text:?}", translations.language);
+    }
+    text
+}
+
+// Fill in {name} placeholders, leaving unknown ones as they are
+pub fn fill(text: &str, values: &[(&str, &dyn Display)]) -> String {
+    let mut filled = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        filled.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let value = rest.find('}').and_then(|end| {
+            let name = &rest[1..end];
+            values
+                .iter()
+                .find(|(known, _)| *known == name)
+                .map(|(_, value)| (end, value))
+        });
+        if let Some((end, value)) = value {
+            filled.push_str(&value.to_string());
+            rest = &rest[end + 1..];
+        } else {
+            filled.push('{');
+            rest = &rest[1..];
+        }
+    }
+    filled.push_str(rest);
+    filled
+}
+
+// Translated text, e.g. `tr!("Quit")`, or with placeholders filled in from
+// variables or expressions, e.g. `tr!("Reinstall {tag}", tag = release.tag_name)`
+macro_rules! tr {
+    ($text:literal) => {
+        $crate::i18n::translate($text)
+    };
+    ($text:literal, $($name:ident $(= $value:expr)?),+ $(,)?) => {
+        $crate::i18n::fill(
+            $crate::i18n::translate($text),
+            &[$((stringify!($name), &$crate::i18n::tr_value!($name $(= $value)?) as &dyn std::fmt::Display)),+],
+        )
+    };
+}
+
+macro_rules! tr_value {
+    ($name:ident) => {
+        $name
+    };
+    ($name:ident = $value:expr) => {
+        $value
+    };
+}
+
+pub(crate) use tr;
+pub(crate) use tr_value;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn placeholders_are_filled_in() {
+        assert_eq!(
+            fill("Update {from} to {to}", &[("from", &"v6.2"), ("to", &7)]),
+            "Update v6.2 to 7"
+        );
+        assert_eq!(fill("{tag} {tag}", &[("tag", &"v6.2")]), "v6.2 v6.2");
+    }
+
+    #[test]
+    fn unknown_and_unclosed_placeholders_are_left_alone() {
+        assert_eq!(
+            fill("{unknown} {tag}", &[("tag", &"v6.2")]),
+            "{unknown} v6.2"
+        );
+        assert_eq!(fill("{tag", &[("tag", &"v6.2")]), "{tag");
+        assert_eq!(fill("{{tag}}", &[("tag", &"v6.2")]), "{v6.2}");
+        assert_eq!(fill("No placeholders", &[]), "No placeholders");
+    }
+
+    #[test]
+    fn tr_takes_variables_and_expressions() {
+        let tag = "v6.2";
+        assert_eq!(tr!("Quit"), "Quit");
+        assert_eq!(tr!("Reinstall {tag}", tag), "Reinstall v6.2");
+        assert_eq!(tr!("{count} downloads", count = 2 + 3), "5 downloads");
+    }
+}
@@ -0,0 +1,282 @@
+use std::time::{Duration, Instant};
+
+use sdl2::controller::{Axis, Button};
+use sdl2::keyboard::Keycode;
+
+use crate::config::ButtonMapping;
+use crate::i18n::tr;
+
+// How far the stick has to be pushed, out of i16::MAX, to count as a direction
+const DEADZONE: i16 = 16_000;
+// Once it does, it has to come back this far to count as released, so a
+// stick resting near the deadzone doesn't flicker
+const RELEASE_ZONE: i16 = 10_000;
+// Holding a direction presses it again after the delay, then every interval
+const REPEAT_DELAY: Duration = Duration::from_millis(400);
+const REPEAT_INTERVAL: Duration = Duration::from_millis(120);
+
+// Repeats the held direction or button, like a keyboard's key repeat
+pub struct Repeat<T> {
+    held: Option<(T, Instant)>,
+}
+
+impl<T> Default for Repeat<T> {
+    fn default() -> Self {
+        Self { held: None }
+    }
+}
+
+impl<T: Copy + PartialEq> Repeat<T> {
+    pub fn press(&mut self, input: T) {
+        self.held = Some((input, Instant::now() + REPEAT_DELAY));
+    }
+
+    pub fn release(&mut self, input: T) {
+        if self.held.is_some_and(|(held, _)| held == input) {
+            self.held = None;
+        }
+    }
+
+    // The held input, if it's time to press it again
+    pub fn poll(&mut self) -> Option<T> {
+        let (input, next) = self.held.as_mut()?;
+        let now = Instant::now();
+        if now < *next {
+            return None;
+        }
+        *next = now + REPEAT_INTERVAL;
+        Some(*input)
+    }
+}
+
+// The left stick, read as a D-pad
+#[derive(Default)]
+pub struct Stick {
+    x: i16,
+    y: i16,
+    direction: Option<Keycode>,
+}
+
+impl Stick {
+    // Returns the released and pressed directions if moving the stick
+    // changed its direction
+    pub fn motion(&mut self, axis: Axis, value: i16) -> Option<(Option<Keycode>, Option<Keycode>)> {
+        match axis {
+            Axis::LeftX => self.x = value,
+            Axis::LeftY => self.y = value,
+            _ => return None,
+        }
+        let direction = self.read_direction();
+        if direction == self.direction {
+            return None;
+        }
+        Some((std::mem::replace(&mut self.direction, direction), direction))
+    }
+
+    // Whichever axis is pushed further wins
+    fn read_direction(&self) -> Option<Keycode> {
+        let threshold = if self.direction.is_some() {
+            RELEASE_ZONE
+        } else {
+            DEADZONE
+        };
+        let (x, y) = (i32::from(self.x), i32::from(self.y));
+        if x.abs().max(y.abs()) < i32::from(threshold) {
+            return None;
+        }
+        Some(if x.abs() > y.abs() {
+            if x > 0 {
+                Keycode::Right
+            } else {
+                Keycode::Left
+            }
+        } else if y > 0 {
+            Keycode::Down
+        } else {
+            Keycode::Up
+        })
+    }
+}
+
+// What a mapped button does. The D-pad always navigates.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Select,
+    Back,
+    Versions,
+    Menu,
+    PageUp,
+    PageDown,
+}
+
+pub const ACTIONS: [Action; 6] = [
+    Action::Select,
+    Action::Back,
+    Action::Versions,
+    Action::Menu,
+    Action::PageUp,
+    Action::PageDown,
+];
+
+impl Action {
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::Select => tr!("Select"),
+            Action::Back => tr!("Back"),
+            Action::Versions => tr!("Versions"),
+            Action::Menu => tr!("Menu"),
+            Action::PageUp => tr!("Page up"),
+            Action::PageDown => tr!("Page down"),
+        }
+    }
+}
+
+const DPAD: [Button; 4] = [
+    Button::DPadUp,
+    Button::DPadDown,
+    Button::DPadLeft,
+    Button::DPadRight,
+];
+
+// The button mapping from the config, checked and resolved to SDL buttons
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Controls {
+    // Indexed like ACTIONS
+    buttons: [Button; 6],
+}
+
+impl Default for Controls {
+    fn default() -> Self {
+        Self::from_config(&ButtonMapping::default()).expect("Invalid default button mapping")
+    }
+}
+
+impl Controls {
+    // Unknown names, D-pad buttons and buttons mapped twice are refused, so a
+    // typo can't leave the updater without a way to select or go back
+    pub fn from_config(mapping: &ButtonMapping) -> Result<Self, String> {
+        let names = [
+            &mapping.select,
+            &mapping.back,
+            &mapping.versions,
+            &mapping.menu,
+            &mapping.page_up,
+            &mapping.page_down,
+        ];
+        let mut buttons = [Button::A; 6];
+        for (index, name) in names.into_iter().enumerate() {
+            let button = Button::from_string(name).ok_or(format!("Unknown button {name:?}"))?;
+            if DPAD.contains(&button) {
+                return Err(format!("{name:?} is for navigating"));
+            }
+            if buttons[..index].contains(&button) {
+                return Err(format!("{name:?} is mapped twice"));
+            }
+            buttons[index] = button;
+        }
+        Ok(Self { buttons })
+    }
+
+    pub fn button(self, action: Action) -> Button {
+        let index = ACTIONS.iter().position(|a| *a == action).unwrap_or(0);
+        self.buttons[index]
+    }
+
+    pub fn action(self, button: Button) -> Option<Action> {
+        ACTIONS
+            .into_iter()
+            .find(|action| self.button(*action) == button)
+    }
+
+    // Held D-pad and page buttons repeat, to scroll through long lists
+    pub fn repeats(self, button: Button) -> bool {
+        DPAD.contains(&button)
+            || matches!(self.action(button), Some(Action::PageUp | Action::PageDown))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn held_inputs_repeat_after_the_delay() {
+        let mut repeat = Repeat::default();
+        repeat.press(Keycode::Down);
+        assert_eq!(repeat.poll(), None);
+
+        // As if the delay had passed
+        repeat.held = Some((Keycode::Down, Instant::now()));
+        assert_eq!(repeat.poll(), Some(Keycode::Down));
+        assert_eq!(repeat.poll(), None);
+
+        // Only releasing the held input stops it
+        repeat.release(Keycode::Up);
+        assert!(repeat.held.is_some());
+        repeat.release(Keycode::Down);
+        assert_eq!(repeat.poll(), None);
+    }
+
+    #[test]
+    fn sticks_read_as_directions() {
+        let mut stick = Stick::default();
+        assert_eq!(stick.motion(Axis::LeftX, DEADZONE - 1), None);
+        assert_eq!(
+            stick.motion(Axis::LeftX, DEADZONE),
+            Some((None, Some(Keycode::Right)))
+        );
+        // The axis pushed further wins
+        assert_eq!(
+            stick.motion(Axis::LeftY, -i16::MAX),
+            Some((Some(Keycode::Right), Some(Keycode::Up)))
+        );
+        assert_eq!(stick.motion(Axis::RightX, i16::MAX), None);
+    }
+
+    #[test]
+    fn sticks_are_released_past_the_release_zone() {
+        let mut stick = Stick::default();
+        stick.motion(Axis::LeftY, DEADZONE);
+        // Back inside the deadzone, but not far enough to let go
+        assert_eq!(stick.motion(Axis::LeftY, RELEASE_ZONE), None);
+        assert_eq!(
+            stick.motion(Axis::LeftY, RELEASE_ZONE - 1),
+            Some((Some(Keycode::Down), None))
+        );
+    }
+
+    fn mapping(select: &str, back: &str) -> ButtonMapping {
+        ButtonMapping {
+            select: select.to_string(),
+            back: back.to_string(),
+            ..ButtonMapping::default()
+        }
+    }
+
+    #[test]
+    fn mappings_resolve_to_buttons() {
+        let controls = Controls::from_config(&ButtonMapping::default()).unwrap();
+        assert_eq!(controls.button(Action::Select), Button::B);
+        assert_eq!(controls.action(Button::Start), Some(Action::Menu));
+        assert_eq!(controls.action(Button::X), None);
+        assert!(controls.repeats(Button::LeftShoulder));
+        assert!(controls.repeats(Button::DPadDown));
+        assert!(!controls.repeats(Button::B));
+
+        let swapped = Controls::from_config(&mapping("a", "b")).unwrap();
+        assert_eq!(swapped.action(Button::A), Some(Action::Select));
+        assert_eq!(swapped.action(Button::B), Some(Action::Back));
+    }
+
+    #[test]
+    fn bad_mappings_are_refused() {
+        for bad in [
+            mapping("select", "a"),
+            mapping("dpup", "a"),
+            mapping("b", "b"),
+            mapping("a", "y"),
+        ] {
+            assert!(Controls::from_config(&bad).is_err(), "{bad:?}");
+        }
+    }
+}
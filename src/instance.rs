@@ -0,0 +1,80 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+
+use log::{info, warn};
+
+use crate::{sdcard_root, UPDATER_DATA_DIR};
+
+const LOCK_FILE: &str = "updater.lock";
+
+// Held for as long as this process is the running updater. The lock file
+// holds our PID, so a lock left behind by a crash or reboot can be detected.
+pub struct InstanceLock {
+    // None if the lock file couldn't be created
+    path: Option<PathBuf>,
+}
+
+fn lock_path() -> PathBuf {
+    PathBuf::from(sdcard_root())
+        .join(UPDATER_DATA_DIR)
+        .join(LOCK_FILE)
+}
+
+fn process_name(pid: &str) -> Option<String> {
+    fs::read_to_string(format!("/proc/{pid}/comm"))
+        .ok()
+        .map(|name| name.trim().to_string())
+}
+
+// A lock is stale if its process is gone, or its PID now belongs to
+// something other than the updater
+fn holder(path: &PathBuf) -> Option<u32> {
+    let pid: u32 = fs::read_to_string(path).ok()?.trim().parse().ok()?;
+    if pid == std::process::id() {
+        return None;
+    }
+    let name = process_name(&pid.to_string())?;
+    (Some(name) == process_name("self")).then_some(pid)
+}
+
+impl InstanceLock {
+    // Err with the other instance's PID if one is running
+    pub fn acquire() -> std::result::Result<Self, u32> {
+        let path = lock_path();
+        if let Some(parent) = path.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+
+        for _ in 0..2 {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    let _ = write!(file, "{}", std::process::id());
+                    return Ok(Self { path: Some(path) });
+                }
+                Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {
+                    if let Some(pid) = holder(&path) {
+                        return Err(pid);
+                    }
+                    info!("Removing stale lock file");
+                    let _ = fs::remove_file(&path);
+                }
+                Err(err) => {
+                    // Read-only or missing SD card, don't lock anyone out over it
+                    warn!("Failed to create lock file: {err}");
+                    return Ok(Self { path: None });
+                }
+            }
+        }
+
+        Ok(Self { path: None })
+    }
+}
+
+impl Drop for InstanceLock {
+    fn drop(&mut self) {
+        if let Some(path) = &self.path {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
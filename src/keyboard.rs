@@ -0,0 +1,180 @@
+use egui_sdl2_gl::egui::{self, Button, RichText, Vec2};
+
+use crate::app_state::AppStateManager;
+use crate::config::is_repo_name;
+use crate::i18n::tr;
+use crate::update::{configure_client, do_repository_change};
+use crate::Result;
+
+// Letters, digits and what URLs and repository names need
+const ROWS: [&str; 5] = [
+    "1234567890",
+    "qwertyuiop",
+    "asdfghjkl:",
+    "zxcvbnm./@",
+    "-_?=&%#~+,",
+];
+const KEY_SIZE: Vec2 = Vec2::new(20.0, 14.0);
+
+// Something that can be typed in: a config setting or a search
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextField {
+    Proxy,
+    GitHubToken,
+    Repository,
+    ReleaseSearch,
+}
+
+impl TextField {
+    pub fn label(self) -> &'static str {
+        match self {
+            TextField::Proxy => tr!("Proxy"),
+            TextField::GitHubToken => tr!("GitHub token"),
+            TextField::Repository => tr!("Repository (owner/repo)"),
+            TextField::ReleaseSearch => tr!("Search versions"),
+        }
+    }
+
+    pub fn get(self, app_state: &AppStateManager) -> String {
+        match self {
+            TextField::Proxy => app_state.config().proxy.unwrap_or_default(),
+            TextField::GitHubToken => app_state.config().github_token.unwrap_or_default(),
+            TextField::Repository => app_state.config().source().repo,
+            TextField::ReleaseSearch => app_state.release_filter().query,
+        }
+    }
+
+    // Settings are saved right away, except a repository, which is checked
+    // first. Empty text unsets them.
+    pub fn set(self, app_state: &'static AppStateManager, text: &str) -> Result<()> {
+        let text = text.trim().to_string();
+        match self {
+            TextField::Proxy | TextField::GitHubToken => {
+                let mut config = app_state.config();
+                let value = Some(text).filter(|text| !text.is_empty());
+                if self == TextField::Proxy {
+                    config.proxy = value;
+                } else {
+                    config.github_token = value;
+                }
+                config.save()?;
+                configure_client(&config);
+                app_state.set_config(config);
+            }
+            TextField::Repository => {
+                if !text.is_empty() && !is_repo_name(&text) {
+                    return Err(tr!("{repo:?} isn't an owner/repo name", repo = text).into());
+                }
+                do_repository_change(app_state, text);
+            }
+            TextField::ReleaseSearch => {
+                let mut filter = app_state.release_filter();
+                filter.query = text;
+                app_state.set_release_filter(filter);
+            }
+        }
+        Ok(())
+    }
+}
+
+// Text being typed in, kept across frames
+#[derive(Clone, Debug)]
+pub struct TextEntry {
+    pub field: TextField,
+    pub text: String,
+    pub shift: bool,
+}
+
+impl TextEntry {
+    pub fn new(field: TextField, app_state: &AppStateManager) -> Self {
+        Self {
+            field,
+            text: field.get(app_state),
+            shift: false,
+        }
+    }
+}
+
+pub struct KeyboardOutput {
+    // The first key, to focus when nothing else is
+    pub response: egui::Response,
+    // Some(true) when OK was pressed, Some(false) when cancelled
+    pub finished: Option<bool>,
+}
+
+// Keys navigable with the D-pad. Text typed on a real keyboard goes in too.
+pub fn show(ui: &mut egui::Ui, entry: &mut TextEntry) -> KeyboardOutput {
+    ui.label(RichText::new(format!("{}:", entry.field.label())).size(10.0));
+    ui.label(
+        RichText::new(format!("{}_", entry.text))
+            .monospace()
+            .size(10.0),
+    );
+    ui.add_space(4.0);
+
+    let key = |ui: &mut egui::Ui, label: &str| {
+        ui.add_sized(KEY_SIZE, Button::new(RichText::new(label).size(10.0)))
+    };
+    let wide_key = |ui: &mut egui::Ui, label: &str| {
+        ui.add_sized(KEY_SIZE * Vec2::new(2.0, 1.0), Button::new(label))
+    };
+
+    let mut first = None;
+    for row in ROWS {
+        ui.horizontal(|ui| {
+            for character in row.chars() {
+                let character = if entry.shift {
+                    character.to_ascii_uppercase()
+                } else {
+                    character
+                };
+                let response = key(ui, &character.to_string());
+                if response.clicked() {
+                    entry.text.push(character);
+                }
+                first.get_or_insert(response);
+            }
+        });
+    }
+
+    let mut finished = None;
+    ui.horizontal(|ui| {
+        let shift_label = if entry.shift { "ABC" } else { "abc" };
+        if key(ui, shift_label).clicked() {
+            entry.shift = !entry.shift;
+        }
+        if wide_key(ui, tr!("Space")).clicked() {
+            entry.text.push(' ');
+        }
+        if wide_key(ui, tr!("Delete")).clicked() {
+            entry.text.pop();
+        }
+        if wide_key(ui, tr!("Cancel")).clicked() {
+            finished = Some(false);
+        }
+        if wide_key(ui, tr!("OK")).clicked() {
+            finished = Some(true);
+        }
+    });
+
+    ui.input(|input| {
+        for event in &input.events {
+            match event {
+                egui::Event::Text(text) => entry.text.push_str(text),
+                egui::Event::Key {
+                    key: egui::Key::Backspace,
+                    pressed: true,
+                    ..
+                } => {
+                    entry.text.pop();
+                }
+                _ => {}
+            }
+        }
+    });
+
+    KeyboardOutput {
+        response: first.expect("Keyboard has no keys"),
+        finished,
+    }
+}
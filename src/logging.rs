@@ -0,0 +1,134 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use parking_lot::Mutex;
+
+use crate::{args, date, sdcard_root, UPDATER_DATA_DIR};
+
+const LOG_DIR: &str = "logs";
+const LOG_FILE: &str = "updater.log";
+const MAX_LOG_SIZE: u64 = 512 * 1024;
+const MAX_LOG_FILES: usize = 5;
+
+// Writes log records to stdout and a size-rotated file on the SD card
+struct FileLogger {
+    file: Mutex<Option<(File, u64)>>,
+}
+
+fn log_dir() -> PathBuf {
+    PathBuf::from(sdcard_root())
+        .join(UPDATER_DATA_DIR)
+        .join(LOG_DIR)
+}
+
+fn rotated_path(index: usize) -> PathBuf {
+    if index == 0 {
+        log_dir().join(LOG_FILE)
+    } else {
+        log_dir().join(format!("updater.{index}.log"))
+    }
+}
+
+// updater.log -> updater.1.log -> ... -> updater.4.log, dropping the oldest
+fn rotate() {
+    for index in (0..MAX_LOG_FILES - 1).rev() {
+        let _ = fs::rename(rotated_path(index), rotated_path(index + 1));
+    }
+}
+
+fn open_log() -> Option<(File, u64)> {
+    fs::create_dir_all(log_dir()).ok()?;
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(rotated_path(0))
+        .ok()?;
+    let size = file.metadata().map_or(0, |m| m.len());
+    Some((file, size))
+}
+
+// "2025-03-02 12:34:56" in UTC
+fn timestamp() -> String {
+    let now = date::now();
+    let (year, month, day) = date::civil_from_days(now.div_euclid(86400));
+    let seconds = now.rem_euclid(86400);
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+impl Log for FileLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let line = format!("{} {:<5} {}\n", timestamp(), record.level(), record.args());
+        // Commands print their result on stdout, so keep it clean for them
+        if record.level() <= Level::Warn || args::headless() {
+            eprint!("{line}");
+        } else {
+            print!("{line}");
+        }
+
+        let mut file = self.file.lock();
+        if file.as_ref().is_some_and(|(_, size)| *size > MAX_LOG_SIZE) {
+            *file = None;
+            rotate();
+        }
+        if file.is_none() {
+            *file = open_log();
+        }
+        if let Some((file, size)) = file.as_mut() {
+            if file.write_all(line.as_bytes()).is_ok() {
+                *size += line.len() as u64;
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Some((file, _)) = self.file.lock().as_mut() {
+            let _ = file.flush();
+        }
+    }
+}
+
+pub fn log_file_path() -> PathBuf {
+    rotated_path(0)
+}
+
+// The last lines of the current log file
+pub fn tail(max_lines: usize) -> String {
+    let contents = fs::read_to_string(log_file_path()).unwrap_or_default();
+    let lines: Vec<&str> = contents.lines().collect();
+    lines[lines.len().saturating_sub(max_lines)..].join("\n")
+}
+
+static LOGGER: FileLogger = FileLogger {
+    file: Mutex::new(None),
+};
+
+// Debug messages are only logged with --verbose until the settings are
+// loaded, see configure
+pub fn init() {
+    if log::set_logger(&LOGGER).is_ok() {
+        configure(false);
+    }
+}
+
+pub fn configure(verbose: bool) {
+    log::set_max_level(if verbose || args::get().verbose {
+        LevelFilter::Debug
+    } else {
+        LevelFilter::Info
+    });
+}
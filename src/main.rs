@@ -4,43 +4,167 @@
 #![allow(clippy::cast_possible_truncation)]
 #![allow(dead_code)]
 
-use app_state::AppStateManager;
+use app_state::{AppStateManager, Submenu};
+use config::Config;
+use i18n::tr;
+use instance::InstanceLock;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::OnceLock;
 use std::thread;
 use ui::run_ui;
-use update::{do_nextui_release_check, do_self_update};
+use update::do_startup_checks;
 
+mod about;
 mod app_state;
+mod args;
+mod cancel;
+mod command;
+mod config;
+mod crash;
+mod date;
+mod device;
+mod diagnostics;
+mod error;
+mod fault;
+mod feedback;
 mod github;
+mod headless;
+mod history;
+mod i18n;
+mod input;
+mod instance;
+mod keyboard;
+mod logging;
+mod network;
+mod nextui_settings;
+mod notify;
+mod power;
+mod release_filter;
+mod rollout;
+mod search;
+mod simulate;
+mod status;
+mod storage;
 mod ui;
 mod update;
+mod version;
 
 // Constants
 pub const SDCARD_ROOT: &str = "/mnt/SDCARD/";
+pub const UPDATER_DATA_DIR: &str = ".userdata/nextui-updater/";
+// Overrides SDCARD_ROOT, same as --sdcard
+pub const SDCARD_ROOT_VAR: &str = "NEXTUI_UPDATER_SDCARD";
+
+static SDCARD_ROOT_CELL: OnceLock<String> = OnceLock::new();
+
+// Where the SD card is, or a directory standing in for it when running off
+// the device. Always ends with a slash.
+pub fn sdcard_root() -> &'static str {
+    SDCARD_ROOT_CELL.get_or_init(|| {
+        let root = args::get()
+            .sdcard
+            .clone()
+            .or_else(|| std::env::var(SDCARD_ROOT_VAR).ok())
+            .filter(|root| !root.is_empty())
+            .unwrap_or_else(|| {
+                if simulate::active() {
+                    simulate::default_sandbox()
+                } else {
+                    SDCARD_ROOT.to_string()
+                }
+            });
+        if root.ends_with('/') {
+            root
+        } else {
+            root + "/"
+        }
+    })
+}
+
+// True when running against a directory other than the real SD card, so
+// nothing should touch the system itself, e.g. rebooting
+#[must_use]
+pub fn sdcard_overridden() -> bool {
+    sdcard_root() != SDCARD_ROOT
+}
 
 // Error type for the application
 pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
 
 fn main() -> Result<()> {
+    // Parsed first, since --sdcard decides where the log goes
+    args::parse();
+    logging::init();
+    log::info!("NextUI Updater {} starting", env!("CARGO_PKG_VERSION"));
+    args::log_warnings();
+    if sdcard_overridden() {
+        log::info!("Using {} as the SD card", sdcard_root());
+    }
+    if simulate::active() {
+        simulate::prepare();
+    }
+    if let Some(command) = args::get().command {
+        std::process::exit(run_command(command));
+    }
+
     // Initialize application state
     let app_state: &'static AppStateManager = Box::leak(Box::new(AppStateManager::new()));
+    crash::install_panic_hook(app_state);
+    app_state.set_init_status(Some("Loading settings...".to_string()));
 
-    // Get current NextUI version
-    let version_file =
-        std::fs::read_to_string(SDCARD_ROOT.to_owned() + ".system/version.txt").unwrap_or_default();
-    let current_sha = version_file
-        .lines()
-        .nth(1)
-        .map(std::borrow::ToOwned::to_owned);
-    app_state.set_current_version(current_sha);
-
-    // Self-update
-    let app_state_clone = app_state.clone();
-    thread::spawn(move || {
-        do_self_update(&app_state_clone);
-        do_nextui_release_check(&app_state_clone);
-    });
-
-    run_ui(app_state)?;
+    // Only one updater may write to the SD card at a time
+    let instance_lock = InstanceLock::acquire();
+    if let Err(pid) = &instance_lock {
+        log::warn!("Another updater instance is running (PID {pid})");
+        app_state.set_init_status(None);
+        app_state.reset_submenu(Submenu::AlreadyRunning);
+    } else {
+        // Initialization and self-update
+        let app_state_clone = app_state.clone();
+        thread::spawn(move || {
+            init(&app_state_clone);
+            do_startup_checks(&app_state_clone);
+        });
+    }
+
+    // A panic in the UI itself leaves SDL and GL half torn down, so exit
+    // instead of drawing the crash screen with them. The panic hook has
+    // saved the crash report by now.
+    let Ok(result) = panic::catch_unwind(AssertUnwindSafe(|| run_ui(app_state))) else {
+        return Err("The UI crashed, see the crash report".into());
+    };
+    result?;
 
     Ok(())
 }
+
+// Headless, for scripts. Holds the instance lock like the UI would.
+fn run_command(command: args::Command) -> i32 {
+    let app_state = AppStateManager::new();
+    let Ok(_instance_lock) = InstanceLock::acquire() else {
+        eprintln!("Another updater instance is running");
+        return 1;
+    };
+    init(&app_state);
+    headless::run(&app_state, command)
+}
+
+// Load settings and the installed version off the UI thread, so the splash
+// screen can be shown right away
+fn init(app_state: &AppStateManager) {
+    let config = Config::load();
+    logging::configure(config.verbose_logging);
+    update::configure_client(&config);
+    i18n::init(&config);
+    app_state.set_config(config);
+    notify::clear_badge();
+
+    app_state.set_init_status(Some(tr!("Checking SD card...").to_string()));
+    app_state.set_limitations(device::limitations());
+
+    // Get current NextUI version
+    app_state.set_init_status(Some(tr!("Reading installed version...").to_string()));
+    app_state.set_current_version(device::installed_commit());
+
+    app_state.set_init_status(None);
+}
@@ -0,0 +1,17 @@
+use std::fs;
+
+// Check for a default route on a non-loopback interface, which is a good hint
+// that Wi-Fi is connected. If the routing table can't be read (e.g. when not
+// running on Linux), assume we're online and let the request fail instead.
+pub fn has_network() -> bool {
+    let Ok(routes) = fs::read_to_string("/proc/net/route") else {
+        return true;
+    };
+
+    routes.lines().skip(1).any(|line| {
+        let mut fields = line.split_whitespace();
+        let iface = fields.next().unwrap_or_default();
+        let destination = fields.next().unwrap_or_default();
+        iface != "lo" && destination == "00000000"
+    })
+}
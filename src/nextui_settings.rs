@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use crate::sdcard_root;
+
+// NextUI's own settings, shared with every pak
+const NEXTUI_SETTINGS: &str = ".userdata/shared/minuisettings.txt";
+
+// Parsed minuisettings.txt: one `key=value` per line, e.g. `font=1` or
+// `color2=0x9B2257`. Missing if NextUI hasn't written it yet.
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+pub struct NextUISettings {
+    values: HashMap<String, String>,
+}
+
+impl NextUISettings {
+    pub fn path() -> PathBuf {
+        PathBuf::from(sdcard_root()).join(NEXTUI_SETTINGS)
+    }
+
+    pub fn load() -> Self {
+        fs::read_to_string(Self::path())
+            .map(|contents| Self::parse(&contents))
+            .unwrap_or_default()
+    }
+
+    pub fn parse(contents: &str) -> Self {
+        let values = contents
+            .lines()
+            .filter_map(|line| line.split_once('='))
+            .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+            .collect();
+        Self { values }
+    }
+
+    // When the file last changed, to notice NextUI changing a setting
+    pub fn modified() -> Option<SystemTime> {
+        fs::metadata(Self::path())
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values
+            .get(key)
+            .map(String::as_str)
+            .filter(|value| !value.is_empty())
+    }
+
+    // Index of the selected system font, 0 unless NextUI says otherwise
+    pub fn font(&self) -> usize {
+        self.get("font")
+            .and_then(|font| font.parse().ok())
+            .unwrap_or(0)
+    }
+
+    pub fn language(&self) -> Option<&str> {
+        self.get("language")
+    }
+
+    // RGB color setting, written by NextUI as `0xRRGGBB`
+    pub fn color(&self, key: &str) -> Option<[u8; 3]> {
+        let value = self.get(key)?;
+        let hex = value
+            .strip_prefix("0x")
+            .or_else(|| value.strip_prefix("0X"))
+            .or_else(|| value.strip_prefix('#'))
+            .unwrap_or(value);
+        let rgb = u32::from_str_radix(hex, 16)
+            .ok()
+            .filter(|rgb| *rgb <= 0xFF_FFFF)?;
+        let [_, r, g, b] = rgb.to_be_bytes();
+        Some([r, g, b])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SETTINGS: &str = "font=1\ncolor1=0xFFFFFF\ncolor2 = 0x9B2257\nlanguage=\nnot a setting\n";
+
+    #[test]
+    fn settings_are_parsed() {
+        let settings = NextUISettings::parse(SETTINGS);
+        assert_eq!(settings.get("font"), Some("1"));
+        assert_eq!(settings.get("color2"), Some("0x9B2257"));
+        // Empty values count as unset
+        assert_eq!(settings.language(), None);
+        assert_eq!(settings.get("not a setting"), None);
+    }
+
+    #[test]
+    fn fonts_default_to_the_first() {
+        assert_eq!(NextUISettings::parse(SETTINGS).font(), 1);
+        assert_eq!(NextUISettings::parse("font=serif").font(), 0);
+        assert_eq!(NextUISettings::default().font(), 0);
+    }
+
+    #[test]
+    fn colors_are_parsed_from_hex() {
+        let settings = NextUISettings::parse(SETTINGS);
+        assert_eq!(settings.color("color1"), Some([0xFF, 0xFF, 0xFF]));
+        assert_eq!(settings.color("color2"), Some([0x9B, 0x22, 0x57]));
+        let settings = NextUISettings::parse("a=#9B2257\nb=9b2257\nc=0x1FFFFFF\nd=red");
+        assert_eq!(settings.color("a"), Some([0x9B, 0x22, 0x57]));
+        assert_eq!(settings.color("b"), Some([0x9B, 0x22, 0x57]));
+        assert_eq!(settings.color("c"), None);
+        assert_eq!(settings.color("d"), None);
+        assert_eq!(settings.color("missing"), None);
+    }
+}
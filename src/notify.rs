@@ -0,0 +1,193 @@
+use std::fs;
+use std::path::PathBuf;
+use std::thread;
+
+use log::{debug, warn};
+use serde_json::json;
+
+use crate::app_state::AppStateManager;
+use crate::config::Config;
+use crate::feedback::{Cue, Rumble};
+use crate::update::post_json;
+use crate::{sdcard_root, status, UPDATER_DATA_DIR};
+
+// Left for NextUI or other paks to show, removed once the updater is opened again
+const BADGE_FILE: &str = "badge.txt";
+
+// TrimUI's RGB LED animation driver
+const LED_DIR: &str = "/sys/class/led_anim";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Success,
+    Failure,
+}
+
+// Something finished that the user should hear about
+#[derive(Clone, Debug)]
+pub struct Notification {
+    pub outcome: Outcome,
+    pub message: String,
+}
+
+impl Notification {
+    pub fn success(message: impl Into<String>) -> Self {
+        Self {
+            outcome: Outcome::Success,
+            message: message.into(),
+        }
+    }
+
+    pub fn failure(message: impl Into<String>) -> Self {
+        Self {
+            outcome: Outcome::Failure,
+            message: message.into(),
+        }
+    }
+}
+
+// One way of telling the user. Sinks shouldn't block or fail loudly, a
+// notification that doesn't arrive is only logged.
+trait Sink {
+    fn notify(&self, app_state: &AppStateManager, notification: &Notification);
+}
+
+// The notice line, or the error line for failures
+struct Toast;
+
+impl Sink for Toast {
+    fn notify(&self, app_state: &AppStateManager, notification: &Notification) {
+        match notification.outcome {
+            Outcome::Success => app_state.set_notice(Some(notification.message.clone())),
+            Outcome::Failure => app_state.set_error(Some(notification.message.clone())),
+        }
+    }
+}
+
+// Played by the UI thread, which owns the audio device
+struct Sound;
+
+impl Sink for Sound {
+    fn notify(&self, app_state: &AppStateManager, notification: &Notification) {
+        app_state.push_cue(match notification.outcome {
+            Outcome::Success => Cue::Success,
+            Outcome::Failure => Cue::Failure,
+        });
+    }
+}
+
+// Also played by the UI thread, which owns the controller
+struct Vibration;
+
+impl Sink for Vibration {
+    fn notify(&self, app_state: &AppStateManager, notification: &Notification) {
+        app_state.push_rumble(match notification.outcome {
+            Outcome::Success => Rumble::Success,
+            Outcome::Failure => Rumble::Failure,
+        });
+    }
+}
+
+// Blink the LEDs green or red
+struct Led;
+
+impl Sink for Led {
+    fn notify(&self, _app_state: &AppStateManager, notification: &Notification) {
+        let color = match notification.outcome {
+            Outcome::Success => "00FF00",
+            Outcome::Failure => "FF0000",
+        };
+        let dir = PathBuf::from(LED_DIR);
+        let result = [
+            ("effect_rgb_hex_lr", color),
+            ("effect_cycles_lr", "3"),
+            ("effect_duration_lr", "500"),
+            ("effect_lr", "3"),
+        ]
+        .into_iter()
+        .try_for_each(|(name, value)| fs::write(dir.join(name), value));
+        if let Err(err) = result {
+            debug!("LED notification failed: {err}");
+        }
+    }
+}
+
+// Small text file with the last result
+struct Badge;
+
+impl Badge {
+    fn path() -> PathBuf {
+        PathBuf::from(sdcard_root())
+            .join(UPDATER_DATA_DIR)
+            .join(BADGE_FILE)
+    }
+}
+
+impl Sink for Badge {
+    fn notify(&self, _app_state: &AppStateManager, notification: &Notification) {
+        if let Err(err) = fs::write(Self::path(), &notification.message) {
+            warn!("Failed to write badge file: {err}");
+        }
+    }
+}
+
+// The last result in the status file, which unlike the badge stays around
+struct StatusFile;
+
+impl Sink for StatusFile {
+    fn notify(&self, _app_state: &AppStateManager, notification: &Notification) {
+        status::record_result(notification);
+    }
+}
+
+// POSTs a JSON summary to a user-configured URL, in the background
+struct Webhook {
+    url: String,
+}
+
+impl Sink for Webhook {
+    fn notify(&self, _app_state: &AppStateManager, notification: &Notification) {
+        let url = self.url.clone();
+        let body = json!({
+            "success": notification.outcome == Outcome::Success,
+            "message": notification.message,
+            "updater_version": env!("CARGO_PKG_VERSION"),
+        });
+        thread::spawn(move || {
+            if let Err(err) = post_json(&url, &body) {
+                warn!("Webhook notification failed: {err}");
+            }
+        });
+    }
+}
+
+fn sinks(config: &Config) -> Vec<Box<dyn Sink>> {
+    let mut sinks: Vec<Box<dyn Sink>> =
+        vec![Box::new(Toast), Box::new(Badge), Box::new(StatusFile)];
+    if config.sounds_enabled() {
+        sinks.push(Box::new(Sound));
+    }
+    if config.rumble_enabled() {
+        sinks.push(Box::new(Vibration));
+    }
+    if config.leds_enabled() {
+        sinks.push(Box::new(Led));
+    }
+    if let Some(url) = config.webhook.clone().filter(|url| !url.is_empty()) {
+        sinks.push(Box::new(Webhook { url }));
+    }
+    sinks
+}
+
+// Tell the user through every enabled sink
+pub fn send(app_state: &AppStateManager, notification: &Notification) {
+    debug!("Notification: {notification:?}");
+    for sink in sinks(&app_state.config()) {
+        sink.notify(app_state, notification);
+    }
+}
+
+// The user has seen the results now
+pub fn clear_badge() {
+    let _ = fs::remove_file(Badge::path());
+}
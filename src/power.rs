@@ -0,0 +1,63 @@
+use std::fs;
+use std::path::Path;
+
+use log::{debug, warn};
+
+const WAKE_LOCK: &str = "/sys/power/wake_lock";
+const WAKE_UNLOCK: &str = "/sys/power/wake_unlock";
+const WAKE_LOCK_NAME: &str = "nextui-updater";
+
+// Keeps the device from suspending while an operation runs, using a kernel
+// wake lock. The display is kept on separately through SDL.
+#[derive(Default)]
+pub struct KeepAwake {
+    active: bool,
+}
+
+impl KeepAwake {
+    pub fn set(&mut self, active: bool) {
+        if active == self.active {
+            return;
+        }
+        self.active = active;
+
+        let path = if active { WAKE_LOCK } else { WAKE_UNLOCK };
+        match fs::write(path, WAKE_LOCK_NAME) {
+            Ok(()) => debug!("Wake lock {}", if active { "held" } else { "released" }),
+            // Not every kernel has wake locks, e.g. when running on desktop
+            Err(err) => warn!("Failed to write {path}: {err}"),
+        }
+    }
+}
+
+impl Drop for KeepAwake {
+    fn drop(&mut self) {
+        self.set(false);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Battery {
+    pub percent: u8,
+    pub charging: bool,
+}
+
+// The first battery the kernel reports, None without one, e.g. on desktop
+pub fn battery() -> Option<Battery> {
+    let read = |supply: &Path, name: &str| {
+        fs::read_to_string(supply.join(name)).map(|value| value.trim().to_string())
+    };
+    fs::read_dir("/sys/class/power_supply")
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|supply| read(supply, "type").is_ok_and(|kind| kind == "Battery"))
+        .find_map(|supply| {
+            let percent = read(&supply, "capacity").ok()?.parse().ok()?;
+            let status = read(&supply, "status").unwrap_or_default();
+            Some(Battery {
+                percent,
+                charging: status == "Charging" || status == "Full",
+            })
+        })
+}
@@ -0,0 +1,143 @@
+use crate::date;
+use crate::github::Release;
+use crate::i18n::tr;
+use crate::search;
+
+const DAY: i64 = 24 * 60 * 60;
+
+// How long ago a release came out, to narrow down the version list
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Period {
+    #[default]
+    Any,
+    LastMonth,
+    LastThreeMonths,
+    LastYear,
+    OverAYear,
+}
+
+impl Period {
+    // Cycled through by the button above the version list
+    pub fn next(self) -> Self {
+        match self {
+            Period::Any => Period::LastMonth,
+            Period::LastMonth => Period::LastThreeMonths,
+            Period::LastThreeMonths => Period::LastYear,
+            Period::LastYear => Period::OverAYear,
+            Period::OverAYear => Period::Any,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Period::Any => tr!("Any time"),
+            Period::LastMonth => tr!("Last month"),
+            Period::LastThreeMonths => tr!("Last 3 months"),
+            Period::LastYear => tr!("Last year"),
+            Period::OverAYear => tr!("Over a year ago"),
+        }
+    }
+
+    // Releases without a date only show up without a period
+    fn contains(self, published_at: Option<i64>, now: i64) -> bool {
+        let (newest, oldest) = match self {
+            Period::Any => return true,
+            Period::LastMonth => (0, Some(31)),
+            Period::LastThreeMonths => (0, Some(92)),
+            Period::LastYear => (0, Some(365)),
+            Period::OverAYear => (365, None),
+        };
+        published_at.is_some_and(|published_at| {
+            let age = (now - published_at).div_euclid(DAY);
+            age >= newest && oldest.is_none_or(|oldest| age < oldest)
+        })
+    }
+}
+
+// What the version list is narrowed down to. Cleared when the version
+// selector is closed.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ReleaseFilter {
+    // Part of a tag, or of a release date written as YYYY-MM-DD
+    pub query: String,
+    pub period: Period,
+}
+
+impl ReleaseFilter {
+    pub fn is_empty(&self) -> bool {
+        self.query.trim().is_empty() && self.period == Period::Any
+    }
+
+    pub fn matches(&self, release: &Release, now: i64) -> bool {
+        let published_at = release
+            .published_at
+            .as_deref()
+            .and_then(date::parse_timestamp);
+        if !self.period.contains(published_at, now) {
+            return false;
+        }
+        if self.query.trim().is_empty() {
+            return true;
+        }
+        let date = published_at.map(|published_at| {
+            let (year, month, day) = date::civil_from_days(published_at.div_euclid(DAY));
+            format!("{year}-{month:02}-{day:02}")
+        });
+        search::score(&self.query, &release.tag_name).is_some()
+            || date.is_some_and(|date| search::score(&self.query, &date).is_some())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn release(tag_name: &str, published_at: Option<&str>) -> Release {
+        Release {
+            tag_name: tag_name.to_string(),
+            published_at: published_at.map(str::to_string),
+            body: None,
+            assets: vec![],
+            draft: false,
+            prerelease: false,
+        }
+    }
+
+    fn now() -> i64 {
+        date::parse_timestamp("2025-06-01T00:00:00Z").unwrap()
+    }
+
+    #[test]
+    fn periods_are_counted_in_days() {
+        let days_ago = |days: i64| Some(now() - days * DAY);
+        assert!(Period::LastMonth.contains(days_ago(12), now()));
+        assert!(!Period::LastMonth.contains(days_ago(31), now()));
+        assert!(Period::LastThreeMonths.contains(days_ago(31), now()));
+        assert!(Period::LastYear.contains(days_ago(364), now()));
+        assert!(!Period::LastYear.contains(days_ago(365), now()));
+        assert!(Period::OverAYear.contains(days_ago(365), now()));
+        assert!(!Period::OverAYear.contains(days_ago(12), now()));
+    }
+
+    #[test]
+    fn undated_releases_only_match_any_period() {
+        assert!(Period::Any.contains(None, now()));
+        assert!(!Period::LastMonth.contains(None, now()));
+        assert!(!Period::OverAYear.contains(None, now()));
+    }
+
+    #[test]
+    fn releases_match_by_tag_or_date() {
+        let recent = release("v6.2.0", Some("2025-05-20T10:00:00Z"));
+        let filter = |query: &str, period| ReleaseFilter {
+            query: query.to_string(),
+            period,
+        };
+        assert!(filter("", Period::Any).matches(&recent, now()));
+        assert!(filter("v6.2", Period::LastMonth).matches(&recent, now()));
+        assert!(filter("2025-05", Period::Any).matches(&recent, now()));
+        assert!(!filter("v7", Period::Any).matches(&recent, now()));
+        assert!(!filter("v6.2", Period::OverAYear).matches(&recent, now()));
+        assert!(!filter("2025", Period::Any).matches(&release("v6.2.0", None), now()));
+    }
+}
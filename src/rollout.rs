@@ -0,0 +1,99 @@
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+use crate::device;
+use crate::github::Release;
+
+// Percentage of devices a release is offered to, from a "Rollout: 25%" line
+// in its notes. Releases without one go to everyone.
+pub fn percentage(release: &Release) -> Option<u8> {
+    static ROLLOUT_RE: OnceLock<Regex> = OnceLock::new();
+    let rollout_re = ROLLOUT_RE.get_or_init(|| {
+        Regex::new(r"(?im)^\W*rollout(?:[ _-]percentage)?\s*[:=]\s*(\d{1,3})\s*%?")
+            .expect("Failed to compile regex")
+    });
+
+    let captures = rollout_re.captures(release.body.as_deref()?)?;
+    captures[1]
+        .parse::<u8>()
+        .ok()
+        .map(|percentage| percentage.min(100))
+}
+
+// FNV-1a, so buckets stay the same across builds
+fn hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+// This device's position in the rollout of a release, 0-99
+fn bucket(device_id: &str, tag_name: &str) -> u8 {
+    let hash = hash(format!("{device_id}/{tag_name}").as_bytes());
+    (hash % 100) as u8
+}
+
+// Whether a release should be offered as the latest version on this device
+pub fn is_offered(release: &Release, immediately: bool) -> bool {
+    // Read once, it doesn't change while the updater runs
+    static DEVICE_ID: OnceLock<String> = OnceLock::new();
+
+    let Some(percentage) = percentage(release) else {
+        return true;
+    };
+    immediately || bucket(DEVICE_ID.get_or_init(device::device_id), &release.tag_name) < percentage
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn release(body: &str) -> Release {
+        Release {
+            tag_name: "v6.2.0".to_string(),
+            published_at: None,
+            body: Some(body.to_string()),
+            assets: vec![],
+            draft: false,
+            prerelease: false,
+        }
+    }
+
+    #[test]
+    fn rollout_percentage_is_read_from_the_notes() {
+        assert_eq!(percentage(&release("Fixes\n\nRollout: 25%")), Some(25));
+        assert_eq!(percentage(&release("- rollout_percentage = 5")), Some(5));
+        assert_eq!(percentage(&release("Rollout: 250%")), Some(100));
+        assert_eq!(percentage(&release("Rolled out to everyone")), None);
+        assert_eq!(percentage(&release("Notes: rollout: 25%")), None);
+    }
+
+    #[test]
+    fn buckets_are_stable_and_spread_out() {
+        assert_eq!(bucket("device", "v6.2.0"), bucket("device", "v6.2.0"));
+        assert!((0..100).all(|n| bucket(&format!("device{n}"), "v6.2.0") < 100));
+
+        // Roughly a quarter of devices fall in the first 25 buckets
+        let early = (0..1000)
+            .filter(|n| bucket(&format!("device{n}"), "v6.2.0") < 25)
+            .count();
+        assert!((150..350).contains(&early), "{early}");
+
+        // Devices early for one release aren't always early for the next
+        let moved = (0..100)
+            .filter(|n| {
+                let id = format!("device{n}");
+                (bucket(&id, "v6.2.0") < 25) != (bucket(&id, "v6.3.0") < 25)
+            })
+            .count();
+        assert!(moved > 0);
+    }
+
+    #[test]
+    fn releases_without_a_rollout_go_to_everyone() {
+        assert!(is_offered(&release("Fixes"), false));
+        assert!(is_offered(&release("Rollout: 0%"), true));
+        assert!(!is_offered(&release("Rollout: 0%"), false));
+    }
+}
@@ -0,0 +1,147 @@
+// Case- and accent-insensitive fuzzy matching for search inputs, tuned for
+// short queries typed with the D-pad
+
+// Lowercase ASCII equivalent of a Latin letter with diacritics
+fn fold_char(c: char) -> char {
+    match c {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' | 'À' | 'Á' | 'Â' | 'Ã' | 'Ä' | 'Å'
+        | 'Ā' | 'Ă' | 'Ą' => 'a',
+        'ç' | 'ć' | 'ĉ' | 'ċ' | 'č' | 'Ç' | 'Ć' | 'Ĉ' | 'Ċ' | 'Č' => 'c',
+        'ď' | 'đ' | 'Ď' | 'Đ' => 'd',
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ĕ' | 'ė' | 'ę' | 'ě' | 'È' | 'É' | 'Ê' | 'Ë' | 'Ē' | 'Ĕ'
+        | 'Ė' | 'Ę' | 'Ě' => 'e',
+        'ĝ' | 'ğ' | 'ġ' | 'ģ' | 'Ĝ' | 'Ğ' | 'Ġ' | 'Ģ' => 'g',
+        'ì' | 'í' | 'î' | 'ï' | 'ĩ' | 'ī' | 'ĭ' | 'į' | 'ı' | 'Ì' | 'Í' | 'Î' | 'Ï' | 'Ĩ' | 'Ī'
+        | 'Ĭ' | 'Į' | 'İ' => 'i',
+        'ñ' | 'ń' | 'ņ' | 'ň' | 'Ñ' | 'Ń' | 'Ņ' | 'Ň' => 'n',
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ŏ' | 'ő' | 'Ò' | 'Ó' | 'Ô' | 'Õ' | 'Ö' | 'Ø'
+        | 'Ō' | 'Ŏ' | 'Ő' => 'o',
+        'ś' | 'ŝ' | 'ş' | 'š' | 'Ś' | 'Ŝ' | 'Ş' | 'Š' => 's',
+        'ţ' | 'ť' | 'Ţ' | 'Ť' => 't',
+        'ù' | 'ú' | 'û' | 'ü' | 'ũ' | 'ū' | 'ŭ' | 'ů' | 'ű' | 'ų' | 'Ù' | 'Ú' | 'Û' | 'Ü' | 'Ũ'
+        | 'Ū' | 'Ŭ' | 'Ů' | 'Ű' | 'Ų' => 'u',
+        'ý' | 'ÿ' | 'Ý' | 'Ÿ' => 'y',
+        'ź' | 'ż' | 'ž' | 'Ź' | 'Ż' | 'Ž' => 'z',
+        c => c.to_ascii_lowercase(),
+    }
+}
+
+// Folded form used for comparisons, e.g. "Pokémon Émeraude" -> "pokemon emeraude"
+fn normalize(text: &str) -> String {
+    text.chars()
+        .flat_map(char::to_lowercase)
+        .map(fold_char)
+        .collect()
+}
+
+// Word starts, where a query character matching counts for more
+fn is_boundary(previous: Option<char>, c: char) -> bool {
+    previous.is_none_or(|previous| !previous.is_alphanumeric() && c.is_alphanumeric())
+}
+
+// Characters of the query in order, anywhere in the candidate. Consecutive
+// matches and matches at word starts score higher, gaps lower.
+fn subsequence_score(query: &[char], candidate: &[char]) -> Option<u32> {
+    let mut score: u32 = 0;
+    let mut query_index = 0;
+    let mut last_match: Option<usize> = None;
+
+    for (index, &c) in candidate.iter().enumerate() {
+        if query_index == query.len() {
+            break;
+        }
+        if c != query[query_index] {
+            continue;
+        }
+
+        score += 10;
+        if last_match.is_some_and(|last| last + 1 == index) {
+            score += 15;
+        }
+        if is_boundary(index.checked_sub(1).map(|i| candidate[i]), c) {
+            score += 20;
+        }
+        if let Some(last) = last_match {
+            score = score.saturating_sub((index - last - 1).min(10) as u32);
+        }
+
+        last_match = Some(index);
+        query_index += 1;
+    }
+
+    (query_index == query.len()).then_some(score)
+}
+
+// How well a candidate matches the query, higher is better. None if it
+// doesn't match at all. An empty query matches everything equally.
+pub fn score(query: &str, candidate: &str) -> Option<u32> {
+    let query = normalize(query.trim());
+    if query.is_empty() {
+        return Some(0);
+    }
+    let candidate = normalize(candidate);
+
+    // Shorter candidates win ties between equally good matches
+    let length_penalty = candidate.chars().count().min(100) as u32;
+
+    if candidate.starts_with(&query) {
+        return Some(3000 - length_penalty);
+    }
+    let word_prefix = candidate
+        .split(|c: char| !c.is_alphanumeric())
+        .any(|word| word.starts_with(&query));
+    if word_prefix {
+        return Some(2000 - length_penalty);
+    }
+    if candidate.contains(&query) {
+        return Some(1000 - length_penalty);
+    }
+
+    let query: Vec<char> = query.chars().collect();
+    let candidate: Vec<char> = candidate.chars().collect();
+    subsequence_score(&query, &candidate).map(|score| score.min(900))
+}
+
+// Indices of the matching items, best match first. Equal matches keep their
+// original order.
+pub fn filter<T>(query: &str, items: &[T], text: impl Fn(&T) -> &str) -> Vec<usize> {
+    let mut matches: Vec<(usize, u32)> = items
+        .iter()
+        .enumerate()
+        .filter_map(|(index, item)| score(query, text(item)).map(|score| (index, score)))
+        .collect();
+    matches.sort_by_key(|&(_, score)| std::cmp::Reverse(score));
+    matches.into_iter().map(|(index, _)| index).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matching_ignores_case_and_accents() {
+        assert_eq!(normalize("Pokémon Émeraude"), "pokemon emeraude");
+        assert!(score("POKEMON", "Pokémon").is_some());
+        assert!(score("emer", "Pokémon Émeraude").is_some());
+        assert!(score("xyz", "Pokémon").is_none());
+    }
+
+    #[test]
+    fn better_matches_score_higher() {
+        let prefix = score("set", "Settings").unwrap();
+        let word = score("set", "Reset Settings").unwrap();
+        let inside = score("set", "Unsettled").unwrap();
+        let scattered = score("set", "Select Theme").unwrap();
+        assert!(prefix > word && word > inside && inside > scattered);
+        // Shorter candidates win ties
+        assert!(score("log", "Logs").unwrap() > score("log", "Logging").unwrap());
+    }
+
+    #[test]
+    fn filtering_keeps_matches_best_first() {
+        let items = ["Check Updates", "Settings", "Storage", "About"];
+        assert_eq!(filter("st", &items, |item| item), [2, 1]);
+        assert_eq!(filter("  ", &items, |item| item), [0, 1, 2, 3]);
+        assert!(filter("zz", &items, |item| item).is_empty());
+    }
+}
@@ -0,0 +1,66 @@
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+use log::{info, warn};
+
+use crate::app_state::AppStateManager;
+use crate::i18n::tr;
+use crate::update::BOOT_ARCHIVE;
+use crate::{args, device, sdcard_root};
+
+// Written in place of NextUI's version file when the sandbox doesn't have
+// one yet. The commit matches no release, so the latest one is offered.
+const FAKE_VERSION_FILE: &str = "NextUI (simulated)\n0000000\n";
+
+// --simulate, for running the whole update flow on a desktop
+pub fn active() -> bool {
+    args::get().simulate
+}
+
+// Used as the SD card unless --sdcard says otherwise
+pub fn default_sandbox() -> String {
+    std::env::temp_dir()
+        .join("nextui-updater-sim")
+        .to_string_lossy()
+        .into_owned()
+}
+
+// Give the sandbox what the updater expects to find on a device
+pub fn prepare() {
+    let root = Path::new(sdcard_root());
+    let version_file = root.join(".system/version.txt");
+    if version_file.exists() {
+        return;
+    }
+    let result = fs::create_dir_all(root.join(".system"))
+        .and_then(|()| fs::write(&version_file, FAKE_VERSION_FILE));
+    match result {
+        Ok(()) => info!("Simulating with a fake {}", version_file.display()),
+        Err(err) => warn!("Failed to write {}: {err}", version_file.display()),
+    }
+}
+
+// Stands in for rebooting after an update: NextUI unpacks MinUI.zip on the
+// way up, then the updater would start again on the new version
+pub fn reboot(app_state: &AppStateManager) {
+    info!("Simulated reboot");
+    let archive = PathBuf::from(sdcard_root()).join(BOOT_ARCHIVE);
+    if archive.exists() {
+        if let Err(err) = unpack(&archive) {
+            warn!("Failed to unpack {}: {err}", archive.display());
+        }
+    }
+
+    let version = device::installed_commit();
+    info!("Now on {}", version.as_deref().unwrap_or("unknown version"));
+    app_state.set_current_version(version);
+    app_state.set_notice(Some(tr!("Simulated reboot finished").to_string()));
+    app_state.finish_operation();
+}
+
+fn unpack(archive: &Path) -> zip::result::ZipResult<()> {
+    zip::ZipArchive::new(File::open(archive)?)?.extract(sdcard_root())?;
+    fs::remove_file(archive)?;
+    info!("Unpacked {}", archive.display());
+    Ok(())
+}
@@ -0,0 +1,157 @@
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use log::warn;
+
+use crate::app_state::AppStateManager;
+use crate::notify::{Notification, Outcome};
+use crate::update::atomic;
+use crate::{date, sdcard_root, UPDATER_DATA_DIR};
+
+// Kept up to date for NextUI or other paks, e.g. to show an "update
+// available" badge without launching the updater. One `key=value` per line,
+// so shell scripts can read it with grep.
+const STATUS_FILE: &str = "status.txt";
+
+// Everything in the file. Keys that aren't known yet are left out.
+#[derive(Default, Debug, PartialEq, Eq)]
+struct Status {
+    // Unix timestamp of the last successful release check
+    last_check: Option<i64>,
+    installed_version: Option<String>,
+    latest_version: Option<String>,
+    update_available: Option<bool>,
+    // "success" or "failure"
+    last_result: Option<String>,
+    last_result_message: Option<String>,
+    last_result_at: Option<i64>,
+}
+
+fn path() -> PathBuf {
+    PathBuf::from(sdcard_root())
+        .join(UPDATER_DATA_DIR)
+        .join(STATUS_FILE)
+}
+
+impl Status {
+    fn load() -> Self {
+        Self::load_from(&path())
+    }
+
+    fn load_from(path: &Path) -> Self {
+        let mut status = Self::default();
+        let Ok(contents) = fs::read_to_string(path) else {
+            return status;
+        };
+        for (key, value) in contents.lines().filter_map(|line| line.split_once('=')) {
+            let value = value.to_string();
+            match key {
+                "last_check" => status.last_check = value.parse().ok(),
+                "installed_version" => status.installed_version = Some(value),
+                "latest_version" => status.latest_version = Some(value),
+                "update_available" => status.update_available = Some(value == "1"),
+                "last_result" => status.last_result = Some(value),
+                "last_result_message" => status.last_result_message = Some(value),
+                "last_result_at" => status.last_result_at = value.parse().ok(),
+                _ => {}
+            }
+        }
+        status
+    }
+
+    fn save(&self) {
+        if let Err(err) = self.save_to(&path()) {
+            warn!("Failed to write status file: {err}");
+        }
+    }
+
+    // Written atomically, so a script never reads half a file
+    fn save_to(&self, path: &Path) -> io::Result<()> {
+        let mut contents = String::new();
+        let mut line = |key: &str, value: Option<String>| {
+            if let Some(value) = value {
+                // Values are single lines, e.g. multi-line error messages are joined
+                let _ = writeln!(contents, "{key}={}", value.replace('\n', " "));
+            }
+        };
+        line("last_check", self.last_check.map(|at| at.to_string()));
+        line("installed_version", self.installed_version.clone());
+        line("latest_version", self.latest_version.clone());
+        line(
+            "update_available",
+            self.update_available
+                .map(|available| u8::from(available).to_string()),
+        );
+        line("last_result", self.last_result.clone());
+        line("last_result_message", self.last_result_message.clone());
+        line(
+            "last_result_at",
+            self.last_result_at.map(|at| at.to_string()),
+        );
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        atomic::write(path, contents.as_bytes())
+    }
+}
+
+// After fetching the releases: what's installed and what's latest
+pub fn record_check(app_state: &AppStateManager) {
+    let mut status = Status::load();
+    status.last_check = Some(date::now());
+    status.installed_version = app_state
+        .installed_tag_name()
+        .or(app_state.current_version());
+    status.latest_version = app_state.nextui_release().map(|release| release.tag_name);
+    status.update_available = app_state.update_available();
+    status.save();
+}
+
+// How the last update (or other operation worth telling about) went
+pub fn record_result(notification: &Notification) {
+    let mut status = Status::load();
+    status.last_result = Some(
+        match notification.outcome {
+            Outcome::Success => "success",
+            Outcome::Failure => "failure",
+        }
+        .to_string(),
+    );
+    status.last_result_message = Some(notification.message.clone());
+    status.last_result_at = Some(date::now());
+    status.save();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_survives_a_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nextui-updater").join(STATUS_FILE);
+        let status = Status {
+            last_check: Some(1_740_000_000),
+            installed_version: Some("v6.2.0".to_string()),
+            latest_version: Some("v6.3.0".to_string()),
+            update_available: Some(true),
+            last_result: Some("failure".to_string()),
+            last_result_message: Some("Download failed".to_string()),
+            last_result_at: Some(1_740_000_100),
+        };
+        status.save_to(&path).unwrap();
+        assert_eq!(Status::load_from(&path), status);
+    }
+
+    #[test]
+    fn missing_status_files_are_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(
+            Status::load_from(&dir.path().join(STATUS_FILE)),
+            Status::default()
+        );
+    }
+}
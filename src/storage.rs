@@ -0,0 +1,80 @@
+use std::fs;
+use std::path::Path;
+use std::thread;
+
+use crate::app_state::AppStateManager;
+use crate::cancel::CancellationToken;
+use crate::device::{self, DiskSpace};
+use crate::error::{Result, UpdaterError};
+use crate::i18n::tr;
+use crate::sdcard_root;
+use crate::update::cache_size;
+
+// Folders on the SD card worth knowing the size of before a full update
+pub const FOLDERS: [&str; 3] = ["Roms", "Saves", ".system"];
+
+#[derive(Clone, Debug, Default)]
+pub struct StorageReport {
+    pub space: Option<DiskSpace>,
+    // Size of each of FOLDERS, None until it's been added up
+    pub folders: Vec<(&'static str, Option<u64>)>,
+    // Size of the download cache
+    pub cache: u64,
+}
+
+pub fn format_size(bytes: u64) -> String {
+    if bytes >= 1024 * 1024 * 1024 {
+        format!("{:.1} GB", bytes as f64 / (1024.0 * 1024.0 * 1024.0))
+    } else if bytes >= 1024 * 1024 {
+        format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+    } else {
+        format!("{} KB", bytes.div_ceil(1024))
+    }
+}
+
+// Total size of the files under a folder. Symlinks aren't followed, and
+// anything that can't be read counts as empty.
+pub fn folder_size(path: &Path, token: &CancellationToken) -> Result<u64> {
+    let mut size = 0;
+    let Ok(entries) = fs::read_dir(path) else {
+        return Ok(0);
+    };
+    for entry in entries.flatten() {
+        token.check()?;
+        let Ok(metadata) = entry.path().symlink_metadata() else {
+            continue;
+        };
+        if metadata.is_dir() {
+            size += folder_size(&entry.path(), token)?;
+        } else if metadata.is_file() {
+            size += metadata.len();
+        }
+    }
+    Ok(size)
+}
+
+// Add up the size of each folder for the storage screen, filling in the
+// report as it goes. Leaving the screen stops it.
+pub fn do_storage_scan(app_state: &'static AppStateManager) {
+    let mut report = StorageReport {
+        space: device::disk_space(),
+        folders: FOLDERS.iter().map(|folder| (*folder, None)).collect(),
+        cache: cache_size(),
+    };
+    app_state.set_storage_report(Some(report.clone()));
+    let token = app_state.screen_token();
+    thread::spawn(move || {
+        for index in 0..report.folders.len() {
+            let path = Path::new(sdcard_root()).join(report.folders[index].0);
+            match folder_size(&path, &token) {
+                Ok(size) => report.folders[index].1 = Some(size),
+                Err(UpdaterError::Cancelled) => return,
+                Err(err) => {
+                    app_state.set_error(Some(err.describe(tr!("Storage check failed"))));
+                    return;
+                }
+            }
+            app_state.set_storage_report(Some(report.clone()));
+        }
+    });
+}
@@ -1,206 +1,2307 @@
-use crate::app_state::{AppStateManager, Progress, Submenu};
-use crate::update::{do_update};
+use crate::about::{self, About};
+use crate::app_state::{AppStateManager, Progress, Snapshot, Submenu, Transfer};
+use crate::command::{Command, COMMANDS};
+use crate::config::{ButtonMapping, Channel, TextScale};
+use crate::date;
+use crate::fault::{self, FAULTS, PHASES};
+use crate::feedback::{Cue, Player};
+use crate::github::Release;
+use crate::history::{self, Outcome};
+use crate::i18n::{self, tr};
+use crate::input::{Action, Controls, Repeat, Stick, ACTIONS};
+use crate::keyboard::{self, TextEntry, TextField};
+use crate::logging;
+use crate::nextui_settings::NextUISettings;
+use crate::power::KeepAwake;
+use crate::rollout;
+use crate::search;
+use crate::storage::{do_storage_scan, format_size};
+use crate::update::{
+    do_cleanup, do_clear_cache, do_integrity_check, do_manual_self_update, do_pinned_install,
+    do_prefetch, do_reinstall, do_release_details_fetch, do_resume_install, do_startup_checks,
+    do_tag_lookup, do_update, full_update_categories, journal, rollback, set_download_limit,
+    FolderSummary, Problem,
+};
+use crate::version::{self, NextUIVersion};
 use egui::{Button, Color32, FullOutput, ProgressBar};
 use egui_backend::egui;
 use egui_backend::{sdl2::event::Event, DpiScaling, ShaderVersion};
 use egui_sdl2_gl as egui_backend;
 use egui_sdl2_gl::egui::{
-    CornerRadius, FontData, FontDefinitions, FontFamily, Pos2, Rect, RichText, Spinner, Vec2,
+    Align, Align2, CornerRadius, FontData, FontDefinitions, FontFamily, FontId, Id, LayerId, Order,
+    Pos2, Rect, RichText, ScrollArea, Spinner, Stroke, Vec2,
 };
+use log::{info, warn};
 use std::collections::BTreeMap;
+use std::fmt::Write;
 use std::path::PathBuf;
-use std::{io::Read, sync::Arc, time::Instant};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime};
+use std::{io::Read, sync::Arc};
+
+use crate::{sdcard_root, Result};
+
+const WINDOW_WIDTH: u32 = 1024;
+const WINDOW_HEIGHT: u32 = 768;
+const DPI_SCALE: f32 = 4.0;
+const FONTS: [&str; 2] = ["BPreplayBold-unhinted.otf", "chillroundm.ttf"];
+// Used when the NextUI fonts are missing, e.g. on a fresh or damaged SD card
+const FALLBACK_FONT: &[u8] = include_bytes!("../res/DejaVuSansMono-Bold.ttf");
+const RELEASE_LIST_PAGE: usize = 8;
+// How long a release has to stay highlighted before its details are prefetched
+const PREFETCH_DELAY: Duration = Duration::from_millis(500);
+// How long destructive actions have to be held down to go ahead
+const HOLD_TO_CONFIRM: Duration = Duration::from_secs(2);
+
+#[allow(clippy::too_many_lines)]
+fn nextui_ui(
+    ui: &mut egui::Ui,
+    app_state: &'static AppStateManager,
+    state: &Snapshot,
+) -> egui::Response {
+    if state.release_selection_menu {
+        if !state.release_selection_open {
+            return release_list_ui(ui, app_state, state);
+        }
+        if let Some(cautions) = &state.downgrade_cautions {
+            return downgrade_warning_ui(ui, app_state, cautions);
+        }
+    }
+
+    let current_version = state.current_version.clone();
+    let mut latest_release = state.nextui_release.clone();
+    let mut latest_tag = state.nextui_tag.clone();
+    let mut update_available = true;
+    let latest_discarded = state.nextui_tag.is_none();
+
+    if state.release_selection_menu {
+        let index = state.nextui_releases_and_tags_index.unwrap_or(0);
+        let relase_and_tag_vector = state.nextui_releases_and_tags.clone().unwrap_or_default();
+        if let Some(release_and_tag) = relase_and_tag_vector.get(index) {
+            latest_release = Some(release_and_tag.release.clone());
+            latest_tag.clone_from(&release_and_tag.tag);
+        }
+    }
+
+    let released = latest_release
+        .as_ref()
+        .map(release_date_label)
+        .unwrap_or_default();
+    let installed_tag = state.installed_tag_name();
+
+    // Show release information if available
+    match (current_version, latest_tag, latest_release) {
+        (Some(current_version), Some(tag), _) => {
+            let selected_tag = nextui_tag_label(state, &tag.name) + &released;
+            if tag.commit.sha.starts_with(&current_version) && !latest_discarded {
+                if state.release_selection_menu {
+                    // selection view
+                    ui.label(
+                        RichText::new(tr!("Selected Version:\n{selected_tag}\nThis version is currently already installed!", selected_tag)).size(10.0),
+                    );
+                } else {
+                    ui.label(
+                        RichText::new(tr!("You currently have the latest available version:\n{selected_tag}\nX to select different version", selected_tag)).size(10.0),
+                    );
+                }
+                update_available = false;
+            } else {
+                let delta = installed_tag
+                    .as_ref()
+                    .and_then(|installed| {
+                        version::compare_label(&tag.name, installed)
+                            .map(|label| format!("\n{}", tr!("{label} vs installed {installed}", label, installed)))
+                    })
+                    .unwrap_or_default();
+                if state.release_selection_menu {
+                    // selection view
+                    ui.label(
+                        RichText::new(tr!("Selected Version:\n{selected_tag}", selected_tag) + &delta).size(10.0),
+                    );
+                } else if state.config.ignored(&tag.name) {
+                    ui.label(
+                        RichText::new(tr!("Ignoring the latest version:\n{selected_tag}{delta}\nX to select different version", selected_tag, delta)).size(10.0),
+                    );
+                    update_available = false;
+                } else if let Some(pinned) = state.config.pinned_version.clone() {
+                    ui.label(
+                        RichText::new(tr!("Pinned to {pinned}\nLatest version: {selected_tag}{delta}\nX to select different version", pinned, selected_tag, delta)).size(10.0),
+                    );
+                } else {
+                    ui.label(
+                        RichText::new(tr!("New version available:\n{selected_tag}{delta}\nX to select different version", selected_tag, delta)).size(10.0),
+                    );
+                }
+            }
+        }
+        (_, _, Some(release)) => {
+            let selected_tag = nextui_tag_label(state, &release.tag_name) + &released;
+            if state.release_selection_menu {
+                // selection view
+                ui.label(RichText::new(tr!("Selected Version:\n{selected_tag}", selected_tag)).size(10.0));
+            } else {
+                ui.label(RichText::new(tr!("Latest version:\n{selected_tag}\nX to select different version", selected_tag)).size(10.0));
+            }
+        }
+        _ => {
+            ui.label(RichText::new(tr!("No release information available")).size(10.0));
+        }
+    }
+
+    if !state.limitations.is_empty() {
+        let reasons: Vec<String> = state.limitations.iter().map(ToString::to_string).collect();
+        ui.colored_label(
+            current_theme(ui).warning,
+            RichText::new(tr!("Limited mode: {reasons}", reasons = reasons.join(", "))).size(8.0),
+        );
+    }
+
+    ui.add_space(8.0);
+
+    let details_button = ui.button(tr!("Release Details"));
+    if details_button.clicked() {
+        // Entered first so a fetch started here is cancelled when leaving
+        app_state.enter_submenu(Submenu::ReleaseDetails);
+        if let Some(tag_name) = details_tag_name(state) {
+            do_release_details_fetch(app_state, &tag_name);
+        }
+    }
+
+    // Releases with several assets let the user pick instead of guessing
+    let picker_button = selected_release(state)
+        .filter(|release| release.installable_assets().len() > 1)
+        .map(|release| {
+            let label = state
+                .chosen_asset(&release.tag_name)
+                .map_or(tr!("Asset: Automatic").to_string(), |name| tr!("Asset: {name}", name));
+            ui.button(label)
+        });
+    if picker_button.as_ref().is_some_and(egui::Response::clicked) {
+        app_state.enter_submenu(Submenu::AssetPicker);
+    }
+
+    // The version selector pins the selected version. The main screen
+    // installs the pinned one, or unpins it.
+    let pinned = state.config.pinned_version.clone();
+    let pin_button = if state.release_selection_menu {
+        selected_release(state).map(|release| {
+            let is_pinned = pinned.as_deref() == Some(release.tag_name.as_str());
+            let button = ui.button(if is_pinned { tr!("Unpin Version") } else { tr!("Pin Version") });
+            if button.clicked() {
+                set_pinned_version(app_state, (!is_pinned).then_some(release.tag_name));
+            }
+            button
+        })
+    } else {
+        pinned.as_ref().map(|_| {
+            let button = ui.button(tr!("Unpin Version"));
+            if button.clicked() {
+                set_pinned_version(app_state, None);
+            }
+            button
+        })
+    };
+    let pinned_blocked = update_blocked(state);
+    let pinned_install_button = pinned
+        .as_ref()
+        .filter(|_| !state.release_selection_menu)
+        .map(|pinned| {
+            let button = action_button(
+                ui,
+                pinned_blocked.as_deref(),
+                &tr!("Reinstall Pinned Version ({pinned})", pinned),
+            );
+            if button.clicked() && pinned_blocked.is_none() {
+                app_state.set_error(None);
+                do_pinned_install(app_state);
+            }
+            button
+        });
+    let pin_hint = if state.release_selection_menu {
+        tr!("Stay on this version: newer ones aren't announced until it's unpinned")
+    } else {
+        tr!("Announce new versions again")
+    };
+
+    ui.add_space(4.0);
+
+    if update_available {
+        let blocked = update_blocked(state);
+        let quick_update_button = action_button(ui, blocked.as_deref(), tr!("Quick Update"));
+
+        // Initiate update if button clicked
+        if quick_update_button.clicked() && blocked.is_none() {
+            // Clear any previous errors
+            app_state.set_error(None);
+            do_update(app_state, false);
+        }
+
+        ui.add_space(4.0);
+
+        // Full updates overwrite everything, so they're confirmed first
+        let full_update_button = action_button(ui, blocked.as_deref(), tr!("Full Update"));
+        if full_update_button.clicked() && blocked.is_none() {
+            app_state.set_full_update_categories(full_update_categories(&state.config.source()));
+            app_state.enter_submenu(Submenu::FullUpdate);
+            if let Some(release) = selected_release(state) {
+                do_prefetch(app_state, &release, true);
+            }
+        }
+
+        // Only the latest version is announced, so only it can be ignored
+        let ignore_tag = state
+            .nextui_tag
+            .as_ref()
+            .map(|tag| tag.name.clone())
+            .filter(|_| !state.release_selection_menu && pinned.is_none());
+        let ignore_button = ignore_tag.as_ref().map(|_| ui.button(tr!("Ignore This Version")));
+        if ignore_button.as_ref().is_some_and(egui::Response::clicked) {
+            if let Some(tag_name) = &ignore_tag {
+                let mut config = app_state.config();
+                config.ignored_versions.push(tag_name.clone());
+                if let Err(err) = config.save() {
+                    app_state.set_error(Some(tr!("Failed to save settings: {err}", err)));
+                }
+                app_state.set_config(config);
+            }
+        }
+
+        // HINTS
+        if ignore_button.as_ref().is_some_and(egui::Response::has_focus) {
+            app_state.set_hint(Some(tr!(
+                "Stop announcing {version} until a newer version comes out",
+                version = ignore_tag.unwrap_or_default()
+            )));
+        } else if quick_update_button.has_focus() {
+            app_state.set_hint(Some(blocked.unwrap_or_else(|| tr!("Update MinUI.zip only").to_string())));
+        } else if full_update_button.has_focus() {
+            app_state.set_hint(Some(blocked.unwrap_or_else(|| {
+                tr!("Extract full zip files (base + extras)").to_string()
+            })));
+        } else if details_button.has_focus() {
+            app_state.set_hint(Some(tr!("Release notes and download sizes").to_string()));
+        } else if pin_button.as_ref().is_some_and(egui::Response::has_focus) {
+            app_state.set_hint(Some(pin_hint.to_string()));
+        } else if pinned_install_button.as_ref().is_some_and(egui::Response::has_focus) {
+            app_state.set_hint(Some(pinned_blocked.clone().unwrap_or_else(|| {
+                tr!("Quick update to the pinned version").to_string()
+            })));
+        } else if picker_button.as_ref().is_some_and(egui::Response::has_focus) {
+            app_state.set_hint(Some(tr!("Choose which file to download").to_string()));
+        } else {
+            app_state.set_hint(None);
+        }
+
+        quick_update_button
+    } else {
+        let blocked = update_blocked(state);
+        let reinstall_button = action_button(ui, blocked.as_deref(), tr!("Reinstall"));
+        if reinstall_button.clicked() && blocked.is_none() {
+            app_state.set_error(None);
+            do_reinstall(app_state);
+        }
+
+        let force_button = ui.button(tr!("Update anyway"));
+        if force_button.clicked() {
+            app_state.set_nextui_tag(None); // forget the tag
+        }
+
+        let quit_button = ui.button(tr!("Quit"));
+        if quit_button.clicked() {
+            if state.release_selection_menu {
+                app_state.set_release_selection_open(false);
+            } else {
+                app_state.set_should_quit(true);
+            }
+        }
+
+        if reinstall_button.has_focus() {
+            app_state.set_hint(Some(blocked.unwrap_or_else(|| {
+                tr!("Quick update to the installed version again").to_string()
+            })));
+        } else if quit_button.has_focus() {
+            if state.release_selection_menu {
+                app_state.set_hint(Some(tr!("Return to version list").to_string()));
+            } else {
+                app_state.set_hint(Some(tr!("Quit NextUI Updater").to_string()));
+            }
+        } else if force_button.has_focus() {
+            app_state.set_hint(Some(tr!("Ignore current version").to_string()));
+        } else if details_button.has_focus() {
+            app_state.set_hint(Some(tr!("Release notes and download sizes").to_string()));
+        } else if pin_button.as_ref().is_some_and(egui::Response::has_focus) {
+            app_state.set_hint(Some(pin_hint.to_string()));
+        } else if pinned_install_button.as_ref().is_some_and(egui::Response::has_focus) {
+            app_state.set_hint(Some(pinned_blocked.clone().unwrap_or_else(|| {
+                tr!("Quick update to the pinned version").to_string()
+            })));
+        } else {
+            app_state.set_hint(None);
+        }
+
+        quit_button
+    }
+}
+
+// Open the update options for a version, working out once what to warn
+// about before them
+fn open_release(app_state: &AppStateManager, index: usize) {
+    app_state.set_nextui_releases_and_tags_index(Some(index));
+    app_state.set_release_selection_open(true);
+    app_state.set_downgrade_cautions(downgrade_cautions(&app_state.snapshot()));
+}
+
+// What to warn about before going to the update options for the version
+// picked in the selector: None for an upgrade. Only an unknown installed
+// version gets the generic warning alone.
+fn downgrade_cautions(state: &Snapshot) -> Option<Vec<String>> {
+    let generic = tr!("Downgrades are not fully supported by NextUI!\n\
+        Some settings may be lost or unstable in old versions\n\
+        Manual editing of settings or files may be required")
+    .to_string();
+    let (Some(installed), Some(target)) = (state.installed_tag_name(), selected_release(state))
+    else {
+        return Some(vec![generic]);
+    };
+    let installed_version = NextUIVersion::parse(&installed);
+    let target_version = NextUIVersion::parse(&target.tag_name);
+    if let (Some(installed_version), Some(target_version)) = (installed_version, target_version) {
+        if target_version >= installed_version {
+            return None;
+        }
+    }
+    let source = state.config.source();
+    let notes = version::downgrade_notes(&source.migration_notes, &installed, &target.tag_name)
+        .unwrap_or_default();
+    Some(
+        std::iter::once(generic)
+            .chain(
+                notes
+                    .into_iter()
+                    .map(|note| tr!("Since {version}: {note}", version = note.version, note = note.note)),
+            )
+            .collect(),
+    )
+}
+
+fn downgrade_warning_ui(
+    ui: &mut egui::Ui,
+    app_state: &'static AppStateManager,
+    cautions: &[String],
+) -> egui::Response {
+    ui.add_space(16.0);
+    let theme = current_theme(ui);
+    ui.label(RichText::new(tr!("WARNING")).color(theme.warning).size(10.0));
+    ScrollArea::vertical()
+        .max_height(ui.available_height() - 72.0)
+        .show(ui, |ui| {
+            for caution in cautions {
+                ui.label(RichText::new(caution).size(10.0));
+            }
+        });
+
+    ui.add_space(8.0);
+
+    let back_button = ui.button(tr!("Return"));
+    if back_button.clicked() {
+        app_state.set_release_selection_open(false);
+    }
+
+    let confirm_button = ui.button(tr!("Accept Warning"));
+    if confirm_button.clicked() {
+        app_state.set_release_selection_confirmed(true);
+    }
+
+    if back_button.has_focus() {
+        app_state.set_hint(Some(tr!("Return to version list").to_string()));
+    } else if confirm_button.has_focus() {
+        app_state.set_hint(Some(tr!("Confirm warning and open update options").to_string()));
+    } else {
+        app_state.set_hint(None);
+    }
+
+    back_button
+}
+
+// Scrollable list of all releases, newest first
+#[allow(clippy::too_many_lines)]
+fn release_list_ui(
+    ui: &mut egui::Ui,
+    app_state: &'static AppStateManager,
+    state: &Snapshot,
+) -> egui::Response {
+    let releases_and_tags = state.nextui_releases_and_tags.clone().unwrap_or_default();
+    let current_version = state.current_version.clone();
+    let focus_request = app_state.take_release_list_focus();
+    let language = i18n::language();
+    let installed_tag = state.installed_tag_name();
+    let skip_rollout = state.config.skip_rollout;
+
+    let mut filter = state.release_filter.clone();
+    let (search_button, period_button) = ui
+        .horizontal(|ui| {
+            let query = if filter.query.is_empty() {
+                tr!("Search").to_string()
+            } else {
+                tr!("Search: {query}", query = filter.query)
+            };
+            (
+                ui.button(RichText::new(query).size(10.0)),
+                ui.button(RichText::new(filter.period.label()).size(10.0)),
+            )
+        })
+        .inner;
+    if search_button.clicked() {
+        app_state.set_text_entry(Some(TextEntry::new(TextField::ReleaseSearch, app_state)));
+        app_state.enter_submenu(Submenu::Keyboard);
+    }
+    if period_button.clicked() {
+        filter.period = filter.period.next();
+        app_state.set_release_filter(filter.clone());
+    }
+
+    // A selection the filter hides moves to the first version it shows
+    let visible = visible_releases(state);
+    let mut selected_index = state.nextui_releases_and_tags_index.unwrap_or(0);
+    if !visible.contains(&selected_index) {
+        if let Some(&first) = visible.first() {
+            selected_index = first;
+            app_state.set_nextui_releases_and_tags_index(Some(first));
+        }
+    }
+
+    let mut selected_row = None;
+    ScrollArea::vertical()
+        .max_height(ui.available_height() - 24.0)
+        .show(ui, |ui| {
+            if visible.is_empty() && !filter.is_empty() {
+                ui.label(RichText::new(tr!("No matching versions")).size(10.0));
+            }
+            for (index, release_and_tag) in releases_and_tags.iter().enumerate() {
+                if !visible.contains(&index) {
+                    continue;
+                }
+                let mut label = release_and_tag.release.tag_name.clone();
+                if let Some(published_at) = release_and_tag
+                    .release
+                    .published_at
+                    .as_deref()
+                    .and_then(date::parse_timestamp)
+                {
+                    label += "  ";
+                    label += &date::format_date(published_at, language);
+                }
+                if current_version
+                    .as_ref()
+                    .is_some_and(|v| release_and_tag.matches_commit(v))
+                {
+                    label += "  ";
+                    label += tr!("(installed)");
+                } else if release_and_tag.release.prerelease {
+                    label += "  ";
+                    label += tr!("(prerelease)");
+                } else if !rollout::is_offered(&release_and_tag.release, skip_rollout) {
+                    label += "  ";
+                    label += tr!("(rolling out)");
+                } else if let Some(delta) = installed_tag.as_ref().and_then(|installed| {
+                    version::compare_label(&release_and_tag.release.tag_name, installed)
+                }) {
+                    label += "  ";
+                    label += &delta;
+                }
+
+                let row = ui.button(RichText::new(label).size(10.0));
+
+                if row.gained_focus() {
+                    app_state.set_nextui_releases_and_tags_index(Some(index));
+                    app_state.set_release_highlighted_at(Some(Instant::now()));
+                    app_state.cancel_details_fetch(Some(&release_and_tag.release.tag_name));
+                    row.scroll_to_me(None);
+                    // Releases whose tag wasn't in the first page get it looked up now
+                    if release_and_tag.tag.is_none() {
+                        do_tag_lookup(app_state, &release_and_tag.release.tag_name);
+                    }
+                }
+                if focus_request == Some(index) {
+                    row.request_focus();
+                    row.scroll_to_me(Some(Align::Center));
+                }
+                if row.clicked() {
+                    open_release(app_state, index);
+                    // Likely to be installed while the warning is being read
+                    if app_state.downgrade_cautions().is_some() {
+                        do_prefetch(app_state, &release_and_tag.release, false);
+                    }
+                }
+
+                if index == selected_index {
+                    selected_row = Some(row);
+                }
+            }
+        });
+
+    // Prefetch details once the selection has settled
+    if state
+        .release_highlighted_at
+        .is_some_and(|highlighted_at| highlighted_at.elapsed() >= PREFETCH_DELAY)
+    {
+        app_state.set_release_highlighted_at(None);
+        if let Some(release_and_tag) = releases_and_tags.get(selected_index) {
+            do_release_details_fetch(app_state, &release_and_tag.release.tag_name);
+        }
+    } else if state.release_highlighted_at.is_some() {
+        ui.ctx().request_repaint_after(PREFETCH_DELAY);
+    }
+
+    // Download size of a quick update and how popular the release is
+    let quick_asset = state.config.source().asset_pattern(false).ok();
+    let download_stats = releases_and_tags
+        .get(selected_index)
+        .map(|release_and_tag| {
+            let release = &release_and_tag.release;
+            let size = quick_asset
+                .as_ref()
+                .and_then(|asset_re| release.asset_for(asset_re))
+                .map(|asset| format_size(asset.size) + "  ")
+                .unwrap_or_default();
+            size + &tr!("{count} downloads", count = format_count(release.download_count())) + "   "
+        })
+        .unwrap_or_default();
+    if search_button.has_focus() {
+        app_state.set_hint(Some(
+            tr!("Part of a version or release date, e.g. 6.2 or 2025-03").to_string(),
+        ));
+    } else if period_button.has_focus() {
+        app_state.set_hint(Some(tr!("Only show versions released in this period").to_string()));
+    } else {
+        app_state.set_hint(Some(download_stats + tr!("A: view   L/R: page")));
+    }
+
+    match selected_row {
+        Some(row) => row,
+        None if !filter.is_empty() => search_button,
+        None => ui.label(RichText::new(tr!("No releases available")).size(10.0)),
+    }
+}
+
+// Indices of the releases the version list filter lets through
+fn visible_releases(state: &Snapshot) -> Vec<usize> {
+    let now = date::now();
+    state
+        .nextui_releases_and_tags
+        .iter()
+        .flatten()
+        .enumerate()
+        .filter(|(_, release_and_tag)| state.release_filter.matches(&release_and_tag.release, now))
+        .map(|(index, _)| index)
+        .collect()
+}
+
+// Move the version list selection a page up or down
+fn page_release_list(app_state: &AppStateManager, down: bool) {
+    let state = app_state.snapshot();
+    let visible = visible_releases(&state);
+    if visible.is_empty() {
+        return;
+    }
+
+    let index = state.nextui_releases_and_tags_index.unwrap_or(0);
+    let position = visible
+        .iter()
+        .position(|visible_index| *visible_index >= index)
+        .unwrap_or(visible.len() - 1);
+    let position = if down {
+        (position + RELEASE_LIST_PAGE).min(visible.len() - 1)
+    } else {
+        position.saturating_sub(RELEASE_LIST_PAGE)
+    };
+    let index = visible[position];
+    app_state.set_nextui_releases_and_tags_index(Some(index));
+    app_state.set_release_list_focus(Some(index));
+}
+
+// What a full update is about to overwrite, confirmed by holding the button
+fn full_update_ui(
+    ui: &mut egui::Ui,
+    app_state: &'static AppStateManager,
+    state: &Snapshot,
+) -> egui::Response {
+    let version = selected_release(state).map_or_else(String::new, |release| release.tag_name);
+    ui.label(RichText::new(tr!("A Full Update to {version} writes:", version)).size(10.0));
+    ui.add_space(4.0);
+    for category in &state.full_update_categories {
+        ui.label(RichText::new(format!("- {category}")).size(8.0));
+    }
+
+    ui.add_space(8.0);
+
+    let blocked = update_blocked(state);
+    let (start_button, start_held) =
+        hold_button(ui, blocked.as_deref(), tr!("Start Full Update"), HOLD_TO_CONFIRM);
+    if start_held {
+        app_state.set_error(None);
+        // Started first so it can claim the prefetched download
+        do_update(app_state, true);
+        app_state.leave_submenu();
+    }
+
+    let cancel_button = ui.button(tr!("Cancel"));
+    if cancel_button.clicked() {
+        app_state.leave_submenu();
+    }
+
+    if start_button.has_focus() {
+        app_state.set_hint(Some(blocked.unwrap_or_else(|| {
+            tr!(
+                "Hold A for {seconds}s to start",
+                seconds = HOLD_TO_CONFIRM.as_secs()
+            )
+        })));
+    } else if cancel_button.has_focus() {
+        app_state.set_hint(Some(tr!("Return to update options").to_string()));
+    } else {
+        app_state.set_hint(None);
+    }
+
+    cancel_button
+}
+
+fn set_pinned_version(app_state: &AppStateManager, pinned_version: Option<String>) {
+    let mut config = app_state.config();
+    config.pinned_version = pinned_version;
+    if let Err(err) = config.save() {
+        app_state.set_error(Some(tr!("Failed to save settings: {err}", err)));
+    }
+    app_state.set_config(config);
+}
+
+// The release shown in the update options: the selected one in the version
+// selector, otherwise the latest
+fn selected_release(state: &Snapshot) -> Option<Release> {
+    if state.release_selection_menu {
+        let index = state.nextui_releases_and_tags_index.unwrap_or(0);
+        if let Some(release_and_tag) = state
+            .nextui_releases_and_tags
+            .as_ref()
+            .and_then(|releases_and_tags| releases_and_tags.get(index))
+        {
+            return Some(release_and_tag.release.clone());
+        }
+    }
+    state.nextui_release.clone()
+}
+
+fn details_tag_name(state: &Snapshot) -> Option<String> {
+    selected_release(state).map(|release| release.tag_name)
+}
+
+// Pick which of a release's assets to download, for releases with several
+fn asset_picker_ui(
+    ui: &mut egui::Ui,
+    app_state: &'static AppStateManager,
+    state: &Snapshot,
+) -> egui::Response {
+    let release = selected_release(state);
+    let tag_name = release
+        .as_ref()
+        .map(|release| release.tag_name.clone())
+        .unwrap_or_default();
+    let chosen = state.chosen_asset(&tag_name);
+
+    let automatic_button = ui.button(format!(
+        "{}{}",
+        if chosen.is_none() { "> " } else { "" },
+        tr!("Automatic")
+    ));
+    if automatic_button.clicked() {
+        app_state.set_chosen_asset(None);
+        app_state.leave_submenu();
+    }
+
+    let mut hint = None;
+    ScrollArea::vertical()
+        .max_height(ui.available_height() - 24.0)
+        .show(ui, |ui| {
+            for asset in release.iter().flat_map(Release::installable_assets) {
+                let marker = if chosen == Some(asset.name.as_str()) {
+                    "> "
+                } else {
+                    ""
+                };
+                let row = ui.button(RichText::new(format!("{marker}{}", asset.name)).size(10.0));
+                if row.gained_focus() {
+                    row.scroll_to_me(None);
+                }
+                if row.has_focus() {
+                    hint = Some(format!(
+                        "{}, {}",
+                        format_size(asset.size),
+                        tr!("{count} downloads", count = format_count(asset.download_count))
+                    ));
+                }
+                if row.clicked() {
+                    app_state.set_chosen_asset(Some((tag_name.clone(), asset.name.clone())));
+                    app_state.leave_submenu();
+                }
+            }
+        });
+
+    if automatic_button.has_focus() {
+        hint = Some(tr!("Pick the asset matching Quick or Full Update").to_string());
+    }
+    app_state.set_hint(hint);
+
+    automatic_button
+}
+
+// "12.3 MB / 45.6 MB   1.2 MB/s   0:27 left"
+fn transfer_label(transfer: &Transfer) -> String {
+    let mut label = format_size(transfer.downloaded);
+    if transfer.total > 0 {
+        label += " / ";
+        label += &format_size(transfer.total);
+    }
+    if transfer.speed > 0.0 {
+        #[allow(clippy::cast_sign_loss)]
+        let speed = transfer.speed as u64;
+        let _ = write!(label, "   {}/s", format_size(speed));
+    }
+    if let Some(eta) = transfer.eta() {
+        let seconds = eta.as_secs();
+        let remaining = format!("{}:{:02}", seconds / 60, seconds % 60);
+        let _ = write!(label, "   {}", tr!("{remaining} left", remaining));
+    }
+    label
+}
+
+// Notes, assets and commit comparison for a release. Usually already
+// prefetched while the release was highlighted in the version selector.
+fn release_details_ui(
+    ui: &mut egui::Ui,
+    app_state: &'static AppStateManager,
+    state: &Snapshot,
+) -> egui::Response {
+    let back_button = ui.button(tr!("Return"));
+    if back_button.clicked() {
+        app_state.leave_submenu();
+    }
+    app_state.set_hint(Some(tr!("Return to update options").to_string()));
+
+    ui.add_space(4.0);
+
+    let Some(tag_name) = details_tag_name(state) else {
+        ui.label(RichText::new(tr!("No release information available")).size(10.0));
+        return back_button;
+    };
+
+    let Some(details) = app_state.release_details(&tag_name) else {
+        if app_state.details_fetch_running() {
+            ui.label(RichText::new(tr!("Loading details...")).size(10.0));
+            ui.add(Spinner::new().color(Color32::WHITE));
+        } else {
+            ui.label(RichText::new(tr!("Release details unavailable")).size(10.0));
+        }
+        return back_button;
+    };
+
+    ui.label(RichText::new(nextui_tag_label(state, &tag_name)).size(10.0));
+
+    if let (Some(comparison), Some(installed)) =
+        (&details.comparison, state.installed_tag_name())
+    {
+        let summary = match comparison.status.as_str() {
+            "identical" => tr!("Same as installed").to_string(),
+            _ => tr!(
+                "{ahead} commits ahead, {behind} behind {installed}",
+                ahead = comparison.ahead_by,
+                behind = comparison.behind_by,
+                installed
+            ),
+        };
+        ui.label(
+            RichText::new(summary)
+                .color(current_theme(ui).dim)
+                .size(8.0),
+        );
+    }
+
+    for asset in &details.release.assets {
+        ui.label(RichText::new(format!("{}  {}", asset.name, format_size(asset.size))).size(8.0));
+    }
+
+    ui.add_space(4.0);
+
+    ScrollArea::vertical()
+        .max_height(ui.available_height() - 24.0)
+        .show(ui, |ui| {
+            let notes = details.release.body.as_deref().unwrap_or_default().trim();
+            ui.label(RichText::new(if notes.is_empty() { tr!("No release notes") } else { notes }).size(8.0));
+        });
+
+    back_button
+}
+
+// Button for an action that's sometimes unavailable. Unlike a disabled button
+// it can still be highlighted, so the hint bar can say why; callers ignore
+// clicks while it's blocked.
+fn action_button(ui: &mut egui::Ui, blocked: Option<&str>, label: &str) -> egui::Response {
+    let text = RichText::new(label);
+    if blocked.is_some() {
+        let theme = current_theme(ui);
+        let text = text.color(theme.disabled);
+        ui.button(if theme.high_contrast { text.strikethrough() } else { text })
+    } else {
+        ui.button(text)
+    }
+}
+
+// Where a hold_button's current press is at
+#[derive(Clone, Copy)]
+enum Hold {
+    Since(f64),
+    // Went off, nothing more until it's released
+    Done,
+}
+
+// Button that only goes off once select (or the mouse) has been held down
+// on it for `duration`, with a bar filling up underneath in the meantime. A
+// tap does nothing, so callers should say to hold it in the hint. Returns
+// whether the hold finished this frame along with the response.
+fn hold_button(
+    ui: &mut egui::Ui,
+    blocked: Option<&str>,
+    label: &str,
+    duration: Duration,
+) -> (egui::Response, bool) {
+    let response = action_button(ui, blocked, label);
+    let id = response.id.with("hold");
+    let held = blocked.is_none()
+        && ((response.has_focus() && ui.input(|i| i.key_down(egui::Key::Enter)))
+            || response.is_pointer_button_down_on());
+    let now = ui.input(|i| i.time);
+
+    let mut finished = false;
+    let mut progress = 0.0;
+    match (held, ui.data(|d| d.get_temp::<Hold>(id))) {
+        (false, _) => ui.data_mut(|d| d.remove::<Hold>(id)),
+        (true, None) => ui.data_mut(|d| d.insert_temp(id, Hold::Since(now))),
+        (true, Some(Hold::Done)) => {}
+        (true, Some(Hold::Since(start))) => {
+            progress = ((now - start) / duration.as_secs_f64()) as f32;
+            if progress >= 1.0 {
+                ui.data_mut(|d| d.insert_temp(id, Hold::Done));
+                finished = true;
+                progress = 0.0;
+            }
+        }
+    }
+
+    if progress > 0.0 {
+        let rect = response.rect;
+        let bar = Rect::from_min_size(
+            Pos2::new(rect.left(), rect.bottom() + 0.5),
+            Vec2::new(rect.width() * progress, 1.0),
+        );
+        ui.painter()
+            .rect_filled(bar, CornerRadius::ZERO, current_theme(ui).warning);
+        // Keep drawing frames while nothing else is happening
+        ui.ctx().request_repaint();
+    }
+
+    (response, finished)
+}
+
+// Why something that runs an operation can't be started right now
+fn busy_reason(state: &Snapshot) -> Option<String> {
+    state
+        .update_phase
+        .busy_reason()
+        .map(|reason| tr!("Disabled while {reason}", reason))
+}
+
+// Why an update can't be started right now, if it can't
+fn update_blocked(state: &Snapshot) -> Option<String> {
+    busy_reason(state).or_else(|| {
+        state
+            .limitations
+            .first()
+            .map(|limitation| tr!("Disabled in limited mode: {limitation}", limitation))
+    })
+}
+
+fn channel_label(channel: Channel) -> &'static str {
+    match channel {
+        Channel::Stable => tr!("Stable"),
+        Channel::Beta => tr!("Beta"),
+    }
+}
+
+fn text_scale_label(scale: TextScale) -> &'static str {
+    match scale {
+        TextScale::Small => tr!("Small"),
+        TextScale::Normal => tr!("Normal"),
+        TextScale::Large => tr!("Large"),
+    }
+}
+
+// Points are DPI_SCALE pixels at normal size, so everything grows or
+// shrinks together
+fn dpi_scale(scale: TextScale) -> f32 {
+    DPI_SCALE
+        * match scale {
+            TextScale::Small => 0.875,
+            TextScale::Normal => 1.0,
+            TextScale::Large => 1.25,
+        }
+}
+
+fn toggle_button(ui: &mut egui::Ui, enabled: bool, label: &str, value: bool) -> egui::Response {
+    ui.add_enabled(
+        enabled,
+        Button::new(format!("{label}: {}", if value { tr!("On") } else { tr!("Off") })),
+    )
+}
+
+#[allow(clippy::too_many_lines)]
+fn settings_ui(
+    ui: &mut egui::Ui,
+    app_state: &'static AppStateManager,
+    state: &Snapshot,
+) -> egui::Response {
+    let mut config = app_state.config();
+    let quiet = config.quiet_mode;
+    let mut changed = false;
+    let mut refresh_releases = false;
+
+    let quiet_button = toggle_button(ui, true, tr!("Quiet mode"), config.quiet_mode);
+    if quiet_button.clicked() {
+        config.quiet_mode = !config.quiet_mode;
+        changed = true;
+    }
+
+    // Individual settings are overridden while quiet mode is on
+    let sounds_button = toggle_button(ui, !quiet, tr!("Sounds"), config.sounds_enabled());
+    if sounds_button.clicked() {
+        config.sounds = !config.sounds;
+        changed = true;
+    }
+
+    let navigation_sounds_button = toggle_button(
+        ui,
+        !quiet && config.sounds,
+        tr!("Navigation sounds"),
+        config.navigation_sounds_enabled(),
+    );
+    if navigation_sounds_button.clicked() {
+        config.navigation_sounds = !config.navigation_sounds;
+        changed = true;
+    }
+
+    let rumble_button = toggle_button(ui, !quiet, tr!("Rumble"), config.rumble_enabled());
+    if rumble_button.clicked() {
+        config.rumble = !config.rumble;
+        changed = true;
+    }
+
+    let leds_button = toggle_button(ui, !quiet, tr!("LEDs"), config.leds_enabled());
+    if leds_button.clicked() {
+        config.leds = !config.leds;
+        changed = true;
+    }
+
+    let auto_update_button = toggle_button(ui, true, tr!("Update automatically"), config.auto_update);
+    if auto_update_button.clicked() {
+        config.auto_update = !config.auto_update;
+        changed = true;
+    }
+
+    let defer_button = toggle_button(ui, true, tr!("Install on next start"), config.defer_updates);
+    if defer_button.clicked() {
+        config.defer_updates = !config.defer_updates;
+        changed = true;
+    }
+
+    let channel_button = ui.button(tr!(
+        "Channel: {channel}",
+        channel = channel_label(config.channel)
+    ));
+    if channel_button.clicked() {
+        config.channel = config.channel.next();
+        changed = true;
+        refresh_releases = true;
+    }
+
+    let rollout_button = toggle_button(ui, true, tr!("Skip staged rollout"), config.skip_rollout);
+    if rollout_button.clicked() {
+        config.skip_rollout = !config.skip_rollout;
+        changed = true;
+    }
+
+    let signatures_button = toggle_button(ui, true, tr!("Require signatures"), config.require_signatures);
+    if signatures_button.clicked() {
+        config.require_signatures = !config.require_signatures;
+        changed = true;
+    }
+
+    let big_progress_button = toggle_button(ui, true, tr!("Large progress text"), config.big_progress);
+    if big_progress_button.clicked() {
+        config.big_progress = !config.big_progress;
+        changed = true;
+    }
+
+    let contrast_button = toggle_button(ui, true, tr!("High contrast"), config.high_contrast);
+    if contrast_button.clicked() {
+        config.high_contrast = !config.high_contrast;
+        changed = true;
+    }
+
+    let text_scale_button = ui.button(tr!(
+        "Text size: {size}",
+        size = text_scale_label(config.text_scale)
+    ));
+    if text_scale_button.clicked() {
+        config.text_scale = config.text_scale.next();
+        changed = true;
+    }
+
+    let self_update_button = toggle_button(ui, true, tr!("Update updater on start"), config.self_update);
+    if self_update_button.clicked() {
+        config.self_update = !config.self_update;
+        changed = true;
+    }
+
+    let proxy_button = ui.button(tr!(
+        "Proxy: {proxy}",
+        proxy = config.proxy.as_deref().unwrap_or(tr!("None"))
+    ));
+    if proxy_button.clicked() {
+        app_state.set_text_entry(Some(TextEntry::new(TextField::Proxy, app_state)));
+        app_state.enter_submenu(Submenu::Keyboard);
+    }
+
+    // Never shown, only whether there is one
+    let token_button = ui.button(if config.github_token.is_some() {
+        tr!("GitHub token: Set")
+    } else {
+        tr!("GitHub token: None")
+    });
+    if token_button.clicked() {
+        app_state.set_text_entry(Some(TextEntry::new(TextField::GitHubToken, app_state)));
+        app_state.enter_submenu(Submenu::Keyboard);
+    }
+
+    let download_limit = config
+        .download_limit
+        .map_or(tr!("None").to_string(), |limit| format!("{}/s", format_size(limit * 1024)));
+    let download_limit_button = ui.button(tr!("Download limit: {limit}", limit = download_limit));
+    if download_limit_button.clicked() {
+        config.download_limit = config.next_download_limit();
+        set_download_limit(config.download_limit);
+        changed = true;
+    }
+
+    let busy = busy_reason(state);
+    let repository_button = action_button(
+        ui,
+        busy.as_deref(),
+        &tr!("Repository: {repo}", repo = config.source().repo),
+    );
+    if repository_button.clicked() && busy.is_none() {
+        app_state.set_text_entry(Some(TextEntry::new(TextField::Repository, app_state)));
+        app_state.enter_submenu(Submenu::Keyboard);
+    }
+
+    let check_button = action_button(ui, busy.as_deref(), tr!("Check for updater updates"));
+    if check_button.clicked() && busy.is_none() {
+        do_manual_self_update(app_state);
+    }
+
+    ui.add_space(4.0);
+
+    let back_button = ui.button(tr!("Return"));
+    if back_button.clicked() {
+        app_state.leave_submenu();
+    }
+
+    if changed {
+        if let Err(err) = config.save() {
+            app_state.set_error(Some(tr!("Failed to save settings: {err}", err)));
+        }
+        app_state.set_config(config);
+    }
+
+    // The release list depends on it, so fetch it again
+    if refresh_releases {
+        Command::Refresh.run(app_state);
+    }
+
+    // HINTS
+    if quiet_button.has_focus() {
+        app_state.set_hint(Some(tr!("Disable sounds, rumble and LEDs at once").to_string()));
+    } else if sounds_button.has_focus() {
+        app_state.set_hint(Some(tr!("Play a sound when an update finishes or fails").to_string()));
+    } else if navigation_sounds_button.has_focus() {
+        app_state.set_hint(Some(tr!("Click when moving between and pressing buttons").to_string()));
+    } else if rumble_button.has_focus() {
+        app_state.set_hint(Some(tr!("Rumble on completion and errors").to_string()));
+    } else if leds_button.has_focus() {
+        app_state.set_hint(Some(tr!("Light effects during updates").to_string()));
+    } else if auto_update_button.has_focus() {
+        app_state.set_hint(Some(tr!("Quick update to new stable releases on launch").to_string()));
+    } else if defer_button.has_focus() {
+        app_state.set_hint(Some(tr!("Download now, install the next time the updater starts").to_string()));
+    } else if channel_button.has_focus() {
+        app_state.set_hint(Some(tr!("Beta also offers test versions that may be unstable").to_string()));
+    } else if rollout_button.has_focus() {
+        app_state.set_hint(Some(tr!("Get new releases right away instead of gradually").to_string()));
+    } else if signatures_button.has_focus() {
+        app_state.set_hint(Some(tr!("Only install signed releases").to_string()));
+    } else if big_progress_button.has_focus() {
+        app_state.set_hint(Some(tr!("Show update progress in large text").to_string()));
+    } else if contrast_button.has_focus() {
+        app_state.set_hint(Some(tr!("Brighter colors and clearer focus markers").to_string()));
+    } else if text_scale_button.has_focus() {
+        app_state.set_hint(Some(tr!("Make text and buttons smaller or larger").to_string()));
+    } else if self_update_button.has_focus() {
+        app_state.set_hint(Some(tr!("Look for a new NextUI Updater every launch").to_string()));
+    } else if proxy_button.has_focus() {
+        app_state.set_hint(Some(tr!("Send all requests through a proxy").to_string()));
+    } else if token_button.has_focus() {
+        app_state.set_hint(Some(tr!("Avoid GitHub's rate limit with a personal access token").to_string()));
+    } else if download_limit_button.has_focus() {
+        app_state.set_hint(Some(tr!("Leave room for others on the same connection").to_string()));
+    } else if repository_button.has_focus() {
+        app_state.set_hint(Some(busy.clone().unwrap_or_else(|| {
+            tr!("Follow a NextUI fork, or clear it for the official releases").to_string()
+        })));
+    } else if check_button.has_focus() {
+        app_state.set_hint(Some(
+            busy.unwrap_or_else(|| tr!("Look for a new NextUI Updater now").to_string()),
+        ));
+    } else if back_button.has_focus() {
+        app_state.set_hint(Some(tr!("Return to menu").to_string()));
+    } else {
+        app_state.set_hint(None);
+    }
+
+    quiet_button
+}
+
+fn menu_ui(
+    ui: &mut egui::Ui,
+    app_state: &'static AppStateManager,
+    state: &Snapshot,
+) -> egui::Response {
+    let settings_button = ui.button(tr!("Settings"));
+    if settings_button.clicked() {
+        app_state.enter_submenu(Submenu::Settings);
+    }
+
+    let history_button = ui.button(tr!("Update History"));
+    if history_button.clicked() {
+        app_state.set_history(history::load());
+        app_state.enter_submenu(Submenu::History);
+    }
+
+    let diagnostics_button = ui.button(tr!("Export Diagnostics"));
+    if diagnostics_button.clicked() {
+        Command::ExportDiagnostics.run(app_state);
+    }
+
+    // Repairs the installed version without going through the version selector
+    let installed = state.installed_tag_name();
+    let blocked = update_blocked(state);
+    let reinstall_button = installed
+        .as_ref()
+        .map(|_| action_button(ui, blocked.as_deref(), tr!("Reinstall Current Version")));
+    if reinstall_button.as_ref().is_some_and(egui::Response::clicked) && blocked.is_none() {
+        app_state.set_error(None);
+        app_state.reset_submenu(Submenu::NextUI);
+        do_reinstall(app_state);
+    }
+
+    let busy = busy_reason(state);
+    let integrity_button = action_button(ui, busy.as_deref(), tr!("Check Installation"));
+    if integrity_button.clicked() && busy.is_none() {
+        app_state.enter_submenu(Submenu::Integrity);
+        do_integrity_check(app_state);
+    }
+
+    let storage_button = ui.button(tr!("Storage"));
+    if storage_button.clicked() {
+        app_state.enter_submenu(Submenu::Storage);
+        do_storage_scan(app_state);
+    }
+
+    let controls_button = ui.button(tr!("Controls"));
+    if controls_button.clicked() {
+        app_state.enter_submenu(Submenu::Controls);
+    }
+
+    let controller_button = ui.button(tr!("Controller Test"));
+    if controller_button.clicked() {
+        app_state.enter_submenu(Submenu::ControllerTest);
+    }
+
+    let about_button = ui.button(tr!("About"));
+    if about_button.clicked() {
+        app_state.set_about(Some(About::probe()));
+        app_state.enter_submenu(Submenu::About);
+    }
+
+    let developer_button = app_state
+        .config()
+        .developer
+        .then(|| ui.button(tr!("Developer")));
+    if developer_button.as_ref().is_some_and(egui::Response::clicked) {
+        app_state.enter_submenu(Submenu::Developer);
+    }
+
+    ui.add_space(4.0);
+
+    let back_button = ui.button(tr!("Return"));
+    if back_button.clicked() {
+        app_state.leave_submenu();
+    }
+
+    if settings_button.has_focus() {
+        app_state.set_hint(Some(tr!("Change updater settings").to_string()));
+    } else if history_button.has_focus() {
+        app_state.set_hint(Some(tr!("Previous installs and their results").to_string()));
+    } else if diagnostics_button.has_focus() {
+        app_state.set_hint(Some(tr!("Save logs and device info for bug reports").to_string()));
+    } else if reinstall_button.as_ref().is_some_and(egui::Response::has_focus) {
+        app_state.set_hint(Some(blocked.unwrap_or_else(|| {
+            tr!(
+                "Quick update to {version} again",
+                version = installed.unwrap_or_default()
+            )
+        })));
+    } else if integrity_button.has_focus() {
+        app_state.set_hint(Some(busy.unwrap_or_else(|| {
+            tr!("Look for missing or damaged NextUI files").to_string()
+        })));
+    } else if storage_button.has_focus() {
+        app_state.set_hint(Some(tr!("What's taking up space on the SD card").to_string()));
+    } else if controls_button.has_focus() {
+        app_state.set_hint(Some(tr!("Which button does what").to_string()));
+    } else if controller_button.has_focus() {
+        app_state.set_hint(Some(tr!("Check that every button and stick works").to_string()));
+    } else if about_button.has_focus() {
+        app_state.set_hint(Some(tr!("Versions, device info and licenses").to_string()));
+    } else if developer_button.as_ref().is_some_and(egui::Response::has_focus) {
+        app_state.set_hint(Some(tr!("Simulate failures").to_string()));
+    } else if back_button.has_focus() {
+        app_state.set_hint(Some(tr!("Return to update options").to_string()));
+    } else {
+        app_state.set_hint(None);
+    }
+
+    settings_button
+}
+
+// Past installs, newest first. Selecting one offers to reinstall the version
+// that was installed before it.
+fn history_ui(
+    ui: &mut egui::Ui,
+    app_state: &'static AppStateManager,
+    state: &Snapshot,
+) -> egui::Response {
+    let entries = app_state.history();
+    let language = i18n::language();
+    let releases_and_tags = state.nextui_releases_and_tags.clone().unwrap_or_default();
+
+    let mut first_row = None;
+    let mut hint = None;
+    ScrollArea::vertical()
+        .max_height(ui.available_height() - 24.0)
+        .show(ui, |ui| {
+            for entry in entries.iter().rev() {
+                let from_version = entry.from_version.as_deref().unwrap_or("?");
+                let outcome = match &entry.outcome {
+                    Outcome::Success => tr!("OK"),
+                    Outcome::Failed(_) => tr!("FAILED"),
+                };
+                let row = ui.button(
+                    RichText::new(format!(
+                        "{}  {from_version} -> {}  {outcome}",
+                        date::format_date(entry.timestamp, language),
+                        entry.to_version,
+                    ))
+                    .size(10.0),
+                );
+
+                // The version installed before this update, if it's still available
+                let previous = releases_and_tags.iter().position(|release_and_tag| {
+                    entry.from_version.as_deref() == Some(release_and_tag.release.tag_name.as_str())
+                });
+
+                if row.gained_focus() {
+                    row.scroll_to_me(None);
+                }
+                if row.has_focus() {
+                    hint = Some(match (&entry.outcome, previous) {
+                        (Outcome::Failed(err), _) => format!("{}: {err}", entry.asset),
+                        (Outcome::Success, Some(_)) => tr!("A: reinstall {version}", version = from_version),
+                        (Outcome::Success, None) => entry.asset.clone(),
+                    });
+                }
+                if row.clicked() {
+                    if let Some(index) = previous {
+                        // Open the version selector on the previous version
+                        app_state.set_release_selection_menu(true);
+                        open_release(app_state, index);
+                        app_state.reset_submenu(Submenu::NextUI);
+                    }
+                }
+
+                first_row.get_or_insert(row);
+            }
+        });
+
+    app_state.set_hint(hint);
+
+    first_row.unwrap_or_else(|| {
+        let back_button = ui.button(tr!("Return"));
+        if back_button.clicked() {
+            app_state.leave_submenu();
+        }
+        ui.label(RichText::new(tr!("No updates recorded yet")).size(10.0));
+        back_button
+    })
+}
+
+// Arm a simulated failure for one phase of the next update or release check
+fn developer_ui(ui: &mut egui::Ui, app_state: &'static AppStateManager) -> egui::Response {
+    let armed = fault::armed();
+
+    let fault = armed.map_or(tr!("None").to_string(), |(fault, _)| fault.to_string());
+    let fault_button = ui.button(tr!("Fault: {fault}", fault));
+    if fault_button.clicked() {
+        // Cycle through the faults, then back to none
+        let next = match armed {
+            None => Some(FAULTS[0]),
+            Some((fault, _)) => FAULTS
+                .iter()
+                .position(|f| *f == fault)
+                .and_then(|index| FAULTS.get(index + 1))
+                .copied(),
+        };
+        let phase = armed.map_or(PHASES[0], |(_, phase)| phase);
+        fault::arm(next.map(|fault| (fault, phase)));
+    }
+
+    let phase_button = ui.add_enabled(
+        armed.is_some(),
+        Button::new(tr!(
+            "Phase: {phase}",
+            phase = armed.map_or("-".to_string(), |(_, phase)| phase.to_string())
+        )),
+    );
+    if phase_button.clicked() {
+        if let Some((fault, phase)) = armed {
+            let index = PHASES.iter().position(|p| *p == phase).unwrap_or(0);
+            fault::arm(Some((fault, PHASES[(index + 1) % PHASES.len()])));
+        }
+    }
+
+    ui.add_space(4.0);
+
+    let back_button = ui.button(tr!("Return"));
+    if back_button.clicked() {
+        app_state.leave_submenu();
+    }
+
+    if fault_button.has_focus() {
+        app_state.set_hint(Some(tr!("Fires once, the next time the phase runs").to_string()));
+    } else if phase_button.has_focus() {
+        app_state.set_hint(Some(tr!("When the fault is injected").to_string()));
+    } else if back_button.has_focus() {
+        app_state.set_hint(Some(tr!("Return to menu").to_string()));
+    } else {
+        app_state.set_hint(None);
+    }
+
+    fault_button
+}
+
+const TEST_BUTTONS: [sdl2::controller::Button; 15] = [
+    sdl2::controller::Button::A,
+    sdl2::controller::Button::B,
+    sdl2::controller::Button::X,
+    sdl2::controller::Button::Y,
+    sdl2::controller::Button::DPadUp,
+    sdl2::controller::Button::DPadDown,
+    sdl2::controller::Button::DPadLeft,
+    sdl2::controller::Button::DPadRight,
+    sdl2::controller::Button::LeftShoulder,
+    sdl2::controller::Button::RightShoulder,
+    sdl2::controller::Button::Start,
+    sdl2::controller::Button::Back,
+    sdl2::controller::Button::Guide,
+    sdl2::controller::Button::LeftStick,
+    sdl2::controller::Button::RightStick,
+];
+
+const TEST_AXES: [sdl2::controller::Axis; 6] = [
+    sdl2::controller::Axis::LeftX,
+    sdl2::controller::Axis::LeftY,
+    sdl2::controller::Axis::RightX,
+    sdl2::controller::Axis::RightY,
+    sdl2::controller::Axis::TriggerLeft,
+    sdl2::controller::Axis::TriggerRight,
+];
+
+// What a controller button does in the updater
+fn button_action(controls: Controls, button: sdl2::controller::Button) -> &'static str {
+    match controls.action(button) {
+        Some(action) => action.label(),
+        None if controller_to_key(controls, button).is_some() => tr!("Navigate"),
+        None => "-",
+    }
+}
+
+// Versions, the device, and the licenses of everything bundled
+fn about_ui(
+    ui: &mut egui::Ui,
+    app_state: &'static AppStateManager,
+    state: &Snapshot,
+) -> egui::Response {
+    let back_button = ui.button(tr!("Return"));
+    if back_button.clicked() {
+        app_state.leave_submenu();
+    }
+    ui.add_space(4.0);
+
+    let about = app_state.about().unwrap_or_default();
+    let unknown = || tr!("Unknown").to_string();
+    let nextui = match (
+        state.installed_tag_name().or(about.nextui_name),
+        state.current_version.clone(),
+    ) {
+        (Some(name), Some(commit)) => format!("{name} ({commit})"),
+        (name, commit) => name.or(commit).unwrap_or_else(unknown),
+    };
+    let rows = [
+        (tr!("Updater"), env!("CARGO_PKG_VERSION").to_string()),
+        (tr!("NextUI"), nextui),
+        (tr!("Device"), about.device.unwrap_or_else(unknown)),
+        (
+            tr!("Free space"),
+            about.free_space.map_or_else(unknown, format_size),
+        ),
+    ];
+    egui::Grid::new("about").show(ui, |ui| {
+        for (label, value) in rows {
+            ui.label(RichText::new(label).size(10.0));
+            ui.label(RichText::new(value).size(10.0));
+            ui.end_row();
+        }
+    });
+
+    ui.add_space(4.0);
+
+    ScrollArea::vertical()
+        .max_height(ui.available_height() - 24.0)
+        .show(ui, |ui| {
+            for (component, license) in about::NOTICES {
+                ui.label(RichText::new(format!("{component}: {license}")).size(6.0));
+            }
+            for text in [about::LICENSE, about::FONT_LICENSE] {
+                ui.add_space(4.0);
+                ui.label(RichText::new(text).monospace().size(6.0));
+            }
+        });
+
+    app_state.set_hint(None);
+
+    back_button
+}
+
+// Typing a setting in, saved when OK is pressed
+fn keyboard_ui(ui: &mut egui::Ui, app_state: &'static AppStateManager) -> egui::Response {
+    let Some(mut entry) = app_state.text_entry() else {
+        app_state.leave_submenu();
+        return ui.label("");
+    };
+
+    let output = keyboard::show(ui, &mut entry);
+    match output.finished {
+        Some(true) => {
+            if let Err(err) = entry.field.set(app_state, &entry.text) {
+                app_state.set_error(Some(tr!("Failed to save settings: {err}", err)));
+            }
+            app_state.set_text_entry(None);
+            app_state.leave_submenu();
+        }
+        Some(false) => {
+            app_state.set_text_entry(None);
+            app_state.leave_submenu();
+        }
+        None => app_state.set_text_entry(Some(entry)),
+    }
+
+    app_state.set_hint(Some(tr!("B: cancel").to_string()));
+
+    output.response
+}
+
+// The button for each action, from the config
+fn controls_ui(
+    ui: &mut egui::Ui,
+    app_state: &'static AppStateManager,
+    controls: Controls,
+) -> egui::Response {
+    let back_button = ui.button(tr!("Return"));
+    if back_button.clicked() {
+        app_state.leave_submenu();
+    }
+    ui.add_space(4.0);
+
+    egui::Grid::new("controls").show(ui, |ui| {
+        ui.label(RichText::new(tr!("Navigate")).size(10.0));
+        ui.label(RichText::new(tr!("D-pad, left stick")).size(10.0));
+        ui.end_row();
+        for action in ACTIONS {
+            ui.label(RichText::new(action.label()).size(10.0));
+            ui.label(RichText::new(controls.button(action).string()).size(10.0));
+            ui.end_row();
+        }
+    });
+
+    app_state.set_hint(Some(
+        tr!("Change them under \"buttons\" in config.json").to_string(),
+    ));
+
+    back_button
+}
+
+// Live button and axis states. Controller input doesn't navigate here, so
+// every button can be tried; Start + Select leaves.
+fn controller_test_ui(
+    ui: &mut egui::Ui,
+    app_state: &'static AppStateManager,
+    controller: Option<&sdl2::controller::GameController>,
+    controls: Controls,
+) -> egui::Response {
+    ui.ctx().request_repaint();
+    app_state.set_hint(Some(tr!("Start + Select: return").to_string()));
+
+    let Some(controller) = controller else {
+        let back_button = ui.button(tr!("Return"));
+        if back_button.clicked() {
+            app_state.leave_submenu();
+        }
+        ui.label(RichText::new(tr!("No controller found")).size(10.0));
+        return back_button;
+    };
+
+    let name = ui.label(RichText::new(controller.name()).size(8.0));
+    ui.add_space(4.0);
+
+    let theme = current_theme(ui);
+    egui::Grid::new("controller_test").show(ui, |ui| {
+        for (index, button) in TEST_BUTTONS.into_iter().enumerate() {
+            let pressed = controller.button(button);
+            let mut text = RichText::new(format!("{}: {}", button.string(), button_action(controls, button)));
+            if pressed && theme.high_contrast {
+                text = text.underline();
+            }
+            ui.label(
+                text.color(if pressed { theme.success } else { theme.dim })
+                    .size(8.0),
+            );
+            if index % 3 == 2 {
+                ui.end_row();
+            }
+        }
+        for (index, axis) in TEST_AXES.into_iter().enumerate() {
+            ui.label(
+                RichText::new(format!("{}: {:+6}", axis.string(), controller.axis(axis))).size(8.0),
+            );
+            if index % 3 == 2 {
+                ui.end_row();
+            }
+        }
+    });
+
+    name
+}
+
+// How much of the log file the logs screen shows
+const LOG_TAIL_LINES: usize = 200;
+
+// Installed files compared with the release they came from
+fn integrity_ui(
+    ui: &mut egui::Ui,
+    app_state: &'static AppStateManager,
+    state: &Snapshot,
+) -> egui::Response {
+    let back_button = ui.button(tr!("Return"));
+    if back_button.clicked() {
+        app_state.leave_submenu();
+    }
+
+    ui.add_space(4.0);
+
+    let report = app_state.integrity_report();
+    match &report {
+        None if state.error.is_none() => {
+            ui.label(RichText::new(tr!("Checking installed files...")).size(10.0));
+        }
+        None => {}
+        Some(report) if report.problems.is_empty() => {
+            ui.label(
+                RichText::new(tr!(
+                    "All {checked} files from {version} are intact",
+                    checked = format_count(report.checked),
+                    version = report.tag_name
+                ))
+                .size(10.0),
+            );
+        }
+        Some(report) => {
+            ui.label(
+                RichText::new(tr!(
+                    "{problems} of {checked} files from {version} differ\n{missing} missing, {modified} modified, {corrupted} corrupted",
+                    problems = format_count(report.problems.len()),
+                    checked = format_count(report.checked),
+                    version = report.tag_name,
+                    missing = report.count(Problem::Missing),
+                    modified = report.count(Problem::Modified),
+                    corrupted = report.count(Problem::Corrupted),
+                ))
+                .size(10.0),
+            );
+            ScrollArea::vertical()
+                .max_height(ui.available_height() - 24.0)
+                .show(ui, |ui| {
+                    for (file, problem) in &report.problems {
+                        let problem = match problem {
+                            Problem::Missing => tr!("Missing"),
+                            Problem::Modified => tr!("Modified"),
+                            Problem::Corrupted => tr!("Corrupted"),
+                        };
+                        ui.label(RichText::new(format!("{problem}: {file}")).monospace().size(6.0));
+                    }
+                });
+        }
+    }
+
+    match report {
+        Some(report) if !report.problems.is_empty() => app_state.set_hint(Some(tr!(
+            "A Full Update to {version} restores them",
+            version = report.tag_name
+        ))),
+        _ => app_state.set_hint(None),
+    }
+
+    back_button
+}
+
+// SD card size and free space, and how much the main folders take up
+fn storage_ui(
+    ui: &mut egui::Ui,
+    app_state: &'static AppStateManager,
+    state: &Snapshot,
+) -> egui::Response {
+    let back_button = ui.button(tr!("Return"));
+    if back_button.clicked() {
+        app_state.leave_submenu();
+    }
+
+    // Staging in progress would look like a leftover
+    let busy = busy_reason(state);
+    let cleanup_button = action_button(ui, busy.as_deref(), tr!("Clean Up"));
+    if cleanup_button.clicked() && busy.is_none() {
+        app_state.set_notice(None);
+        do_cleanup(app_state);
+    }
+    let clear_cache_button = action_button(ui, busy.as_deref(), tr!("Clear Cache"));
+    if clear_cache_button.clicked() && busy.is_none() {
+        app_state.set_notice(None);
+        do_clear_cache(app_state);
+    }
+
+    ui.add_space(4.0);
+
+    let report = app_state.storage_report().unwrap_or_default();
+    if let Some(space) = report.space {
+        let used = space.total.saturating_sub(space.free);
+        ui.add(
+            ProgressBar::new(used as f32 / space.total.max(1) as f32).text(
+                RichText::new(tr!(
+                    "{used} used of {total}",
+                    used = format_size(used),
+                    total = format_size(space.total)
+                ))
+                .size(10.0),
+            ),
+        );
+    }
+
+    egui::Grid::new("storage").show(ui, |ui| {
+        ui.label(RichText::new(tr!("Free")).size(10.0));
+        ui.label(
+            RichText::new(
+                report
+                    .space
+                    .map_or_else(|| tr!("Unknown").to_string(), |space| format_size(space.free)),
+            )
+            .size(10.0),
+        );
+        ui.end_row();
+        for (folder, size) in &report.folders {
+            ui.label(RichText::new(*folder).size(10.0));
+            ui.label(
+                RichText::new(size.map_or_else(|| tr!("Counting...").to_string(), format_size))
+                    .size(10.0),
+            );
+            ui.end_row();
+        }
+        ui.label(RichText::new(tr!("Download cache")).size(10.0));
+        ui.label(RichText::new(format_size(report.cache)).size(10.0));
+        ui.end_row();
+    });
+
+    if cleanup_button.has_focus() {
+        app_state.set_hint(Some(busy.unwrap_or_else(|| {
+            tr!("Remove the old updater binary and unfinished downloads").to_string()
+        })));
+    } else if clear_cache_button.has_focus() {
+        app_state.set_hint(Some(busy.unwrap_or_else(|| {
+            tr!("Remove downloads kept for reinstalling, they're downloaded again when needed")
+                .to_string()
+        })));
+    } else {
+        app_state.set_hint(Some(
+            tr!("A Full Update needs room for the download and the unpacked files").to_string(),
+        ));
+    }
+
+    back_button
+}
+
+// The end of the log file, newest at the bottom
+fn logs_ui(ui: &mut egui::Ui, app_state: &'static AppStateManager) -> egui::Response {
+    let back_button = ui.button(tr!("Return"));
+    if back_button.clicked() {
+        app_state.leave_submenu();
+    }
+
+    ui.add_space(4.0);
+
+    ScrollArea::vertical()
+        .max_height(ui.available_height() - 24.0)
+        .stick_to_bottom(true)
+        .show(ui, |ui| {
+            ui.label(RichText::new(logging::tail(LOG_TAIL_LINES)).monospace().size(6.0));
+        });
+
+    app_state.set_hint(Some(logging::log_file_path().display().to_string()));
+
+    back_button
+}
+
+// Type to filter commands, Enter runs the best match
+fn command_palette_ui(ui: &mut egui::Ui, app_state: &'static AppStateManager) -> egui::Response {
+    let mut query = app_state.command_query();
+    let input = ui.add(
+        egui::TextEdit::singleline(&mut query)
+            .hint_text(tr!("Type a command"))
+            .desired_width(160.0),
+    );
+    if input.changed() {
+        app_state.set_command_query(query.clone());
+    }
+
+    ui.add_space(4.0);
+
+    let matches = search::filter(&query, &COMMANDS, |command| command.label());
+    for &index in &matches {
+        let command = COMMANDS[index];
+        let label = match command.shortcut() {
+            Some(key) => format!("{}  ({key})", command.label()),
+            None => command.label().to_string(),
+        };
+        if ui.button(RichText::new(label).size(10.0)).clicked() {
+            command.run(app_state);
+        }
+    }
+
+    if input.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+        if let Some(&index) = matches.first() {
+            COMMANDS[index].run(app_state);
+        }
+    }
+
+    app_state.set_hint(Some(tr!("Enter: run   Esc: close").to_string()));
+
+    input
+}
+
+// Keyboard shortcut for a command, outside of the palette
+fn keyboard_command(keycode: sdl2::keyboard::Keycode) -> Option<Command> {
+    COMMANDS.into_iter().find(|command| {
+        command.shortcut().is_some_and(|key| {
+            sdl2::keyboard::Keycode::from_name(&key.to_string()) == Some(keycode)
+        })
+    })
+}
+
+// Where a version is in the version selector, if it's still available
+fn release_index(app_state: &AppStateManager, version: &str) -> Option<usize> {
+    app_state
+        .nextui_releases_and_tags()
+        .unwrap_or_default()
+        .iter()
+        .position(|release_and_tag| {
+            release_and_tag.release.tag_name == version || release_and_tag.matches_commit(version)
+        })
+}
+
+// Offered at startup when the last install didn't boot cleanly
+fn failed_install_ui(ui: &mut egui::Ui, app_state: &'static AppStateManager) -> egui::Response {
+    let failed = app_state.failed_install();
+    let to_version = failed
+        .as_ref()
+        .map_or(tr!("The last update"), |failed| failed.to_version.as_str());
+    let from_version = failed.as_ref().and_then(|failed| failed.from_version.clone());
+
+    ui.add_space(16.0);
+    ui.label(
+        RichText::new(tr!(
+            "{version} didn't finish installing\n\
+            The device may not have booted into it cleanly",
+            version = to_version
+        ))
+        .size(10.0),
+    );
+
+    ui.add_space(8.0);
+
+    let previous = from_version
+        .as_deref()
+        .and_then(|from_version| release_index(app_state, from_version));
+
+    let rollback_button = ui.add_enabled(
+        previous.is_some(),
+        egui::Button::new(tr!(
+            "Reinstall {version}",
+            version = from_version.as_deref().unwrap_or(tr!("previous version"))
+        )),
+    );
+    if rollback_button.clicked() {
+        if let Some(index) = previous {
+            // Open the version selector on the previous version
+            app_state.set_failed_install(None);
+            app_state.set_release_selection_menu(true);
+            open_release(app_state, index);
+            app_state.reset_submenu(Submenu::NextUI);
+        }
+    }
+
+    let keep_button = ui.button(tr!("Keep current version"));
+    if keep_button.clicked() {
+        app_state.set_failed_install(None);
+        app_state.leave_submenu();
+    }
+
+    if rollback_button.has_focus() {
+        app_state.set_hint(Some(tr!("Choose how to reinstall it").to_string()));
+    } else if keep_button.has_focus() {
+        app_state.set_hint(Some(tr!("Return to the updater").to_string()));
+    } else if previous.is_none() {
+        app_state.set_hint(Some(tr!("The previous version isn't available").to_string()));
+    } else {
+        app_state.set_hint(None);
+    }
+
+    if previous.is_some() {
+        rollback_button
+    } else {
+        keep_button
+    }
+}
+
+// Offered at startup when the last install stopped partway through
+// extracting, leaving a mix of old and new files
+fn interrupted_install_ui(
+    ui: &mut egui::Ui,
+    app_state: &'static AppStateManager,
+    state: &Snapshot,
+) -> egui::Response {
+    let interrupted = app_state.interrupted_install();
+    let extraction = interrupted.as_ref().map(|interrupted| &interrupted.extraction);
+    let to_version = extraction.map_or(tr!("The last update"), |extraction| {
+        extraction.tag_name.as_str()
+    });
+    let from_version = extraction.and_then(|extraction| extraction.from_version.clone());
+
+    ui.add_space(16.0);
+    ui.label(
+        RichText::new(tr!(
+            "{version} stopped installing after {count} files\n\
+            Some files may still be from the previous version",
+            version = to_version,
+            count = interrupted.as_ref().map_or(0, |interrupted| interrupted.applied)
+        ))
+        .size(10.0),
+    );
+
+    ui.add_space(8.0);
+
+    let busy = busy_reason(state);
+    let resumable = release_index(app_state, to_version).is_some();
+    let resume_button = ui.add_enabled(
+        resumable && busy.is_none(),
+        egui::Button::new(tr!("Resume installing {version}", version = to_version)),
+    );
+    if resume_button.clicked() {
+        app_state.set_interrupted_install(None);
+        app_state.reset_submenu(Submenu::NextUI);
+        do_resume_install(app_state);
+    }
+
+    let previous = from_version
+        .as_deref()
+        .and_then(|from_version| release_index(app_state, from_version));
+    let rollback_button = ui.add_enabled(
+        previous.is_some(),
+        egui::Button::new(tr!(
+            "Reinstall {version}",
+            version = from_version.as_deref().unwrap_or(tr!("previous version"))
+        )),
+    );
+    if rollback_button.clicked() {
+        if let Some(index) = previous {
+            // Open the version selector on the previous version
+            app_state.set_interrupted_install(None);
+            app_state.set_release_selection_menu(true);
+            open_release(app_state, index);
+            app_state.reset_submenu(Submenu::NextUI);
+        }
+    }
+
+    let ignore_button = ui.button(tr!("Ignore"));
+    if ignore_button.clicked() {
+        journal::discard();
+        app_state.set_interrupted_install(None);
+        app_state.leave_submenu();
+    }
+
+    if resume_button.has_focus() {
+        app_state.set_hint(Some(
+            tr!("Install it again, skipping the files already written").to_string(),
+        ));
+    } else if rollback_button.has_focus() {
+        app_state.set_hint(Some(tr!("Choose how to reinstall it").to_string()));
+    } else if ignore_button.has_focus() {
+        app_state.set_hint(Some(
+            tr!("Keep the files as they are and don't ask again").to_string(),
+        ));
+    } else if let Some(reason) = busy.clone() {
+        app_state.set_hint(Some(reason));
+    } else if !resumable {
+        app_state.set_hint(Some(
+            tr!("{version} isn't available anymore", version = to_version).to_string(),
+        ));
+    } else {
+        app_state.set_hint(None);
+    }
+
+    if resumable && busy.is_none() {
+        resume_button
+    } else if previous.is_some() {
+        rollback_button
+    } else {
+        ignore_button
+    }
+}
+
+fn offline_ui(
+    ui: &mut egui::Ui,
+    app_state: &'static AppStateManager,
+    state: &Snapshot,
+) -> egui::Response {
+    ui.add_space(16.0);
+    ui.label(
+        RichText::new(tr!(
+            "No network connection\n\
+            Connect to Wi-Fi in NextUI settings\n\
+            and press A to retry"
+        ))
+        .size(10.0),
+    );
+
+    ui.add_space(8.0);
 
-use crate::{Result, SDCARD_ROOT};
+    let busy = busy_reason(state);
+    let retry_button = action_button(ui, busy.as_deref(), tr!("Retry"));
+    if retry_button.clicked() && busy.is_none() {
+        app_state.clear_error();
+        thread::spawn(move || do_startup_checks(app_state));
+    }
 
-const WINDOW_WIDTH: u32 = 1024;
-const WINDOW_HEIGHT: u32 = 768;
-const DPI_SCALE: f32 = 4.0;
-const FONTS: [&str; 2] = ["BPreplayBold-unhinted.otf", "chillroundm.ttf"];
+    let quit_button = ui.button(tr!("Quit"));
+    if quit_button.clicked() {
+        app_state.set_should_quit(true);
+    }
 
-fn nextui_ui(ui: &mut egui::Ui, app_state: &'static AppStateManager) -> egui::Response {
-    let current_version = app_state.current_version();
-    let mut latest_release = app_state.nextui_release().clone();
-    let mut latest_tag = app_state.nextui_tag().clone();
-    let mut update_available = true;
-    let latest_discarded = app_state.nextui_tag().clone().is_none();
-
-    if app_state.release_selection_menu() {
-        let index = app_state.nextui_releases_and_tags_index().unwrap_or(0);
-        let relase_and_tag_vector = app_state.nextui_releases_and_tags().unwrap();
-        latest_release = Some(relase_and_tag_vector[index].release.clone());
-        latest_tag = Some(relase_and_tag_vector[index].tag.clone());
-    }
-
-    if app_state.release_selection_menu() & !app_state.release_selection_confirmed() {
-        ui.add_space(16.0);
-        ui.label(RichText::new("WARNING\n\
-            Downgrades are not fully supported by NextUI!\n\
-            Some settings may be lost or unstable in old versions\n\
-            Manual editing of settings or files may be required")
-            .size(10.0),);
+    if retry_button.has_focus() {
+        app_state.set_hint(Some(
+            busy.unwrap_or_else(|| tr!("Check the connection again").to_string()),
+        ));
+    } else if quit_button.has_focus() {
+        app_state.set_hint(Some(tr!("Quit NextUI Updater").to_string()));
     } else {
-        // Show release information if available
-        match (current_version, latest_tag, latest_release) {
-            (Some(current_version), Some(tag), _) => {
-                let selected_tag = hint_wrap_nextui_tag(app_state, tag.name);
-                if tag.commit.sha.starts_with(&current_version) & !latest_discarded {
-                    if app_state.release_selection_menu() {
-                        // selection view
-                        ui.label(
-                            RichText::new(format!("Selected Version:\n{}\nThis version is currently already installed!", selected_tag)).size(10.0),
-                        );
-                    } else {
-                        ui.label(
-                            RichText::new(format!("You currently have the latest available version:\n{}\nX to select different version", selected_tag)).size(10.0),
-                        );
-                    }
-                    update_available = false;
-                } else {
-                    if app_state.release_selection_menu() {
-                        // selection view
-                        ui.label(
-                            RichText::new(format!("Selected Version:\n{}", selected_tag)).size(10.0),
-                        );
-                    } else {
-                        ui.label(
-                            RichText::new(format!("New version available:\n{}\nX to select different version", selected_tag)).size(10.0),
-                        );
-                    }
-                }
-            }
-            (_, _, Some(release)) => {
-                if app_state.release_selection_menu() {
-                    // selection view
-                    let selected_tag = hint_wrap_nextui_tag(app_state, release.tag_name);
-                    ui.label(RichText::new(format!("Selected Version:\n{}", selected_tag)).size(10.0));
-                } else {
-                    ui.label(RichText::new(format!("Latest version:\nNextUI {}\nX to select different version", release.tag_name)).size(10.0));
-                }
-            }
-            _ => {
-                ui.label(RichText::new("No release information available".to_string()).size(10.0));
-            }
-        }
+        app_state.set_hint(None);
     }
 
-    ui.add_space(8.0);
+    retry_button
+}
 
-    if app_state.release_selection_menu() & !app_state.release_selection_confirmed() {
-        let back_button = ui.button("Return");
-        if back_button.clicked() {
-            app_state.set_release_selection_menu(false);
-        }
+fn already_running_ui(ui: &mut egui::Ui, app_state: &'static AppStateManager) -> egui::Response {
+    ui.add_space(16.0);
+    ui.label(
+        RichText::new(tr!(
+            "Another updater instance is running\n\
+            Close it before starting a new one"
+        ))
+        .size(10.0),
+    );
 
-        let confirm_button = ui.button("Accept Warning");
-        if confirm_button.clicked() {
-            app_state.set_release_selection_confirmed(true);
-        }
+    ui.add_space(8.0);
 
-        if back_button.has_focus() {
-            app_state.set_hint(Some("Return to Latest Version options".to_string()));
-        } else if confirm_button.has_focus() {
-            app_state.set_hint(Some("Confirm warning and open update options".to_string()));
-        } else {
-            app_state.set_hint(None);
-        }
+    let quit_button = ui.button(tr!("Quit"));
+    if quit_button.clicked() {
+        app_state.set_should_quit(true);
+    }
 
-        back_button
-    } else if update_available {
-        let quick_update_button = ui.add(Button::new("Quick Update"));
+    app_state.set_hint(Some(tr!("Quit NextUI Updater").to_string()));
 
-        // Initiate update if button clicked
-        if quick_update_button.clicked() {
-            // Clear any previous errors
-            app_state.set_error(None);
-            do_update(app_state, false);
-        }
+    quit_button
+}
 
-        ui.add_space(4.0);
+fn crashed_ui(ui: &mut egui::Ui, app_state: &'static AppStateManager) -> egui::Response {
+    ui.add_space(8.0);
+    ui.label(RichText::new(tr!("Something went wrong and the updater crashed.")).size(10.0));
+    if let Some(crash) = app_state.crash() {
+        ui.label(error_text(&current_theme(ui), &crash).size(8.0));
+    }
 
-        let full_update_button = ui.add(Button::new("Full Update"));
+    ui.add_space(8.0);
 
-        if full_update_button.clicked() {
-            // Clear any previous errors
-            app_state.set_error(None);
-            do_update(app_state, true);
-        }
+    let quit_button = ui.button(tr!("Quit"));
+    if quit_button.clicked() {
+        app_state.set_should_quit(true);
+    }
 
-        // HINTS
-        if quick_update_button.has_focus() {
-            app_state.set_hint(Some("Update MinUI.zip only".to_string()));
-        } else if full_update_button.has_focus() {
-            app_state.set_hint(Some("Extract full zip files (base + extras)".to_string()));
-        } else {
-            app_state.set_hint(None);
-        }
+    app_state.set_hint(Some(tr!("Please include the crash report when reporting this").to_string()));
 
-        quick_update_button
-    } else {
-        let force_button = ui.button("Update anyway");
-        if force_button.clicked() {
-            app_state.set_nextui_tag(None); // forget the tag
-        }
+    quit_button
+}
 
-        let quit_button = ui.button("Quit");
-        if quit_button.clicked() {
-            if app_state.release_selection_menu() {
-                app_state.set_release_selection_menu(false);
-            } else {
-                app_state.set_should_quit(true);
-            }
-        }
+// Sound for a key press, controller buttons included. Back has its own.
+fn navigation_cue(keycode: sdl2::keyboard::Keycode) -> Option<Cue> {
+    match keycode {
+        sdl2::keyboard::Keycode::Up
+        | sdl2::keyboard::Keycode::Down
+        | sdl2::keyboard::Keycode::Left
+        | sdl2::keyboard::Keycode::Right => Some(Cue::Move),
+        sdl2::keyboard::Keycode::Return => Some(Cue::Confirm),
+        _ => None,
+    }
+}
 
-        if quit_button.has_focus() {
-            if app_state.release_selection_menu() {
-                app_state.set_hint(Some("Return to Latest Version options".to_string()));
-            } else {
-                app_state.set_hint(Some("Quit NextUI Updater".to_string()));
-            }
-        } else if force_button.has_focus() {
-            app_state.set_hint(Some("Ignore current version".to_string()));
-        } else {
-            app_state.set_hint(None);
-        }
+// In the version list, the page buttons and D-pad left/right page through
+// it. Returns whether the button pages down.
+fn page_direction(
+    app_state: &AppStateManager,
+    controls: Controls,
+    button: sdl2::controller::Button,
+) -> Option<bool> {
+    let in_list = app_state.submenu() == Submenu::NextUI
+        && app_state.release_selection_menu()
+        && !app_state.release_selection_open();
+    if !in_list {
+        return None;
+    }
+    match (button, controls.action(button)) {
+        (sdl2::controller::Button::DPadLeft, _) | (_, Some(Action::PageUp)) => Some(false),
+        (sdl2::controller::Button::DPadRight, _) | (_, Some(Action::PageDown)) => Some(true),
+        _ => None,
+    }
+}
 
-        quit_button
+// Key event for egui, standing in for a controller button or the stick
+fn controller_key_event(
+    window: &sdl2::video::Window,
+    keycode: sdl2::keyboard::Keycode,
+    timestamp: u32,
+    down: bool,
+) -> Event {
+    let (keycode, window_id) = (Some(keycode), window.id());
+    let scancode = Some(sdl2::keyboard::Scancode::Down);
+    let keymod = sdl2::keyboard::Mod::empty();
+    if down {
+        Event::KeyDown { timestamp, window_id, keycode, scancode, keymod, repeat: false }
+    } else {
+        Event::KeyUp { timestamp, window_id, keycode, scancode, keymod, repeat: false }
     }
 }
 
 // Map controller buttons to keyboard keys
-fn controller_to_key(button: sdl2::controller::Button) -> Option<sdl2::keyboard::Keycode> {
+fn controller_to_key(
+    controls: Controls,
+    button: sdl2::controller::Button,
+) -> Option<sdl2::keyboard::Keycode> {
     match button {
         sdl2::controller::Button::DPadUp => Some(sdl2::keyboard::Keycode::Up),
         sdl2::controller::Button::DPadDown => Some(sdl2::keyboard::Keycode::Down),
         sdl2::controller::Button::DPadLeft => Some(sdl2::keyboard::Keycode::Left),
         sdl2::controller::Button::DPadRight => Some(sdl2::keyboard::Keycode::Right),
-        sdl2::controller::Button::B => Some(sdl2::keyboard::Keycode::Return),
-        sdl2::controller::Button::A => Some(sdl2::keyboard::Keycode::Escape),
-        sdl2::controller::Button::Y => Some(sdl2::keyboard::Keycode::X),
-        _ => None,
+        _ => match controls.action(button)? {
+            Action::Select => Some(sdl2::keyboard::Keycode::Return),
+            Action::Back => Some(sdl2::keyboard::Keycode::Escape),
+            Action::Versions => Some(sdl2::keyboard::Keycode::X),
+            Action::Menu | Action::PageUp | Action::PageDown => None,
+        },
+    }
+}
+
+// Colors picked in NextUI's settings, so the updater looks like the rest of
+// the firmware. Each one falls back to the updater's own white on black.
+#[derive(Clone, Copy, PartialEq)]
+struct Theme {
+    // Selected button background
+    main: Color32,
+    // Progress bars
+    accent: Color32,
+    text: Color32,
+    selected_text: Color32,
+    hint: Color32,
+    background: Color32,
+    // Secondary text, e.g. the header and download speed
+    dim: Color32,
+    disabled: Color32,
+    warning: Color32,
+    error: Color32,
+    success: Color32,
+    // Focus and states are also shown without relying on color
+    high_contrast: bool,
+}
+
+impl Theme {
+    fn from_settings(settings: &NextUISettings) -> Self {
+        let color = |key, default| {
+            settings
+                .color(key)
+                .map_or(default, |[r, g, b]| Color32::from_rgb(r, g, b))
+        };
+        Self {
+            main: color("color1", Color32::WHITE),
+            accent: color("color2", Color32::WHITE),
+            text: color("color4", Color32::WHITE),
+            selected_text: color("color5", Color32::BLACK),
+            hint: color("color6", Color32::WHITE),
+            background: color("color7", Color32::BLACK),
+            dim: Color32::from_rgb(150, 150, 150),
+            disabled: Color32::from_rgb(100, 100, 100),
+            warning: Color32::from_rgb(255, 200, 100),
+            error: Color32::from_rgb(255, 150, 150),
+            success: Color32::from_rgb(150, 255, 150),
+            high_contrast: false,
+        }
+    }
+
+    // Bright on black, ignoring NextUI's colors
+    fn high_contrast() -> Self {
+        Self {
+            main: Color32::YELLOW,
+            accent: Color32::YELLOW,
+            text: Color32::WHITE,
+            selected_text: Color32::BLACK,
+            hint: Color32::WHITE,
+            background: Color32::BLACK,
+            dim: Color32::WHITE,
+            disabled: Color32::from_rgb(180, 180, 180),
+            warning: Color32::YELLOW,
+            error: Color32::from_rgb(255, 100, 100),
+            success: Color32::from_rgb(100, 255, 100),
+            high_contrast: true,
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::from_settings(&NextUISettings::default())
+    }
+}
+
+// The theme of the current frame, for screens drawn outside run_ui
+fn current_theme(ui: &egui::Ui) -> Theme {
+    ui.ctx()
+        .data(|data| data.get_temp(Id::new(THEME_ID)))
+        .unwrap_or_default()
+}
+
+const THEME_ID: &str = "theme";
+
+// Error text, marked with more than its color in high contrast mode
+fn error_text(theme: &Theme, error: &str) -> RichText {
+    if theme.high_contrast {
+        RichText::new(tr!("Error: {error}", error)).color(theme.error).strong()
+    } else {
+        RichText::new(error).color(theme.error)
     }
 }
 
-fn setup_ui_style() -> egui::Style {
+// Underline and arrow next to the focused widget, so it stands out even
+// when the highlight color doesn't
+fn mark_focused(ctx: &egui::Context, theme: &Theme) {
+    let Some(rect) = ctx
+        .memory(egui::Memory::focused)
+        .and_then(|id| ctx.read_response(id))
+        .map(|response| response.rect)
+    else {
+        return;
+    };
+    let painter = ctx.layer_painter(LayerId::new(Order::Foreground, Id::new("focus_marker")));
+    painter.hline(rect.x_range(), rect.bottom() + 1.0, Stroke::new(1.0, theme.text));
+    painter.text(
+        rect.left_center() - Vec2::new(2.0, 0.0),
+        Align2::RIGHT_CENTER,
+        ">",
+        FontId::proportional(10.0),
+        theme.text,
+    );
+}
+
+fn setup_ui_style(theme: &Theme) -> egui::Style {
     let mut style = egui::Style::default();
     style.spacing.button_padding = Vec2::new(8.0, 2.0);
 
-    style.visuals.panel_fill = Color32::from_rgb(0, 0, 0);
-    style.visuals.selection.bg_fill = Color32::WHITE;
+    style.visuals.panel_fill = theme.background;
+    style.visuals.selection.bg_fill = theme.accent;
     style.visuals.selection.stroke.color = Color32::GRAY;
 
-    style.visuals.widgets.inactive.fg_stroke.color = Color32::WHITE;
+    style.visuals.widgets.inactive.fg_stroke.color = theme.text;
     style.visuals.widgets.inactive.weak_bg_fill = Color32::TRANSPARENT;
 
-    style.visuals.widgets.active.bg_fill = Color32::WHITE;
-    style.visuals.widgets.active.weak_bg_fill = Color32::WHITE;
-    style.visuals.widgets.active.fg_stroke.color = Color32::BLACK;
+    style.visuals.widgets.active.bg_fill = theme.main;
+    style.visuals.widgets.active.weak_bg_fill = theme.main;
+    style.visuals.widgets.active.fg_stroke.color = theme.selected_text;
     style.visuals.widgets.active.corner_radius = CornerRadius::same(255);
+    if theme.high_contrast {
+        style.visuals.widgets.active.bg_stroke = Stroke::new(1.0, theme.text);
+    }
 
-    style.visuals.widgets.noninteractive.fg_stroke.color = Color32::WHITE;
+    style.visuals.widgets.noninteractive.fg_stroke.color = theme.text;
     style.visuals.widgets.noninteractive.bg_fill = Color32::TRANSPARENT;
 
-    style.visuals.widgets.hovered.bg_fill = Color32::WHITE;
+    style.visuals.widgets.hovered.bg_fill = theme.main;
     style.visuals.widgets.hovered.weak_bg_fill = Color32::TRANSPARENT;
     style.visuals.widgets.hovered.corner_radius = CornerRadius::same(255);
 
@@ -229,7 +2330,7 @@ fn init_sdl() -> Result<(
         match game_controller_subsystem.open(id) {
             Ok(c) => Some(c),
             Err(e) => {
-                eprintln!("Failed to open controller {id}: {e:?}");
+                warn!("Failed to open controller {id}: {e:?}");
                 None
             }
         }
@@ -251,29 +2352,62 @@ fn init_sdl() -> Result<(
     Ok((sdl_context, window, event_pump, controller))
 }
 
-// Load font from file
-fn load_font() -> Result<FontDefinitions> {
-    fn get_font_preference() -> Result<usize> {
-        // Load NextUI settings
-        let mut settings_file =
-            std::fs::File::open(SDCARD_ROOT.to_owned() + ".userdata/shared/minuisettings.txt")?;
+const SETTINGS_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+// Watches the NextUI settings file so font and color changes apply without
+// restarting
+struct SettingsWatcher {
+    last_poll: Instant,
+    modified: Option<SystemTime>,
+    settings: NextUISettings,
+}
+
+impl SettingsWatcher {
+    fn new() -> Self {
+        Self {
+            last_poll: Instant::now(),
+            modified: NextUISettings::modified(),
+            settings: NextUISettings::load(),
+        }
+    }
+
+    // Returns the previous settings if they changed since the last poll
+    fn poll(&mut self) -> Option<NextUISettings> {
+        if self.last_poll.elapsed() < SETTINGS_POLL_INTERVAL {
+            return None;
+        }
+        self.last_poll = Instant::now();
 
-        let mut settings = String::new();
-        settings_file.read_to_string(&mut settings)?;
+        let modified = NextUISettings::modified();
+        if modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
 
-        // Very crappy parser
-        Ok(settings.contains("font=1").into())
+        let settings = NextUISettings::load();
+        if settings == self.settings {
+            return None;
+        }
+        Some(std::mem::replace(&mut self.settings, settings))
     }
+}
 
+// Load font from file, or the built-in one if that fails
+fn load_font() -> FontDefinitions {
     // Now load the font
-    let mut path = PathBuf::from(SDCARD_ROOT);
+    let mut path = PathBuf::from(sdcard_root());
     path.push(format!(
         ".system/res/{}",
-        FONTS[get_font_preference().unwrap_or(0)]
+        FONTS
+            .get(NextUISettings::load().font())
+            .unwrap_or(&FONTS[0])
     ));
-    println!("Loading font: {}", path.display());
+    info!("Loading font: {}", path.display());
     let mut font_bytes = vec![];
-    std::fs::File::open(path)?.read_to_end(&mut font_bytes)?;
+    if let Err(err) = std::fs::File::open(&path).and_then(|mut file| file.read_to_end(&mut font_bytes)) {
+        warn!("Failed to load {}, using the built-in font: {err}", path.display());
+        font_bytes = FALLBACK_FONT.to_vec();
+    }
 
     let mut font_data: BTreeMap<String, Arc<FontData>> = BTreeMap::new();
 
@@ -287,70 +2421,302 @@ fn load_font() -> Result<FontDefinitions> {
     families.insert(FontFamily::Proportional, vec!["custom_font".to_owned()]);
     families.insert(FontFamily::Monospace, vec!["custom_font".to_owned()]);
 
-    Ok(FontDefinitions {
+    FontDefinitions {
         font_data,
         families,
-    })
+    }
+}
+
+// 1234567 -> "1,234,567"
+fn format_count(count: impl std::fmt::Display) -> String {
+    let digits = count.to_string();
+    let mut formatted = String::new();
+    for (index, digit) in digits.chars().enumerate() {
+        if index > 0 && (digits.len() - index).is_multiple_of(3) {
+            formatted.push(',');
+        }
+        formatted.push(digit);
+    }
+    formatted
 }
 
-fn hint_wrap_nextui_tag(app_state: &'static AppStateManager, tag_name: String) -> String {
-    let mut selected_tag = format!("NextUI {}", tag_name);
-    if !app_state.release_selection_menu() {
-        return selected_tag;
+// What a full update will write to each top-level folder, before extracting
+fn extraction_preview_ui(
+    ui: &mut egui::Ui,
+    app_state: &'static AppStateManager,
+    folders: &[FolderSummary],
+) -> egui::Response {
+    ui.label(RichText::new(tr!("The full update will write:")).size(10.0));
+    ui.add_space(4.0);
+
+    ScrollArea::vertical()
+        .max_height(ui.available_height() - 72.0)
+        .show(ui, |ui| {
+            for folder in folders {
+                let summary = if folder.files == 0 {
+                    tr!("skipped").to_string()
+                } else {
+                    let mut summary = if folder.files == 1 {
+                        tr!("1 file").to_string()
+                    } else {
+                        tr!("{count} files", count = format_count(folder.files))
+                    };
+                    if folder.existing > 0 {
+                        summary += &tr!(", {count} replaced", count = format_count(folder.existing));
+                    }
+                    if folder.skipped > 0 {
+                        summary += &tr!(", {count} skipped", count = format_count(folder.skipped));
+                    }
+                    summary
+                };
+                ui.label(RichText::new(format!("{}: {summary}", folder.name)).size(10.0));
+            }
+        });
+
+    ui.add_space(8.0);
+
+    let confirm_button = ui.button(tr!("Install"));
+    if confirm_button.clicked() {
+        app_state.answer_extraction_preview(true);
     }
-    if !is_most_left_index(app_state) {
-        selected_tag = format!("<<     {}", selected_tag);
+
+    let cancel_button = ui.button(tr!("Cancel"));
+    if cancel_button.clicked() {
+        app_state.answer_extraction_preview(false);
     }
-    if !is_most_right_index(app_state) {
-        selected_tag = format!("{}     >>", selected_tag);
+
+    if confirm_button.has_focus() {
+        app_state.set_hint(Some(tr!("Extract the update to the SD card").to_string()));
+    } else if cancel_button.has_focus() {
+        app_state.set_hint(Some(tr!("Cancel the update, nothing is written").to_string()));
+    } else {
+        app_state.set_hint(None);
     }
-    return selected_tag;
+
+    confirm_button
 }
 
-fn is_most_left_index(app_state: &'static AppStateManager) -> bool {
-    let index = app_state.nextui_releases_and_tags_index().unwrap_or(0);
-    let max_index = app_state.nextui_releases_and_tags().unwrap().len();
-    if index < max_index-1 {
-        return false;
+// Percentage and phase of the running operation, readable from a distance
+fn big_progress_ui(ui: &mut egui::Ui, state: &Snapshot) {
+    ui.add_space(24.0);
+
+    match &state.progress {
+        Some(Progress::Determinate(pr, _)) => {
+            ui.label(RichText::new(format!("{:.0}%", pr * 100.0)).strong().size(48.0));
+        }
+        _ => {
+            ui.add(Spinner::new().color(Color32::WHITE).size(48.0));
+        }
+    }
+
+    ui.add_space(8.0);
+
+    if let Some(operation) = &state.current_operation {
+        ui.label(RichText::new(operation).size(20.0));
+    }
+
+    if let Some(error) = &state.error {
+        ui.label(error_text(&current_theme(ui), error).size(20.0));
     }
-    return true;
 }
 
-fn is_most_right_index(app_state: &'static AppStateManager) -> bool {
-    let index = app_state.nextui_releases_and_tags_index().unwrap_or(0);
-    if index > 0 {
-        return false;
+fn nextui_tag_label(state: &Snapshot, tag_name: &str) -> String {
+    format!("{} {}", state.config.source().name, tag_name)
+}
+
+// "Released Mar 2, 2025 (3 weeks ago)" line for a release, if it has a date
+fn release_date_label(release: &Release) -> String {
+    release
+        .published_at
+        .as_deref()
+        .and_then(date::parse_timestamp)
+        .map(|published_at| {
+            tr!(
+                "\nReleased {date}",
+                date = date::format_date_relative(published_at, i18n::language())
+            )
+        })
+        .unwrap_or_default()
+}
+
+fn header_title(state: &Snapshot) -> String {
+    let title = format!("NextUI Updater {}", env!("CARGO_PKG_VERSION"));
+    match state.submenu {
+        Submenu::Menu => format!("{title} {}", tr!("Menu")),
+        Submenu::Settings => format!("{title} {}", tr!("Settings")),
+        Submenu::History => format!("{title} {}", tr!("Update History")),
+        Submenu::ReleaseDetails => format!("{title} {}", tr!("Release Details")),
+        Submenu::Developer => format!("{title} {}", tr!("Developer")),
+        Submenu::Crashed => format!("{title} {}", tr!("Crashed")),
+        Submenu::AlreadyRunning => format!("{title} {}", tr!("Already Running")),
+        Submenu::FailedInstall => format!("{title} {}", tr!("Install Check")),
+        Submenu::InterruptedInstall => format!("{title} {}", tr!("Interrupted Install")),
+        Submenu::Logs => format!("{title} {}", tr!("Logs")),
+        Submenu::CommandPalette => format!("{title} {}", tr!("Commands")),
+        Submenu::ControllerTest => format!("{title} {}", tr!("Controller Test")),
+        Submenu::Controls => format!("{title} {}", tr!("Controls")),
+        Submenu::Keyboard => format!("{title} {}", tr!("Enter Text")),
+        Submenu::AssetPicker => format!("{title} {}", tr!("Choose Asset")),
+        Submenu::Integrity => format!("{title} {}", tr!("Installation Check")),
+        Submenu::Storage => format!("{title} {}", tr!("Storage")),
+        Submenu::FullUpdate => format!("{title} {}", tr!("Full Update")),
+        Submenu::About => format!("{title} {}", tr!("About")),
+        Submenu::NextUI | Submenu::Offline => {
+            // Beta users should always know they may be offered prereleases
+            let title = match state.config.channel {
+                Channel::Stable => title,
+                Channel::Beta => format!("{title} ({})", tr!("Beta")),
+            };
+            if !state.release_selection_menu {
+                title
+            } else if state.release_selection_open && state.downgrade_cautions.is_some() {
+                format!("{title} {}", tr!("Downgrade Warning"))
+            } else {
+                format!("{title} {}", tr!("Version Selector"))
+            }
+        }
     }
-    return true;
 }
 
+// Pushed by other threads when they change the app state
+struct StateChanged;
+
 #[allow(clippy::too_many_lines)]
 pub fn run_ui(app_state: &'static AppStateManager) -> Result<()> {
     // Initialize SDL and create window
-    let (_sdl_context, window, mut event_pump, _controller) = init_sdl()?;
+    let (sdl_context, window, mut event_pump, mut controller) = init_sdl()?;
+    let player = Player::new(&sdl_context);
 
     // Create OpenGL context and egui painter
     let _gl_context = window.gl_create_context()?;
-    let shader_ver = ShaderVersion::Adaptive;
+
+    // Work in other threads wakes the loop to redraw instead of it polling
+    let events = sdl_context.event()?;
+    events.register_custom_event::<StateChanged>()?;
+    let event_sender = events.event_sender();
+    app_state.set_waker(move || {
+        if let Err(err) = event_sender.push_custom_event(StateChanged) {
+            warn!("Failed to wake the UI: {err}");
+        }
+    });
+    let mut state = app_state.snapshot();
+
+    let mut text_scale = state.config.text_scale;
+    let mut dpi = dpi_scale(text_scale);
     let (mut painter, mut egui_state) =
-        egui_backend::with_sdl2(&window, shader_ver, DpiScaling::Custom(DPI_SCALE));
+        egui_backend::with_sdl2(&window, ShaderVersion::Adaptive, DpiScaling::Custom(dpi));
 
     // Create egui context and set style
     let egui_ctx = egui::Context::default();
-    egui_ctx.set_style(setup_ui_style());
+    let mut settings_watcher = SettingsWatcher::new();
+    let mut theme = Theme::default();
+    egui_ctx.set_style(setup_ui_style(&theme));
 
-    // Font stuff
-    if let Ok(fonts) = load_font() {
-        egui_ctx.set_fonts(fonts);
-    }
+    // Font stuff, loaded in the background while the splash screen is shown
+    let mut font_loader = Some(thread::spawn(load_font));
+    let mut fonts_loaded = false;
 
     let start_time: Instant = Instant::now();
 
+    let mut stick = Stick::default();
+    let mut stick_repeat = Repeat::default();
+    let mut button_repeat = Repeat::default();
+    let mut button_mapping = ButtonMapping::default();
+    let mut controls = Controls::default();
+
+    // Stay awake with the screen on while something is running
+    let mut keep_awake = KeepAwake::default();
+    let mut reached_ui = false;
+
     loop {
-        if app_state.should_quit() {
+        if app_state.changed_since(&state) {
+            state = app_state.snapshot();
+        }
+        if state.should_quit {
             break;
         }
 
+        // The text size setting changed, or the config was loaded. egui
+        // rebuilds the fonts for the new scale on the next frame. The old
+        // painter's GL objects are freed first, or they'd leak each time.
+        if state.config.text_scale != text_scale {
+            text_scale = state.config.text_scale;
+            dpi = dpi_scale(text_scale);
+            painter.cleanup();
+            (painter, egui_state) =
+                egui_backend::with_sdl2(&window, ShaderVersion::Adaptive, DpiScaling::Custom(dpi));
+        }
+
+        // The button mapping changed, or the config was loaded
+        if state.config.buttons != button_mapping {
+            button_mapping = state.config.buttons.clone();
+            controls = Controls::from_config(&button_mapping).unwrap_or_else(|err| {
+                warn!("Invalid button mapping, using the default: {err}");
+                Controls::default()
+            });
+        }
+
+        let busy = state.current_operation.is_some();
+        let video = window.subsystem();
+        if busy && video.is_screen_saver_enabled() {
+            video.disable_screen_saver();
+        } else if !busy && !video.is_screen_saver_enabled() {
+            video.enable_screen_saver();
+        }
+        keep_awake.set(busy);
+
+        for cue in app_state.take_cues() {
+            player.play(cue);
+        }
+        for rumble in app_state.take_rumbles() {
+            if let Some(controller) = controller.as_mut() {
+                rumble.play(controller);
+            }
+        }
+
+        if font_loader.as_ref().is_some_and(JoinHandle::is_finished) {
+            if let Some(Ok(fonts)) = font_loader.take().map(JoinHandle::join) {
+                egui_ctx.set_fonts(fonts);
+            }
+            fonts_loaded = true;
+        }
+
+        // Follow font and color changes made in NextUI, once the fonts are loaded
+        let changed = if font_loader.is_none() {
+            settings_watcher.poll()
+        } else {
+            None
+        };
+        if let Some(previous) = changed {
+            let settings = &settings_watcher.settings;
+            if settings.font() != previous.font() {
+                font_loader = Some(thread::spawn(load_font));
+            }
+        }
+
+        // NextUI's colors, or the high contrast setting, changed
+        let new_theme = if state.config.high_contrast {
+            Theme::high_contrast()
+        } else {
+            Theme::from_settings(&settings_watcher.settings)
+        };
+        if new_theme != theme {
+            theme = new_theme;
+            egui_ctx.set_style(setup_ui_style(&theme));
+            egui_ctx.data_mut(|data| data.insert_temp(Id::new(THEME_ID), theme));
+        }
+
+        let init_status = state
+            .init_status
+            .clone()
+            .or_else(|| (!fonts_loaded).then(|| tr!("Loading fonts...").to_string()));
+
+        // Made it past the splash screen, so this binary works
+        if init_status.is_none() && !reached_ui {
+            reached_ui = true;
+            rollback::confirm();
+        }
+
         egui_state.input.time = Some(start_time.elapsed().as_secs_f64());
         egui_ctx.begin_pass(egui_state.input.take());
 
@@ -358,35 +2724,72 @@ pub fn run_ui(app_state: &'static AppStateManager) -> Result<()> {
         egui::CentralPanel::default().show(&egui_ctx, |ui| {
             ui.vertical_centered(|ui| {
                 // Check application state
-                let update_in_progress = app_state.current_operation().is_some();
-
-                if app_state.release_selection_menu() {
-                    if app_state.release_selection_confirmed() {
-                        ui.label(
-                            RichText::new(format!("NextUI Updater {} Version Selector", env!("CARGO_PKG_VERSION")))
-                                .color(Color32::from_rgb(150, 150, 150))
-                                .size(10.0),
-                        );
-                    } else {
-                        ui.label(
-                            RichText::new(format!("NextUI Updater {} Version Selector Warning", env!("CARGO_PKG_VERSION")))
-                                .color(Color32::from_rgb(150, 150, 150))
-                                .size(10.0),
-                        );
-                    }
-                } else {
-                    ui.label(
-                        RichText::new(format!("NextUI Updater {}", env!("CARGO_PKG_VERSION")))
-                            .color(Color32::from_rgb(150, 150, 150))
-                            .size(10.0),
-                    );
-                }
+                let update_in_progress = state.update_phase.is_active();
+
+                ui.label(
+                    RichText::new(header_title(&state))
+                        .color(theme.dim)
+                        .size(10.0),
+                );
                 ui.add_space(4.0);
 
-                ui.add_enabled_ui(!update_in_progress, |ui| {
-                    let submenu = app_state.submenu();
-                    let menu = match submenu {
-                        Submenu::NextUI => nextui_ui(ui, app_state),
+                // Splash screen while initializing
+                if let Some(status) = &init_status {
+                    ui.add_space(16.0);
+                    ui.label(RichText::new(status).color(theme.dim).size(10.0));
+                    ui.add_space(4.0);
+                    ui.add(Spinner::new().color(Color32::WHITE));
+                    return;
+                }
+
+                // A full update waits here for the user to confirm what it will write
+                if let Some(folders) = &state.extraction_preview {
+                    let menu = extraction_preview_ui(ui, app_state, folders);
+                    ui.memory_mut(|r| {
+                        if r.focused().is_none() {
+                            r.request_focus(menu.id);
+                        }
+                    });
+                    return;
+                }
+
+                // Progress takes over the whole screen in large text mode
+                if update_in_progress && state.config.big_progress {
+                    big_progress_ui(ui, &state);
+                    return;
+                }
+
+                // The update and settings screens are locked while an operation
+                // runs. The others disable whatever conflicts with it themselves.
+                let locked = update_in_progress
+                    && matches!(state.submenu, Submenu::NextUI | Submenu::Settings);
+                ui.add_enabled_ui(!locked, |ui| {
+                    let menu = match state.submenu {
+                        Submenu::NextUI => nextui_ui(ui, app_state, &state),
+                        Submenu::Menu => menu_ui(ui, app_state, &state),
+                        Submenu::Settings => settings_ui(ui, app_state, &state),
+                        Submenu::History => history_ui(ui, app_state, &state),
+                        Submenu::ReleaseDetails => release_details_ui(ui, app_state, &state),
+                        Submenu::Developer => developer_ui(ui, app_state),
+                        Submenu::Offline => offline_ui(ui, app_state, &state),
+                        Submenu::AlreadyRunning => already_running_ui(ui, app_state),
+                        Submenu::Crashed => crashed_ui(ui, app_state),
+                        Submenu::FailedInstall => failed_install_ui(ui, app_state),
+                        Submenu::InterruptedInstall => {
+                            interrupted_install_ui(ui, app_state, &state)
+                        }
+                        Submenu::Logs => logs_ui(ui, app_state),
+                        Submenu::CommandPalette => command_palette_ui(ui, app_state),
+                        Submenu::AssetPicker => asset_picker_ui(ui, app_state, &state),
+                        Submenu::Integrity => integrity_ui(ui, app_state, &state),
+                        Submenu::ControllerTest => {
+                            controller_test_ui(ui, app_state, controller.as_ref(), controls)
+                        }
+                        Submenu::Controls => controls_ui(ui, app_state, controls),
+                        Submenu::Keyboard => keyboard_ui(ui, app_state),
+                        Submenu::Storage => storage_ui(ui, app_state, &state),
+                        Submenu::FullUpdate => full_update_ui(ui, app_state, &state),
+                        Submenu::About => about_ui(ui, app_state, &state),
                     };
 
                     // Focus the first available button for controller navigation
@@ -397,26 +2800,45 @@ pub fn run_ui(app_state: &'static AppStateManager) -> Result<()> {
                     });
                 });
 
+                // Whatever the screen just did shows on this frame
+                if app_state.changed_since(&state) {
+                    state = app_state.snapshot();
+                }
+
                 ui.add_space(8.0);
 
                 // Display current operation
-                if let Some(operation) = app_state.current_operation() {
-                    ui.label(RichText::new(operation).color(Color32::from_rgb(150, 150, 150)).size(10.0));
+                if let Some(operation) = &state.current_operation {
+                    ui.label(RichText::new(operation).color(theme.dim).size(10.0));
+                }
+
+                if state.fetch_slow() {
+                    ui.label(
+                        RichText::new(tr!("Taking longer than usual - press B to continue offline"))
+                            .color(theme.warning)
+                            .size(8.0),
+                    );
+                } else if state.fetch_skippable().is_some() {
+                    ui.label(RichText::new(tr!("Press B to skip")).color(theme.dim).size(8.0));
+                }
+
+                if let Some(notice) = &state.notice {
+                    ui.label(RichText::new(notice).color(theme.dim).size(8.0));
                 }
 
                 // Display error if any
-                if let Some(error) = app_state.error() {
-                    ui.colored_label(Color32::from_rgb(255, 150, 150), RichText::new(error));
+                if let Some(error) = &state.error {
+                    ui.label(error_text(&theme, error));
                 }
 
                 // Show progress bar if available
-                if let Some(progress) = app_state.progress() {
-                    match progress {
+                if let Some(progress) = &state.progress {
+                    match *progress {
                         Progress::Indeterminate => {
                             ui.add_space(4.0);
                             ui.add(Spinner::new().color(Color32::WHITE));
                         }
-                        Progress::Determinate(pr) => {
+                        Progress::Determinate(pr, transfer) => {
                             let mut progress_bar = ProgressBar::new(pr);
                             // Show percentage only if progress is > 10% to avoid text
                             // escaping the progress bar
@@ -424,12 +2846,20 @@ pub fn run_ui(app_state: &'static AppStateManager) -> Result<()> {
                                 progress_bar = progress_bar.show_percentage();
                             }
                             ui.add(progress_bar);
+
+                            if let Some(transfer) = transfer {
+                                ui.label(
+                                    RichText::new(transfer_label(&transfer))
+                                        .color(theme.dim)
+                                        .size(8.0),
+                                );
+                            }
                         }
                     }
                 }
             });
 
-            if let Some(hint) = app_state.hint() {
+            if let Some(hint) = &state.hint {
                 ui.allocate_new_ui(
                     egui::UiBuilder::new().max_rect(Rect {
                         min: Pos2 {
@@ -437,13 +2867,13 @@ pub fn run_ui(app_state: &'static AppStateManager) -> Result<()> {
                             y: ui.max_rect().height() - 2.0,
                         },
                         max: Pos2 {
-                            x: 1024.0 / DPI_SCALE,
+                            x: 1024.0 / dpi,
                             y: ui.max_rect().height(),
                         },
                     }),
                     |ui| {
                         ui.centered_and_justified(|ui| {
-                            ui.label(RichText::new(hint).size(10.0));
+                            ui.label(RichText::new(hint).color(theme.hint).size(10.0));
                         });
                     },
                 );
@@ -464,6 +2894,10 @@ pub fn run_ui(app_state: &'static AppStateManager) -> Result<()> {
             );
         });
 
+        if theme.high_contrast {
+            mark_focused(&egui_ctx, &theme);
+        }
+
         // End frame and render
         let FullOutput {
             platform_output,
@@ -486,8 +2920,49 @@ pub fn run_ui(app_state: &'static AppStateManager) -> Result<()> {
         painter.paint_jobs(None, textures_delta, paint_jobs);
         window.gl_swap_window();
 
+        // Played right away instead of queued like result cues, so they keep
+        // up with the button presses
+        let play_navigation = |cue| {
+            if state.config.navigation_sounds_enabled() {
+                player.play(cue);
+            }
+        };
+
         let handle_back_button = || {
-            if app_state.release_selection_menu() {
+            play_navigation(Cue::Back);
+            if app_state.submenu() == Submenu::NextUI && app_state.fetch_skippable().is_some() {
+                app_state.skip_fetch();
+            } else if app_state.extraction_preview().is_some() {
+                app_state.answer_extraction_preview(false);
+            } else if matches!(
+                app_state.submenu(),
+                Submenu::Menu
+                    | Submenu::Settings
+                    | Submenu::History
+                    | Submenu::ReleaseDetails
+                    | Submenu::Developer
+                    | Submenu::FailedInstall
+                    | Submenu::InterruptedInstall
+                    | Submenu::Logs
+                    | Submenu::CommandPalette
+                    | Submenu::ControllerTest
+                    | Submenu::Controls
+                    | Submenu::Keyboard
+                    | Submenu::AssetPicker
+                    | Submenu::Integrity
+                    | Submenu::Storage
+                    | Submenu::FullUpdate
+                    | Submenu::About
+            ) {
+                app_state.leave_submenu();
+            } else if matches!(
+                app_state.submenu(),
+                Submenu::Offline | Submenu::AlreadyRunning | Submenu::Crashed
+            ) {
+                app_state.set_should_quit(true);
+            } else if app_state.release_selection_open() {
+                app_state.set_release_selection_open(false);
+            } else if app_state.release_selection_menu() {
                 app_state.set_release_selection_menu(false);
             } else {
                 app_state.set_should_quit(true);
@@ -498,64 +2973,89 @@ pub fn run_ui(app_state: &'static AppStateManager) -> Result<()> {
         let mut process_event = |event| {
             match event {
                 Event::Quit { .. } => app_state.set_should_quit(true),
+                // The controller test screen sees raw button states instead
+                Event::ControllerButtonDown { .. } | Event::ControllerButtonUp { .. }
+                    if app_state.submenu() == Submenu::ControllerTest =>
+                {
+                    let pressed = |button| {
+                        controller
+                            .as_ref()
+                            .is_some_and(|controller| controller.button(button))
+                    };
+                    if pressed(sdl2::controller::Button::Start)
+                        && pressed(sdl2::controller::Button::Back)
+                    {
+                        app_state.leave_submenu();
+                    }
+                }
                 Event::ControllerButtonDown {
                     timestamp, button, ..
                 } => {
-                    if let Some(keycode) = controller_to_key(button) {
-                        let key_event = Event::KeyDown {
-                            keycode: Some(keycode),
-                            timestamp,
-                            window_id: window.id(),
-                            scancode: Some(sdl2::keyboard::Scancode::Down),
-                            keymod: sdl2::keyboard::Mod::empty(),
-                            repeat: false,
-                        };
+                    if controls.repeats(button) {
+                        button_repeat.press(button);
+                    }
+                    if let Some(down) = page_direction(app_state, controls, button) {
+                        play_navigation(Cue::Move);
+                        page_release_list(app_state, down);
+                    } else if let Some(keycode) = controller_to_key(controls, button) {
+                        if let Some(cue) = navigation_cue(keycode) {
+                            play_navigation(cue);
+                        }
+                        let key_event = controller_key_event(&window, keycode, timestamp, true);
                         egui_state.process_input(&window, key_event, &mut painter);
                     }
                 }
                 Event::ControllerButtonUp {
                     timestamp, button, ..
                 } => {
-                    if button == sdl2::controller::Button::A {
-                        // Exit with "B" button
+                    button_repeat.release(button);
+                    let action = controls.action(button);
+                    if action == Some(Action::Back) {
                         handle_back_button();
                     }
 
-                    if app_state.release_selection_menu() {
-                        if app_state.release_selection_confirmed() {
-                            // Add left/right options in selection menu
-                            let index = app_state.nextui_releases_and_tags_index().unwrap_or(0);
-                            if button == sdl2::controller::Button::DPadLeft {
-                                if !is_most_left_index(app_state) {
-                                    app_state.set_nextui_releases_and_tags_index(Some(index+1));
-                                }
-                            }
-                            if button == sdl2::controller::Button::DPadRight {
-                                if !is_most_right_index(app_state) {
-                                    app_state.set_nextui_releases_and_tags_index(Some(index-1));
-                                }
-                            }
-                        }
+                    if !matches!(app_state.submenu(), Submenu::NextUI) {
+                        // No extra buttons outside of the main menu
+                    } else if app_state.release_selection_menu() {
+                        // Paging through the version list happens on press
                     } else {
                         // Add X button to reach selection menu
-                        if button == sdl2::controller::Button::Y {
+                        if action == Some(Action::Versions)
+                            && app_state.nextui_releases_and_tags().is_some()
+                        {
                             app_state.set_release_selection_menu(true);
                         }
+                        if action == Some(Action::Menu) {
+                            app_state.enter_submenu(Submenu::Menu);
+                        }
                     }
 
-                    if let Some(keycode) = controller_to_key(button) {
-                        let key_event = Event::KeyUp {
-                            keycode: Some(keycode),
-                            timestamp,
-                            window_id: window.id(),
-                            scancode: Some(sdl2::keyboard::Scancode::Down),
-                            keymod: sdl2::keyboard::Mod::empty(),
-                            repeat: false,
-                        };
-
+                    if let Some(keycode) = controller_to_key(controls, button) {
+                        let key_event = controller_key_event(&window, keycode, timestamp, false);
                         egui_state.process_input(&window, key_event, &mut painter);
                     }
                 }
+                // The left stick navigates like the D-pad
+                Event::ControllerAxisMotion {
+                    timestamp,
+                    axis,
+                    value,
+                    ..
+                } if app_state.submenu() != Submenu::ControllerTest => {
+                    if let Some((released, pressed)) = stick.motion(axis, value) {
+                        if let Some(keycode) = released {
+                            stick_repeat.release(keycode);
+                            let key_event = controller_key_event(&window, keycode, timestamp, false);
+                            egui_state.process_input(&window, key_event, &mut painter);
+                        }
+                        if let Some(keycode) = pressed {
+                            stick_repeat.press(keycode);
+                            play_navigation(Cue::Move);
+                            let key_event = controller_key_event(&window, keycode, timestamp, true);
+                            egui_state.process_input(&window, key_event, &mut painter);
+                        }
+                    }
+                }
                 // for easy testing on desktop
                 Event::KeyDown {
                     keycode: Some(sdl2::keyboard::Keycode::Escape),
@@ -563,6 +3063,31 @@ pub fn run_ui(app_state: &'static AppStateManager) -> Result<()> {
                 } => {
                     handle_back_button();
                 }
+                // Desktop keyboard shortcuts. Controller buttons are turned
+                // into key events separately, so they never get here. Keys
+                // typed into a text field are left to it.
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    repeat: false,
+                    ..
+                } if !matches!(
+                    app_state.submenu(),
+                    Submenu::CommandPalette | Submenu::Keyboard
+                ) && !egui_ctx.wants_keyboard_input() => {
+                    if let Some(command) = keyboard_command(keycode) {
+                        command.run(app_state);
+                    } else if keycode == sdl2::keyboard::Keycode::P {
+                        app_state.set_command_query(String::new());
+                        app_state.enter_submenu(Submenu::CommandPalette);
+                    } else {
+                        if let Some(cue) = navigation_cue(keycode) {
+                            play_navigation(cue);
+                        }
+                        egui_state.process_input(&window, event, &mut painter);
+                    }
+                }
+                // Only wakes the loop, which picks up the new state
+                Event::User { .. } => {}
                 _ => {
                     // Process other input events
                     egui_state.process_input(&window, event, &mut painter);
@@ -577,6 +3102,25 @@ pub fn run_ui(app_state: &'static AppStateManager) -> Result<()> {
         } else if let Some(event) = event_pump.wait_event_timeout(50) {
             process_event(event);
         }
+
+        // A held D-pad, shoulder button or stick keeps moving
+        let mut repeated = stick_repeat.poll();
+        if let Some(button) = button_repeat.poll() {
+            if let Some(down) = page_direction(app_state, controls, button) {
+                play_navigation(Cue::Move);
+                page_release_list(app_state, down);
+            } else {
+                repeated = controller_to_key(controls, button);
+            }
+        }
+        if let Some(keycode) = repeated {
+            play_navigation(Cue::Move);
+            let timestamp = sdl_context.timer().map_or(0, |timer| timer.ticks());
+            for down in [true, false] {
+                let key_event = controller_key_event(&window, keycode, timestamp, down);
+                egui_state.process_input(&window, key_event, &mut painter);
+            }
+        }
     }
 
     Ok(())
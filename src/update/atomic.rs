@@ -0,0 +1,128 @@
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+// Writes a power cut can't leave half-done. The new contents go to a
+// temporary file next to the original, which is synced and then renamed over
+// it, so until the rename the old file is still there untouched. Syncing the
+// directory afterwards makes the rename itself stick.
+pub struct AtomicFile {
+    file: File,
+    path: PathBuf,
+    temp_path: PathBuf,
+    committed: bool,
+}
+
+// Hidden, and the same every time, so retrying after a crash overwrites what
+// the crashed attempt left instead of piling up
+fn temp_path(path: &Path) -> PathBuf {
+    let mut name = OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(".tmp");
+    path.with_file_name(name)
+}
+
+// Whether `path` is where a write that never got committed would have been
+// left, e.g. by a power cut
+pub fn is_temp_path(path: &Path) -> bool {
+    let hidden = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .is_some_and(|stem| stem.len() > 1 && stem.starts_with('.'));
+    hidden && path.extension().is_some_and(|extension| extension == "tmp")
+}
+
+impl AtomicFile {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let temp_path = temp_path(path);
+        Ok(Self {
+            file: File::create(&temp_path)?,
+            path: path.to_path_buf(),
+            temp_path,
+            committed: false,
+        })
+    }
+
+    // Replace the original with what was written, keeping its permissions,
+    // e.g. so a script stays executable
+    pub fn commit(mut self) -> io::Result<()> {
+        self.file.sync_all()?;
+        if let Ok(metadata) = fs::metadata(&self.path) {
+            fs::set_permissions(&self.temp_path, metadata.permissions())?;
+        }
+        fs::rename(&self.temp_path, &self.path)?;
+        self.committed = true;
+        sync_parent(&self.path)
+    }
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+// Not committed, e.g. because extracting failed, so the original stays
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = fs::remove_file(&self.temp_path);
+        }
+    }
+}
+
+pub fn write(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let mut file = AtomicFile::create(path)?;
+    file.write_all(bytes)?;
+    file.commit()
+}
+
+// Sync the directory holding `path`, so a rename or new file in it survives
+// a power cut
+pub fn sync_parent(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => File::open(parent)?.sync_all(),
+        _ => File::open(".")?.sync_all(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_replace_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("launch.sh");
+        fs::write(&path, b"old").unwrap();
+        write(&path, b"new").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn uncommitted_writes_leave_the_file_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("launch.sh");
+        fs::write(&path, b"old").unwrap();
+        let mut file = AtomicFile::create(&path).unwrap();
+        file.write_all(b"new").unwrap();
+        drop(file);
+        assert_eq!(fs::read(&path).unwrap(), b"old");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn temp_paths_are_recognized() {
+        let path = Path::new("/mnt/SDCARD/.system/launch.sh");
+        assert!(is_temp_path(&temp_path(path)));
+        assert!(!is_temp_path(path));
+        assert!(!is_temp_path(Path::new("/mnt/SDCARD/.tmp")));
+        assert!(!is_temp_path(Path::new("/mnt/SDCARD/notes.tmp")));
+    }
+}
@@ -0,0 +1,239 @@
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use log::{info, warn};
+use sha2::{Digest, Sha256};
+
+use super::cleanup::Cleanup;
+use super::memory::{self, Archive};
+use crate::config::Source;
+use crate::github::Asset;
+use crate::{sdcard_root, UPDATER_DATA_DIR};
+
+const CACHE_DIR: &str = "cache";
+
+fn dir() -> PathBuf {
+    PathBuf::from(sdcard_root())
+        .join(UPDATER_DATA_DIR)
+        .join(CACHE_DIR)
+}
+
+// Named after the source, release and asset, so a re-uploaded asset or one
+// with the same name from a fork is never mistaken for another
+fn file_name(source: &Source, tag_name: &str, asset: &Asset) -> String {
+    let mut hasher = Sha256::new();
+    for part in [&source.repo, tag_name, &asset.name] {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+    hasher.update(asset.size.to_le_bytes());
+    let hash = format!("{:x}", hasher.finalize());
+    format!("{}.zip", &hash[..32])
+}
+
+// A downloaded asset, if it's cached and the size still matches. It's
+// verified again like a fresh download before it's used.
+pub fn load(source: &Source, tag_name: &str, asset: &Asset) -> Option<Archive> {
+    load_from(&dir(), &file_name(source, tag_name, asset), asset)
+}
+
+fn load_from(dir: &Path, name: &str, asset: &Asset) -> Option<Archive> {
+    let path = dir.join(name);
+    let bytes = memory::read_file(&path).ok()?;
+    if asset.size != 0 && bytes.size() != asset.size {
+        warn!("Discarding cached {}: wrong size", asset.name);
+        let _ = fs::remove_file(path);
+        return None;
+    }
+    info!("Using cached {}", asset.name);
+    // Marks it as recently used, so it's evicted last
+    if let Err(err) = File::options()
+        .write(true)
+        .open(&path)
+        .and_then(|file| file.set_modified(SystemTime::now()))
+    {
+        warn!("Failed to mark cached {} as used: {err}", asset.name);
+    }
+    Some(bytes)
+}
+
+// Whether `load` would find the asset, without reading it
+pub fn contains(source: &Source, tag_name: &str, asset: &Asset) -> bool {
+    fs::metadata(dir().join(file_name(source, tag_name, asset)))
+        .is_ok_and(|metadata| asset.size == 0 || metadata.len() == asset.size)
+}
+
+// Caching is best effort: a full SD card only means downloading again. The
+// least recently used assets are evicted to keep the cache within `limit`
+// bytes, and an asset bigger than that isn't cached at all.
+pub fn store(source: &Source, tag_name: &str, asset: &Asset, bytes: &Archive, limit: u64) {
+    let name = file_name(source, tag_name, asset);
+    if let Err(err) = store_in(&dir(), &name, bytes, limit) {
+        warn!("Failed to cache {}: {err}", asset.name);
+    }
+}
+
+fn store_in(dir: &Path, name: &str, bytes: &Archive, limit: u64) -> std::io::Result<()> {
+    let size = bytes.size();
+    if size > limit {
+        info!("Not caching {name}, it's bigger than the cache");
+        return Ok(());
+    }
+    fs::create_dir_all(dir)?;
+    evict_in(dir, limit - size)?;
+    // Written atomically, so a half-written file is never reused
+    bytes.save(&dir.join(name))
+}
+
+// Cached files, least recently used first, with their sizes
+fn entries(dir: &Path) -> std::io::Result<Vec<(PathBuf, SystemTime, u64)>> {
+    let mut entries = vec![];
+    let read_dir = match fs::read_dir(dir) {
+        Ok(read_dir) => read_dir,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(entries),
+        Err(err) => return Err(err),
+    };
+    for entry in read_dir {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_file() {
+            let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            entries.push((entry.path(), modified, metadata.len()));
+        }
+    }
+    entries.sort_by_key(|(_, modified, _)| *modified);
+    Ok(entries)
+}
+
+// Remove the least recently used files until the rest fit in `limit` bytes
+fn evict_in(dir: &Path, limit: u64) -> std::io::Result<()> {
+    let entries = entries(dir)?;
+    let mut total: u64 = entries.iter().map(|(_, _, size)| size).sum();
+    for (path, _, size) in entries {
+        if total <= limit {
+            break;
+        }
+        fs::remove_file(&path)?;
+        info!("Evicted {} from the download cache", path.display());
+        total -= size;
+    }
+    Ok(())
+}
+
+// Total size of the cache, for the storage screen
+pub fn size() -> u64 {
+    entries(&dir()).map_or(0, |entries| entries.iter().map(|(_, _, size)| size).sum())
+}
+
+// Remove every cached file
+pub fn clear() -> std::io::Result<Cleanup> {
+    clear_in(&dir())
+}
+
+fn clear_in(dir: &Path) -> std::io::Result<Cleanup> {
+    let mut cleanup = Cleanup::default();
+    for (path, _, size) in entries(dir)? {
+        fs::remove_file(&path)?;
+        cleanup.removed += 1;
+        cleanup.reclaimed += size;
+    }
+    info!("Cleared the download cache ({} bytes)", cleanup.reclaimed);
+    Ok(cleanup)
+}
+
+// For a cached asset that turned out to be unusable, so the next attempt
+// downloads it again
+pub fn remove(source: &Source, tag_name: &str, asset: &Asset) {
+    let _ = fs::remove_file(dir().join(file_name(source, tag_name, asset)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+
+    fn archive(bytes: &'static [u8]) -> Archive {
+        Bytes::from_static(bytes).into()
+    }
+
+    fn asset(size: u64) -> Asset {
+        Asset {
+            name: "NextUI-base.zip".to_string(),
+            url: String::new(),
+            size,
+            download_count: 0,
+            digest: None,
+        }
+    }
+
+    #[test]
+    fn cached_assets_load() {
+        let dir = tempfile::tempdir().unwrap();
+        store_in(dir.path(), "asset.zip", &archive(b"archive"), 100).unwrap();
+        let bytes = load_from(dir.path(), "asset.zip", &asset(7)).unwrap();
+        assert_eq!(bytes.to_vec(), b"archive");
+        assert!(!dir.path().join("asset.part").exists());
+    }
+
+    #[test]
+    fn cached_assets_of_the_wrong_size_are_discarded() {
+        let dir = tempfile::tempdir().unwrap();
+        store_in(dir.path(), "asset.zip", &archive(b"archive"), 100).unwrap();
+        assert!(load_from(dir.path(), "asset.zip", &asset(300)).is_none());
+        assert!(!dir.path().join("asset.zip").exists());
+    }
+
+    #[test]
+    fn least_recently_used_assets_are_evicted() {
+        let dir = tempfile::tempdir().unwrap();
+        store_in(dir.path(), "old.zip", &archive(b"archive"), 20).unwrap();
+        store_in(dir.path(), "used.zip", &archive(b"archive"), 20).unwrap();
+        // Older than the other, but used since
+        let old = SystemTime::now() - std::time::Duration::from_secs(90);
+        for name in ["old.zip", "used.zip"] {
+            let file = File::options().write(true).open(dir.path().join(name));
+            file.unwrap().set_modified(old).unwrap();
+        }
+        load_from(dir.path(), "used.zip", &asset(7)).unwrap();
+
+        store_in(dir.path(), "new.zip", &archive(b"archive"), 20).unwrap();
+        assert!(!dir.path().join("old.zip").exists());
+        assert!(dir.path().join("used.zip").exists());
+        assert!(dir.path().join("new.zip").exists());
+    }
+
+    #[test]
+    fn assets_bigger_than_the_cache_are_not_cached() {
+        let dir = tempfile::tempdir().unwrap();
+        store_in(dir.path(), "small.zip", &archive(b"archive"), 10).unwrap();
+        store_in(dir.path(), "big.zip", &archive(b"big archive"), 10).unwrap();
+        assert!(dir.path().join("small.zip").exists());
+        assert!(!dir.path().join("big.zip").exists());
+    }
+
+    #[test]
+    fn clearing_removes_everything() {
+        let dir = tempfile::tempdir().unwrap();
+        store_in(dir.path(), "a.zip", &archive(b"archive"), 100).unwrap();
+        store_in(dir.path(), "b.zip", &archive(b"archive"), 100).unwrap();
+        let cleanup = clear_in(dir.path()).unwrap();
+        assert_eq!(cleanup.removed, 2);
+        assert_eq!(cleanup.reclaimed, 14);
+        assert!(entries(dir.path()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn cache_names_differ_by_release_and_source() {
+        let source = Source::default();
+        let fork = Source {
+            repo: "someone/NextUI".to_string(),
+            ..Source::default()
+        };
+        let name = file_name(&source, "v6.0.0", &asset(7));
+        assert_eq!(name, file_name(&source, "v6.0.0", &asset(7)));
+        assert_ne!(name, file_name(&source, "v6.1.0", &asset(7)));
+        assert_ne!(name, file_name(&fork, "v6.0.0", &asset(7)));
+        assert_ne!(name, file_name(&source, "v6.0.0", &asset(8)));
+    }
+}
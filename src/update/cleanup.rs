@@ -0,0 +1,114 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use log::{info, warn};
+
+use super::{atomic, memory, rollback, staging};
+use crate::cancel::CancellationToken;
+use crate::error::Result;
+use crate::{sdcard_root, storage};
+
+// Emulators keep their own files here, and updates never write to it
+const SKIPPED_FOLDERS: [&str; 1] = ["Saves"];
+
+// What cleaning up removed
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Cleanup {
+    pub removed: usize,
+    pub reclaimed: u64,
+}
+
+// Files the updater left behind that nothing uses anymore: the previous
+// binary once a self-update is confirmed, a half-staged update, a download
+// that was too big for memory and never finished, and the temporary files of
+// writes cut short
+pub fn leftovers(token: &CancellationToken) -> Result<Vec<PathBuf>> {
+    let mut leftovers = vec![];
+    if !rollback::pending() {
+        leftovers.extend(rollback::backup_path().filter(|path| path.exists()));
+    }
+    leftovers.extend(staging::stale());
+    let spilled = memory::spill_path();
+    if spilled.exists() {
+        leftovers.push(spilled);
+    }
+    temp_files(Path::new(sdcard_root()), token, &mut leftovers)?;
+    Ok(leftovers)
+}
+
+// Left by atomic writes anywhere an update or the updater writes
+fn temp_files(dir: &Path, token: &CancellationToken, found: &mut Vec<PathBuf>) -> Result<()> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Ok(());
+    };
+    for entry in entries.flatten() {
+        token.check()?;
+        let path = entry.path();
+        let Ok(metadata) = path.symlink_metadata() else {
+            continue;
+        };
+        if metadata.is_dir() {
+            let skipped = SKIPPED_FOLDERS
+                .iter()
+                .any(|folder| entry.file_name() == *folder);
+            if !skipped {
+                temp_files(&path, token, found)?;
+            }
+        } else if metadata.is_file() && atomic::is_temp_path(&path) {
+            found.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn size(path: &Path, token: &CancellationToken) -> Result<u64> {
+    if path.is_dir() {
+        storage::folder_size(path, token)
+    } else {
+        Ok(fs::metadata(path).map_or(0, |metadata| metadata.len()))
+    }
+}
+
+pub fn clean_up(token: &CancellationToken) -> Result<Cleanup> {
+    let mut cleanup = Cleanup::default();
+    for path in leftovers(token)? {
+        token.check()?;
+        let size = size(&path, token)?;
+        let result = if path.is_dir() {
+            fs::remove_dir_all(&path)
+        } else {
+            fs::remove_file(&path)
+        };
+        match result {
+            Ok(()) => {
+                info!("Removed {} ({size} bytes)", path.display());
+                cleanup.removed += 1;
+                cleanup.reclaimed += size;
+            }
+            Err(err) => warn!("Failed to remove {}: {err}", path.display()),
+        }
+    }
+    // Not reported to a screen that's gone
+    token.check()?;
+    Ok(cleanup)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interrupted_writes_are_found() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join(".system")).unwrap();
+        fs::create_dir_all(dir.path().join("Saves")).unwrap();
+        fs::write(dir.path().join(".system/launch.sh"), b"launch").unwrap();
+        // As if the device lost power before the write was committed
+        fs::write(dir.path().join(".system/.launch.sh.tmp"), b"lau").unwrap();
+        fs::write(dir.path().join("Saves/.game.sav.tmp"), b"save").unwrap();
+
+        let mut found = vec![];
+        temp_files(dir.path(), &CancellationToken::new(), &mut found).unwrap();
+        assert_eq!(found, vec![dir.path().join(".system/.launch.sh.tmp")]);
+    }
+}
@@ -0,0 +1,504 @@
+use std::fs;
+use std::io::{self, Cursor};
+use std::path::{Component, Path, PathBuf};
+use std::time::Instant;
+
+use bytes::Bytes;
+use log::{debug, info, warn};
+
+use super::atomic::AtomicFile;
+use super::fetching::download_range;
+use super::journal::Journal;
+use super::preview::{self, FolderSummary};
+use super::{unchanged, EXTRACT_CHUNK_SIZE};
+use crate::app_state::Transfer;
+use crate::cancel::CancellationToken;
+use crate::error::{Result, UpdaterError};
+use crate::fault::{self, CrashPoint};
+use crate::sdcard_root;
+
+// Delta updates download only the files that changed. The central directory
+// at the end of a zip lists every entry with its size and CRC-32, which is
+// enough to tell which files on the SD card are out of date; those entries
+// are then fetched on their own with range requests.
+
+// End of central directory record, which is followed by a comment of up to
+// 64KB
+const EOCD_SIZE: usize = 22;
+const EOCD_SIGNATURE: u32 = 0x0605_4b50;
+const CENTRAL_HEADER_SIZE: usize = 46;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x0201_4b50;
+// General purpose flags
+const FLAG_ENCRYPTED: u16 = 1;
+const FLAG_DATA_DESCRIPTOR: u16 = 1 << 3;
+const FLAG_UTF8: u16 = 1 << 11;
+
+// An archive entry to be written, and where it is in the archive
+struct Entry {
+    name: PathBuf,
+    // From its local header to the next entry's
+    start: u64,
+    end: u64,
+}
+
+// An entry in an archive's central directory
+pub struct Listed {
+    pub name: String,
+    flags: u16,
+    pub size: u64,
+    pub crc32: u32,
+    // Where its local header is
+    start: u64,
+}
+
+// An archive's central directory, read without downloading the rest
+pub struct Directory {
+    archive_size: u64,
+    // Where the directory itself starts, after the last entry
+    start: u64,
+    pub entries: Vec<Listed>,
+}
+
+// The entries of an archive that differ from what's on the SD card
+pub struct Plan {
+    url: String,
+    archive_size: u64,
+    changed: Vec<Entry>,
+    // Directories in the archive that don't exist yet
+    directories: Vec<PathBuf>,
+    folders: Vec<FolderSummary>,
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        bytes.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        bytes.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+// The name as a relative path inside the SD card, or None if it would point
+// anywhere else
+pub fn enclosed_name(name: &str) -> Option<PathBuf> {
+    let path = Path::new(name);
+    path.components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+        .then(|| path.to_path_buf())
+}
+
+// Work out what a delta update from the first URL that supports range
+// requests would download. None if none does, or the archive can't be
+// handled this way.
+pub fn plan(urls: &[String], filter: impl Fn(&str) -> bool) -> Option<Plan> {
+    for url in urls {
+        match plan_from(url, &filter) {
+            Ok(Some(plan)) => return Some(plan),
+            Ok(None) => debug!("No delta update possible from {url}"),
+            Err(err) => warn!("Delta update check against {url} failed: {err}"),
+        }
+    }
+    None
+}
+
+// Read the central directory of the archive at `url` with range requests.
+// None if the server doesn't support them, or the archive is one this can't
+// read, e.g. zip64.
+pub fn read_directory(url: &str) -> Result<Option<Directory>> {
+    let Some((tail, archive_size)) =
+        download_range(url, &format!("-{}", EOCD_SIZE + usize::from(u16::MAX)))?
+    else {
+        return Ok(None);
+    };
+    let Some((directory_start, directory_size)) = locate_directory(&tail) else {
+        return Ok(None);
+    };
+
+    // The central directory is usually small enough to have come with the tail
+    let directory_end = directory_start + directory_size;
+    let tail_start = archive_size.saturating_sub(tail.len() as u64);
+    let directory = if directory_start >= tail_start {
+        let (Ok(start), Ok(end)) = (
+            usize::try_from(directory_start - tail_start),
+            usize::try_from(directory_end - tail_start),
+        ) else {
+            return Ok(None);
+        };
+        if end > tail.len() {
+            return Ok(None);
+        }
+        tail.slice(start..end)
+    } else {
+        match download_range(url, &format!("{directory_start}-{}", directory_end - 1))? {
+            Some((directory, _)) => directory,
+            None => return Ok(None),
+        }
+    };
+
+    let Some(entries) = parse_directory(&directory) else {
+        return Ok(None);
+    };
+    Ok(Some(Directory {
+        archive_size,
+        start: directory_start,
+        entries,
+    }))
+}
+
+// Where the central directory starts and how big it is, going by the end of
+// central directory record in `tail`. None if there's no record, or the
+// archive is zip64.
+fn locate_directory(tail: &[u8]) -> Option<(u64, u64)> {
+    let record = (0..=tail.len().saturating_sub(EOCD_SIZE))
+        .rev()
+        .find(|&offset| u32_at(tail, offset) == Some(EOCD_SIGNATURE))?;
+    let entry_count = u16_at(tail, record + 10)?;
+    let directory_size = u32_at(tail, record + 12)?;
+    let directory_start = u32_at(tail, record + 16)?;
+    // Zip64 archives keep the real values somewhere else
+    if entry_count == u16::MAX || directory_size == u32::MAX || directory_start == u32::MAX {
+        return None;
+    }
+    Some((u64::from(directory_start), u64::from(directory_size)))
+}
+
+// Name, flags, size, CRC-32 and local header offset of each entry. None if
+// the directory is cut short, or has entries this can't handle.
+fn parse_directory(directory: &[u8]) -> Option<Vec<Listed>> {
+    let mut entries = vec![];
+    let mut offset = 0;
+    while offset < directory.len() {
+        if u32_at(directory, offset) != Some(CENTRAL_HEADER_SIGNATURE) {
+            return None;
+        }
+        let (
+            Some(flags),
+            Some(crc32),
+            Some(size),
+            Some(name_length),
+            Some(extra_length),
+            Some(comment_length),
+            Some(start),
+        ) = (
+            u16_at(directory, offset + 8),
+            u32_at(directory, offset + 16),
+            u32_at(directory, offset + 24),
+            u16_at(directory, offset + 28),
+            u16_at(directory, offset + 30),
+            u16_at(directory, offset + 32),
+            u32_at(directory, offset + 42),
+        )
+        else {
+            return None;
+        };
+        let name_start = offset + CENTRAL_HEADER_SIZE;
+        let name = directory.get(name_start..name_start + usize::from(name_length))?;
+        // Other encodings would have to be decoded the way the zip crate does
+        if !name.is_ascii() && flags & FLAG_UTF8 == 0 {
+            return None;
+        }
+        let name = String::from_utf8_lossy(name).into_owned();
+        // Sizes kept in a zip64 extra field
+        if size == u32::MAX || start == u32::MAX {
+            return None;
+        }
+        entries.push(Listed {
+            name,
+            flags,
+            size: u64::from(size),
+            crc32,
+            start: u64::from(start),
+        });
+        offset = name_start
+            + usize::from(name_length)
+            + usize::from(extra_length)
+            + usize::from(comment_length);
+    }
+    Some(entries)
+}
+
+fn plan_from(url: &str, filter: &impl Fn(&str) -> bool) -> Result<Option<Plan>> {
+    let Some(Directory {
+        archive_size,
+        start: directory_start,
+        entries,
+    }) = read_directory(url)?
+    else {
+        return Ok(None);
+    };
+
+    // Entries are stored one after the other, so each ends where the next starts
+    let mut starts: Vec<u64> = entries.iter().map(|entry| entry.start).collect();
+    starts.push(directory_start);
+    starts.sort_unstable();
+    let end_of = |start: u64| {
+        starts
+            .iter()
+            .find(|&&other| other > start)
+            .copied()
+            .unwrap_or(directory_start)
+    };
+
+    let target_directory = PathBuf::from(sdcard_root());
+    let mut buffer = vec![0; EXTRACT_CHUNK_SIZE];
+    let mut files = vec![];
+    let mut changed = vec![];
+    let mut directories = vec![];
+    for Listed {
+        name,
+        flags,
+        size,
+        crc32,
+        start,
+    } in entries
+    {
+        let Some(sanitized_name) = enclosed_name(&name) else {
+            return Ok(None);
+        };
+        if name.ends_with('/') {
+            if filter(&sanitized_name.to_string_lossy())
+                && !target_directory.join(&sanitized_name).exists()
+            {
+                directories.push(sanitized_name);
+            }
+            continue;
+        }
+        files.push(sanitized_name.clone());
+        if !filter(&sanitized_name.to_string_lossy())
+            || unchanged(
+                &target_directory.join(&sanitized_name),
+                size,
+                crc32,
+                &mut buffer,
+            )
+        {
+            continue;
+        }
+        // The sizes of these are only known from the central directory
+        if flags & (FLAG_ENCRYPTED | FLAG_DATA_DESCRIPTOR) != 0 {
+            return Ok(None);
+        }
+        changed.push(Entry {
+            name: sanitized_name,
+            start,
+            end: end_of(start),
+        });
+    }
+    changed.sort_by_key(|entry| entry.start);
+
+    Ok(Some(Plan {
+        url: url.to_string(),
+        archive_size,
+        changed,
+        directories,
+        folders: preview::summarize_files(files, filter),
+    }))
+}
+
+impl Plan {
+    pub fn files(&self) -> usize {
+        self.changed.len()
+    }
+
+    pub fn download_size(&self) -> u64 {
+        self.changed
+            .iter()
+            .map(|entry| entry.end - entry.start)
+            .sum()
+    }
+
+    // Downloading most of the archive in pieces is slower than all of it at once
+    pub fn worth_it(&self) -> bool {
+        self.download_size() * 2 < self.archive_size
+    }
+
+    pub fn folders(&self) -> &[FolderSummary] {
+        &self.folders
+    }
+
+    // Download the changed entries, one request per run of neighbouring ones,
+    // stopping between requests once `token` is cancelled
+    pub fn download(
+        &self,
+        token: &CancellationToken,
+        progress_cb: impl Fn(Transfer),
+    ) -> Result<Vec<Bytes>> {
+        let total = self.download_size();
+        let started = Instant::now();
+        let mut downloaded = 0;
+        let mut parts = Vec::with_capacity(self.changed.len());
+
+        let mut remaining = &self.changed[..];
+        while let Some(first) = remaining.first() {
+            token.check()?;
+            let run = remaining
+                .windows(2)
+                .take_while(|pair| pair[0].end == pair[1].start)
+                .count()
+                + 1;
+            let (entries, rest) = remaining.split_at(run);
+            remaining = rest;
+
+            let end = entries.last().map_or(first.end, |entry| entry.end);
+            let Some((bytes, _)) =
+                download_range(&self.url, &format!("{}-{}", first.start, end - 1))?
+            else {
+                return Err(UpdaterError::CorruptDownload);
+            };
+            if bytes.len() as u64 != end - first.start {
+                warn!("Got {} bytes, expected {}", bytes.len(), end - first.start);
+                return Err(UpdaterError::CorruptDownload);
+            }
+            for entry in entries {
+                let (Ok(start), Ok(end)) = (
+                    usize::try_from(entry.start - first.start),
+                    usize::try_from(entry.end - first.start),
+                ) else {
+                    return Err(UpdaterError::CorruptDownload);
+                };
+                parts.push(bytes.slice(start..end));
+            }
+
+            downloaded += end - first.start;
+            progress_cb(Transfer {
+                downloaded,
+                total,
+                speed: downloaded as f64 / started.elapsed().as_secs_f64().max(0.001),
+            });
+        }
+
+        Ok(parts)
+    }
+
+    // Check each downloaded part is the entry it's supposed to be. Contents
+    // are checked against their CRC-32 while extracting.
+    pub fn check(&self, parts: &[Bytes]) -> Result<()> {
+        if parts.len() != self.changed.len() {
+            return Err(UpdaterError::CorruptDownload);
+        }
+        for (entry, part) in self.changed.iter().zip(parts) {
+            let name = zip::read::read_zipfile_from_stream(&mut Cursor::new(part))?
+                .and_then(|file| enclosed_name(file.name()));
+            if name.as_ref() != Some(&entry.name) {
+                warn!("{} isn't where the archive says", entry.name.display());
+                return Err(UpdaterError::CorruptDownload);
+            }
+        }
+        Ok(())
+    }
+
+    // Write the downloaded entries to the SD card
+    pub fn extract(
+        &self,
+        parts: Vec<Bytes>,
+        journal: &mut Journal,
+        token: &CancellationToken,
+        progress_cb: impl Fn(f32),
+    ) -> Result<()> {
+        let target_directory = PathBuf::from(sdcard_root());
+        for directory in &self.directories {
+            fs::create_dir_all(target_directory.join(directory))?;
+        }
+
+        let total = self.download_size().max(1);
+        let mut extracted = 0;
+        for (entry, part) in self.changed.iter().zip(parts) {
+            token.check()?;
+            let mut reader = Cursor::new(part);
+            let Some(mut file) = zip::read::read_zipfile_from_stream(&mut reader)? else {
+                return Err(UpdaterError::CorruptDownload);
+            };
+
+            let path = target_directory.join(&entry.name);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let mut extracted_file = AtomicFile::create(&path)?;
+            io::copy(&mut file, &mut extracted_file)?;
+            extracted_file.commit()?;
+            journal.record(&entry.name.to_string_lossy())?;
+            debug!("Extracted file: {}", path.display());
+            fault::crash_point(CrashPoint::ExtractedFile);
+
+            extracted += entry.end - entry.start;
+            progress_cb(extracted as f32 / total as f32);
+        }
+
+        info!("Delta update wrote {} files", self.changed.len());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+
+    const FILES: [(&str, &[u8]); 2] = [("MinUI.zip", b"boot"), (".system/version.txt", b"6.2.0")];
+
+    fn archive() -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, contents) in FILES {
+            writer
+                .start_file(name, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(contents).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    // The central directory, as a range request would return it
+    fn directory(archive: &[u8]) -> Vec<u8> {
+        let (start, size) = locate_directory(archive).unwrap();
+        archive[usize::try_from(start).unwrap()..usize::try_from(start + size).unwrap()].to_vec()
+    }
+
+    #[test]
+    fn central_directories_list_every_entry() {
+        let entries = parse_directory(&directory(&archive())).unwrap();
+        assert_eq!(entries.len(), FILES.len());
+        for (entry, (name, contents)) in entries.iter().zip(FILES) {
+            assert_eq!(entry.name, name);
+            assert_eq!(entry.size, contents.len() as u64);
+            assert_eq!(entry.crc32, crc32fast::hash(contents));
+        }
+    }
+
+    #[test]
+    fn truncated_archives_are_not_read() {
+        let archive = archive();
+        assert!(locate_directory(&archive[..archive.len() - 4]).is_none());
+        let directory = directory(&archive);
+        assert!(parse_directory(&directory[..directory.len() - 4]).is_none());
+        assert!(parse_directory(&directory[..CENTRAL_HEADER_SIZE - 4]).is_none());
+    }
+
+    #[test]
+    fn zip64_archives_are_not_read() {
+        let original = archive();
+
+        // The directory's offset moved to the zip64 record
+        let mut archive = original.clone();
+        let record = archive.len() - EOCD_SIZE;
+        archive[record + 16..record + 20].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(locate_directory(&archive).is_none());
+
+        // An entry's size moved to its zip64 extra field
+        let mut directory = directory(&original);
+        directory[24..28].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(parse_directory(&directory).is_none());
+    }
+
+    #[test]
+    fn archives_without_an_end_record_are_not_read() {
+        assert!(locate_directory(&[]).is_none());
+        assert!(locate_directory(&[0; 100]).is_none());
+        let archive = archive();
+        let directory = directory(&archive);
+        assert!(locate_directory(&directory).is_none());
+    }
+}
@@ -1,106 +1,424 @@
+use std::fs::File;
 use std::io::{Read, Write};
-use std::sync::OnceLock;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
 
+use base64::prelude::{Engine, BASE64_STANDARD};
 use bytes::Bytes;
 use const_format::concatcp;
-use reqwest::blocking::Client;
-use reqwest::IntoUrl;
+use log::{debug, info, warn};
+use md5::{Digest, Md5};
+use parking_lot::Mutex;
+use reqwest::blocking::{Client, Response};
+use reqwest::{IntoUrl, Proxy, StatusCode};
+use serde::de::DeserializeOwned;
 
-use crate::github::{Release, Tag};
-use crate::Result;
+use crate::app_state::Transfer;
+use crate::cancel::CancellationToken;
+use crate::config::Config;
+use crate::error::{Result, UpdaterError};
+use crate::fault::{self, Phase};
+use crate::github::{Commit, Comparison, Release, Tag};
 
 const USER_AGENT: &str = concatcp!("NextUIUpdater/", env!("CARGO_PKG_VERSION"));
 
-static CLIENT_CELL: OnceLock<Client> = OnceLock::new();
+// Download speed is measured over windows this long and smoothed between them
+const SPEED_WINDOW: Duration = Duration::from_millis(500);
+const SPEED_SMOOTHING: f64 = 0.3;
 
-fn get_client() -> &'static Client {
-    CLIENT_CELL.get_or_init(|| {
-        reqwest::blocking::Client::builder()
-            .danger_accept_invalid_certs(true)
-            .danger_accept_invalid_hostnames(true)
-            .timeout(None)
-            .build()
-            .expect("Failed to create HTTP client")
-    })
+// Built on first use, and again after the settings change
+static CLIENT: Mutex<Option<Client>> = Mutex::new(None);
+static SETTINGS: Mutex<ClientSettings> = Mutex::new(ClientSettings {
+    proxy: None,
+    insecure_tls: false,
+    connect_timeout: None,
+    stall_timeout: None,
+    github_token: None,
+});
+// Bytes per second downloads are held to, 0 for no limit. Unlike the other
+// settings it can change at any time, even during a download.
+static DOWNLOAD_LIMIT: AtomicU64 = AtomicU64::new(0);
+
+struct ClientSettings {
+    proxy: Option<String>,
+    insecure_tls: bool,
+    connect_timeout: Option<Duration>,
+    stall_timeout: Option<Duration>,
+    github_token: Option<String>,
 }
 
-pub fn fetch_latest_release(repo: &str) -> Result<Release> {
-    let response = get_client()
-        .get(format!(
-            "https://api.github.com/repos/{repo}/releases/latest"
-        ))
-        .header("User-Agent", USER_AGENT)
-        .send()?;
+// Apply the network settings to every request from now on. Without a proxy
+// the usual HTTPS_PROXY/ALL_PROXY variables apply.
+pub fn configure_client(config: &Config) {
+    *SETTINGS.lock() = ClientSettings {
+        proxy: config.proxy.clone().filter(|proxy| !proxy.is_empty()),
+        insecure_tls: config.insecure_tls,
+        connect_timeout: seconds(config.connect_timeout),
+        stall_timeout: seconds(config.stall_timeout),
+        github_token: config
+            .github_token
+            .clone()
+            .filter(|token| !token.is_empty()),
+    };
+    *CLIENT.lock() = None;
+    set_download_limit(config.download_limit);
+}
+
+fn seconds(timeout: u64) -> Option<Duration> {
+    Some(Duration::from_secs(timeout)).filter(|timeout| !timeout.is_zero())
+}
+
+// In KB per second
+pub fn set_download_limit(limit: Option<u64>) {
+    DOWNLOAD_LIMIT.store(limit.unwrap_or(0) * 1024, Ordering::Relaxed);
+}
 
-    if !response.status().is_success() {
-        return Err(format!("GitHub API request failed: {}", response.status()).into());
+// Paces a download to the download limit, one chunk at a time
+struct Throttle {
+    last_chunk: Instant,
+}
+
+impl Throttle {
+    fn new() -> Self {
+        Self {
+            last_chunk: Instant::now(),
+        }
     }
 
-    Ok(response.json()?)
+    // Wait until a chunk this big would have arrived at the limit
+    fn pace(&mut self, chunk_size: usize) {
+        let limit = DOWNLOAD_LIMIT.load(Ordering::Relaxed);
+        if limit > 0 {
+            let chunk_time = Duration::from_secs_f64(chunk_size as f64 / limit as f64);
+            if let Some(wait) = chunk_time.checked_sub(self.last_chunk.elapsed()) {
+                std::thread::sleep(wait);
+            }
+        }
+        self.last_chunk = Instant::now();
+    }
 }
 
-pub fn fetch_releases(repo: &str) -> Result<Vec<Release>> {
-    let response = get_client()
-        .get(format!(
-            "https://api.github.com/repos/{repo}/releases?per_page=100"
-        ))
-        .header("User-Agent", USER_AGENT)
-        .send()?;
+// Read the whole response, keeping to the download limit
+fn read_limited(
+    mut response: Response,
+    mut on_chunk: impl FnMut(&[u8]) -> Result<()>,
+) -> Result<()> {
+    let mut buffer = [0; 16384];
+    let mut throttle = Throttle::new();
+    loop {
+        let bytes_read = response.read(&mut buffer)?;
+        if bytes_read == 0 {
+            return Ok(());
+        }
+        throttle.pace(bytes_read);
+        on_chunk(&buffer[..bytes_read])?;
+    }
+}
+
+// Cheap to clone, it shares its connections
+fn get_client() -> Client {
+    let mut client = CLIENT.lock();
+    let client = client.get_or_insert_with(|| {
+        let settings = SETTINGS.lock();
+        // Certificates are checked against the bundled webpki roots, so this
+        // works without a system CA store. The blocking client's timeout
+        // applies to waiting for the response and then to each read of it,
+        // so it catches stalled downloads without limiting how long they take.
+        let mut builder = reqwest::blocking::Client::builder()
+            .use_rustls_tls()
+            .tls_built_in_webpki_certs(true)
+            .connect_timeout(settings.connect_timeout)
+            .timeout(settings.stall_timeout);
+        if settings.insecure_tls {
+            warn!("TLS certificate validation is disabled in the settings");
+            builder = builder
+                .danger_accept_invalid_certs(true)
+                .danger_accept_invalid_hostnames(true);
+        }
+        if let Some(proxy) = &settings.proxy {
+            // http://, https://, socks5:// or socks5h://
+            match Proxy::all(proxy) {
+                Ok(proxy) => {
+                    info!("Using proxy from settings");
+                    builder = builder.proxy(proxy);
+                }
+                Err(err) => warn!("Ignoring invalid proxy setting: {err}"),
+            }
+        }
+        builder.build().expect("Failed to create HTTP client")
+    });
+    client.clone()
+}
+
+// Turn unsuccessful responses into errors, telling rate limiting apart from
+// other failures
+fn check_status(response: Response) -> Result<Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let rate_limited = status == StatusCode::TOO_MANY_REQUESTS
+        || (status == StatusCode::FORBIDDEN
+            && response
+                .headers()
+                .get("x-ratelimit-remaining")
+                .is_some_and(|remaining| remaining == "0"));
+    if rate_limited {
+        return Err(UpdaterError::RateLimited);
+    }
+    Err(UpdaterError::Api(status))
+}
+
+fn api_response(url: &str) -> Result<Response> {
+    fault::inject(Phase::Metadata)?;
+
+    let mut request = get_client().get(url).header("User-Agent", USER_AGENT);
+    // Only ever sent to GitHub's API, never to mirrors or other forges
+    if url.starts_with("https://api.github.com/") {
+        if let Some(token) = &SETTINGS.lock().github_token {
+            request = request.bearer_auth(token);
+        }
+    }
+
+    check_status(request.send()?)
+}
+
+pub(super) fn api_get<T: DeserializeOwned>(url: &str) -> Result<T> {
+    Ok(api_response(url)?.json()?)
+}
+
+// URL of the next page from a Link header, e.g.
+// `<https://api.github.com/...&page=2>; rel="next", <...>; rel="last"`
+fn next_page(response: &Response) -> Option<String> {
+    let link = response.headers().get("link")?.to_str().ok()?;
+    link.split(',').find_map(|part| {
+        let (url, params) = part.split_once(';')?;
+        params
+            .split(';')
+            .any(|param| param.trim() == r#"rel="next""#)
+            .then(|| {
+                url.trim()
+                    .trim_start_matches('<')
+                    .trim_end_matches('>')
+                    .to_string()
+            })
+    })
+}
 
-    if !response.status().is_success() {
-        return Err(format!("GitHub API request failed: {}", response.status()).into());
+// Follow Link headers for up to `max_pages` pages, collecting every item
+pub(super) fn api_get_pages<T: DeserializeOwned>(url: &str, max_pages: usize) -> Result<Vec<T>> {
+    let mut items = Vec::new();
+    let mut url = Some(url.to_string());
+    for _ in 0..max_pages.max(1) {
+        let Some(page_url) = url.take() else {
+            break;
+        };
+        let response = api_response(&page_url)?;
+        url = next_page(&response);
+        items.extend(response.json::<Vec<T>>()?);
     }
+    if let Some(url) = url {
+        debug!("Stopped after {max_pages} pages, next was {url}");
+    }
+    Ok(items)
+}
+
+pub fn fetch_latest_release(repo: &str) -> Result<Release> {
+    api_get(&format!("https://api.github.com/repos/{repo}/releases/latest"))
+}
+
+pub fn fetch_releases(repo: &str, max_pages: usize) -> Result<Vec<Release>> {
+    api_get_pages(
+        &format!("https://api.github.com/repos/{repo}/releases?per_page=100"),
+        max_pages,
+    )
+}
+
+pub fn fetch_tags(repo: &str, max_pages: usize) -> Result<Vec<Tag>> {
+    api_get_pages(
+        &format!("https://api.github.com/repos/{repo}/tags?per_page=100"),
+        max_pages,
+    )
+}
+
+pub fn fetch_release_by_tag(repo: &str, tag_name: &str) -> Result<Release> {
+    api_get(&format!("https://api.github.com/repos/{repo}/releases/tags/{tag_name}"))
+}
 
-    Ok(response.json()?)
+pub fn fetch_comparison(repo: &str, base: &str, head: &str) -> Result<Comparison> {
+    api_get(&format!("https://api.github.com/repos/{repo}/compare/{base}...{head}"))
 }
 
-pub fn fetch_tags(repo: &str) -> Result<Vec<Tag>> {
+// Look up a single tag by name, for tags that weren't in the fetched tag list
+pub fn fetch_tag(repo: &str, tag_name: &str) -> Result<Tag> {
+    let commit: Commit = api_get(&format!(
+        "https://api.github.com/repos/{repo}/commits/{tag_name}"
+    ))?;
+
+    Ok(Tag {
+        name: tag_name.to_string(),
+        commit,
+    })
+}
+
+pub fn post_json(url: &str, body: &serde_json::Value) -> Result<()> {
     let response = get_client()
-        .get(format!("https://api.github.com/repos/{repo}/tags?per_page=100"))
+        .post(url)
         .header("User-Agent", USER_AGENT)
+        .json(body)
         .send()?;
+    check_status(response)?;
+    Ok(())
+}
+
+// MD5 the server vouched for, if any. S3, where GitHub keeps release assets,
+// sends it as a plain hex ETag for files uploaded in one part.
+fn expected_md5(response: &Response) -> Option<[u8; 16]> {
+    let headers = response.headers();
+    if let Some(md5) = headers.get("content-md5") {
+        return BASE64_STANDARD.decode(md5.as_bytes()).ok()?.try_into().ok();
+    }
+
+    // Weak ETags and multipart ones ("<hex>-<parts>") aren't checksums
+    let etag = headers.get("etag")?.to_str().ok()?;
+    let etag = etag.strip_prefix('"')?.strip_suffix('"')?;
+    if etag.len() != 32 {
+        return None;
+    }
+    let mut md5 = [0; 16];
+    for (index, byte) in md5.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(etag.get(index * 2..index * 2 + 2)?, 16).ok()?;
+    }
+    Some(md5)
+}
 
-    if !response.status().is_success() {
-        return Err(format!("GitHub API request failed: {}", response.status()).into());
+// Download part of a file, with `range` like "100-199" or "-100" for the last
+// 100 bytes. Returns the part and the size of the whole file, or None if the
+// server doesn't support range requests.
+pub fn download_range(url: &str, range: &str) -> Result<Option<(Bytes, u64)>> {
+    let response = check_status(
+        get_client()
+            .get(url)
+            .header("Accept", "application/octet-stream")
+            .header("User-Agent", USER_AGENT)
+            .header("Range", format!("bytes={range}"))
+            .send()?,
+    )?;
+    if response.status() != StatusCode::PARTIAL_CONTENT {
+        return Ok(None);
     }
 
-    let tags: Vec<Tag> = response.json()?;
+    // "bytes 100-199/1234"
+    let total_size = response
+        .headers()
+        .get("content-range")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.rsplit('/').next())
+        .and_then(|total| total.parse().ok());
+    let Some(total_size) = total_size else {
+        return Ok(None);
+    };
+    let mut bytes = Vec::new();
+    read_limited(response, |chunk| {
+        bytes.extend_from_slice(chunk);
+        Ok(())
+    })?;
+    Ok(Some((bytes.into(), total_size)))
+}
 
-    Ok(tags.clone())
+// Stops with Cancelled between chunks once `token` is cancelled
+pub fn download<U: IntoUrl>(
+    url: U,
+    token: &CancellationToken,
+    progress_cb: impl Fn(Transfer),
+) -> Result<Bytes> {
+    let mut bytes = Vec::new();
+    download_into(url, &mut bytes, token, progress_cb)?;
+    Ok(bytes.into())
+}
+
+// Like `download`, but to a file, for downloads too big to hold in memory
+pub fn download_to_file<U: IntoUrl>(
+    url: U,
+    path: &Path,
+    token: &CancellationToken,
+    progress_cb: impl Fn(Transfer),
+) -> Result<()> {
+    let mut file = File::create(path)?;
+    download_into(url, &mut file, token, progress_cb)?;
+    file.sync_all()?;
+    Ok(())
 }
 
-pub fn download<U: IntoUrl>(url: U, progress_cb: impl Fn(f32)) -> Result<Bytes> {
+fn download_into<U: IntoUrl>(
+    url: U,
+    out: &mut impl Write,
+    token: &CancellationToken,
+    progress_cb: impl Fn(Transfer),
+) -> Result<()> {
     let request_builder = get_client()
         .get(url)
         .header("Accept", "application/octet-stream")
         .header("User-Agent", USER_AGENT);
 
-    let mut response = request_builder.send()?;
-    println!("Status: {}", response.status());
-    println!("Headers: {:?}", response.headers());
+    let response = check_status(request_builder.send()?)?;
+    debug!("Status: {}", response.status());
+    debug!("Headers: {:?}", response.headers());
 
     let total_size = response.content_length().unwrap_or(0);
+    let expected_md5 = expected_md5(&response);
+    let mut md5 = Md5::new();
 
-    let mut bytes = Vec::new();
     let mut downloaded: u64 = 0;
-    let mut buffer = [0; 16384];
+    let mut window_start = Instant::now();
+    let mut window_bytes: u64 = 0;
+    let mut speed: Option<f64> = None;
 
-    loop {
-        let bytes_read = response.read(&mut buffer)?;
-        if bytes_read == 0 {
-            break;
+    read_limited(response, |chunk| {
+        token.check()?;
+        out.write_all(chunk)?;
+        md5.update(chunk);
+        let bytes_read = chunk.len() as u64;
+        if downloaded < total_size / 2 && downloaded + bytes_read >= total_size / 2 {
+            fault::inject(Phase::Download)?;
+        }
+        downloaded += bytes_read;
+        window_bytes += bytes_read;
+
+        let elapsed = window_start.elapsed();
+        if elapsed >= SPEED_WINDOW {
+            let window_speed = window_bytes as f64 / elapsed.as_secs_f64();
+            speed = Some(speed.map_or(window_speed, |speed| {
+                speed + (window_speed - speed) * SPEED_SMOOTHING
+            }));
+            window_start = Instant::now();
+            window_bytes = 0;
         }
-        bytes.write_all(&buffer[..bytes_read])?;
-        downloaded += bytes_read as u64;
 
         // Show progress
-        if total_size > 0 {
-            let percentage = downloaded as f64 / total_size as f64;
-            progress_cb(percentage as f32);
+        progress_cb(Transfer {
+            downloaded,
+            total: total_size,
+            speed: speed.unwrap_or(0.0),
+        });
+        Ok(())
+    })?;
+
+    info!("Download complete!");
+
+    if total_size > 0 && downloaded != total_size {
+        warn!("Downloaded {downloaded} bytes, expected {total_size}");
+        return Err(UpdaterError::CorruptDownload);
+    }
+    if let Some(expected_md5) = expected_md5 {
+        if md5.finalize().as_slice() != expected_md5 {
+            warn!("Download doesn't match the MD5 sent by the server");
+            return Err(UpdaterError::CorruptDownload);
         }
+        debug!("Download matches the MD5 sent by the server");
     }
 
-    println!("\nDownload complete!");
-
-    Ok(bytes.into())
+    Ok(())
 }
@@ -0,0 +1,169 @@
+use std::path::Path;
+
+use bytes::Bytes;
+use serde::Deserialize;
+
+use super::fetching::{self, api_get, api_get_pages};
+use super::source::ReleaseSource;
+use crate::app_state::Transfer;
+use crate::cancel::CancellationToken;
+use crate::error::Result;
+use crate::github::{Asset, Release, Tag};
+
+// Largest page Gitea serves by default
+const PAGE_SIZE: usize = 50;
+
+// A Gitea or Forgejo server's API. Releases look like GitHub's except for
+// their assets, tags are the same.
+pub struct Gitea {
+    // e.g. "https://codeberg.org"
+    pub url: String,
+}
+
+#[derive(Deserialize)]
+struct GiteaAsset {
+    name: String,
+    // Direct download, Gitea has no API URL for assets
+    browser_download_url: String,
+    #[serde(default)]
+    size: u64,
+    #[serde(default)]
+    download_count: u64,
+}
+
+#[derive(Deserialize)]
+struct GiteaRelease {
+    tag_name: String,
+    published_at: Option<String>,
+    body: Option<String>,
+    #[serde(default)]
+    assets: Vec<GiteaAsset>,
+    #[serde(default)]
+    draft: bool,
+    #[serde(default)]
+    prerelease: bool,
+}
+
+impl From<GiteaRelease> for Release {
+    fn from(release: GiteaRelease) -> Self {
+        Release {
+            tag_name: release.tag_name,
+            published_at: release.published_at,
+            body: release.body,
+            assets: release
+                .assets
+                .into_iter()
+                .map(|asset| Asset {
+                    name: asset.name,
+                    url: asset.browser_download_url,
+                    size: asset.size,
+                    download_count: asset.download_count,
+                    digest: None,
+                })
+                .collect(),
+            draft: release.draft,
+            prerelease: release.prerelease,
+        }
+    }
+}
+
+impl Gitea {
+    fn api_url(&self, repo: &str, path: &str) -> String {
+        format!(
+            "{}/api/v1/repos/{repo}/{path}",
+            self.url.trim_end_matches('/')
+        )
+    }
+}
+
+impl ReleaseSource for Gitea {
+    fn fetch_releases(&self, repo: &str, max_pages: usize) -> Result<Vec<Release>> {
+        let releases: Vec<GiteaRelease> = api_get_pages(
+            &self.api_url(repo, &format!("releases?limit={PAGE_SIZE}")),
+            max_pages,
+        )?;
+        Ok(releases.into_iter().map(Release::from).collect())
+    }
+
+    fn fetch_release(&self, repo: &str, tag_name: &str) -> Result<Release> {
+        let release: GiteaRelease =
+            api_get(&self.api_url(repo, &format!("releases/tags/{tag_name}")))?;
+        Ok(release.into())
+    }
+
+    fn fetch_tags(&self, repo: &str, max_pages: usize) -> Result<Vec<Tag>> {
+        api_get_pages(
+            &self.api_url(repo, &format!("tags?limit={PAGE_SIZE}")),
+            max_pages,
+        )
+    }
+
+    fn fetch_tag(&self, repo: &str, tag_name: &str) -> Result<Tag> {
+        api_get(&self.api_url(repo, &format!("tags/{tag_name}")))
+    }
+
+    fn download(
+        &self,
+        url: &str,
+        token: &CancellationToken,
+        progress_cb: &dyn Fn(Transfer),
+    ) -> Result<Bytes> {
+        fetching::download(url, token, progress_cb)
+    }
+
+    fn download_to_file(
+        &self,
+        url: &str,
+        path: &Path,
+        token: &CancellationToken,
+        progress_cb: &dyn Fn(Transfer),
+    ) -> Result<()> {
+        fetching::download_to_file(url, path, token, progress_cb)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn releases_use_direct_download_urls() {
+        let json = r#"{
+            "id": 1,
+            "tag_name": "v1.0.0",
+            "name": "v1.0.0",
+            "body": "Notes",
+            "draft": false,
+            "prerelease": true,
+            "published_at": "2025-01-01T00:00:00Z",
+            "assets": [{
+                "id": 2,
+                "name": "NextUI-base.zip",
+                "size": 1234,
+                "download_count": 5,
+                "browser_download_url": "https://codeberg.org/someone/NextUI/releases/download/v1.0.0/NextUI-base.zip"
+            }]
+        }"#;
+
+        let release: Release = serde_json::from_str::<GiteaRelease>(json).unwrap().into();
+
+        assert_eq!(release.tag_name, "v1.0.0");
+        assert!(release.prerelease);
+        assert_eq!(release.assets[0].size, 1234);
+        assert_eq!(
+            release.assets[0].url,
+            "https://codeberg.org/someone/NextUI/releases/download/v1.0.0/NextUI-base.zip"
+        );
+    }
+
+    #[test]
+    fn api_urls_ignore_a_trailing_slash() {
+        let gitea = Gitea {
+            url: "https://codeberg.org/".to_string(),
+        };
+        assert_eq!(
+            gitea.api_url("someone/NextUI", "tags?limit=50"),
+            "https://codeberg.org/api/v1/repos/someone/NextUI/tags?limit=50"
+        );
+    }
+}
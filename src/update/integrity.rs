@@ -0,0 +1,100 @@
+use std::fs::File;
+use std::path::PathBuf;
+
+use log::{info, warn};
+
+use super::delta::{self, Directory};
+use super::{file_crc32, EXTRACT_CHUNK_SIZE};
+use crate::cancel::CancellationToken;
+use crate::error::{Result, UpdaterError};
+use crate::sdcard_root;
+
+// What's wrong with an installed file, compared to the release it came from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Problem {
+    Missing,
+    // A different size, e.g. edited or replaced
+    Modified,
+    // The right size with different contents, which is what SD card
+    // corruption tends to look like
+    Corrupted,
+}
+
+#[derive(Clone, Debug)]
+pub struct IntegrityReport {
+    pub tag_name: String,
+    pub checked: usize,
+    pub problems: Vec<(String, Problem)>,
+}
+
+impl IntegrityReport {
+    pub fn count(&self, problem: Problem) -> usize {
+        self.problems
+            .iter()
+            .filter(|(_, found)| *found == problem)
+            .count()
+    }
+}
+
+// Compare the files on the SD card with the archive at the first of `urls`
+// that can be read with range requests, without downloading it
+pub fn check(
+    urls: &[String],
+    tag_name: &str,
+    filter: impl Fn(&str) -> bool,
+    token: &CancellationToken,
+    progress_cb: impl Fn(f32),
+) -> Result<IntegrityReport> {
+    let directory = urls
+        .iter()
+        .find_map(|url| {
+            delta::read_directory(url)
+                .inspect_err(|err| warn!("Failed to read the file list from {url}: {err}"))
+                .ok()
+                .flatten()
+        })
+        .ok_or(UpdaterError::NotFound("Release file list unavailable"))?;
+    token.check()?;
+
+    let Directory { entries, .. } = directory;
+    let files: Vec<_> = entries
+        .into_iter()
+        .filter(|entry| !entry.name.ends_with('/'))
+        .filter_map(|entry| {
+            let name = delta::enclosed_name(&entry.name)?;
+            filter(&name.to_string_lossy()).then_some((name, entry))
+        })
+        .collect();
+
+    let target_directory = PathBuf::from(sdcard_root());
+    let mut buffer = vec![0; EXTRACT_CHUNK_SIZE];
+    let mut problems = vec![];
+    for (index, (name, entry)) in files.iter().enumerate() {
+        token.check()?;
+        let problem = match File::open(target_directory.join(name)) {
+            Err(_) => Some(Problem::Missing),
+            Ok(file) if !file.metadata().is_ok_and(|m| m.len() == entry.size) => {
+                Some(Problem::Modified)
+            }
+            Ok(mut file) => (!file_crc32(&mut file, &mut buffer)
+                .is_ok_and(|crc32| crc32 == entry.crc32))
+            .then_some(Problem::Corrupted),
+        };
+        if let Some(problem) = problem {
+            warn!("{}: {problem:?}", name.display());
+            problems.push((name.to_string_lossy().into_owned(), problem));
+        }
+        progress_cb((index + 1) as f32 / files.len() as f32);
+    }
+
+    info!(
+        "Checked {} files against {tag_name}, {} problems",
+        files.len(),
+        problems.len()
+    );
+    Ok(IntegrityReport {
+        tag_name: tag_name.to_string(),
+        checked: files.len(),
+        problems,
+    })
+}
@@ -0,0 +1,204 @@
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+use crate::{date, sdcard_root, UPDATER_DATA_DIR};
+
+// A header line describing the extraction, then the name of each archive
+// entry once it's been written. It's only left behind if the updater never
+// got to finish, e.g. because the device lost power.
+const JOURNAL_FILE: &str = "extract-journal";
+
+// The update being extracted
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Extraction {
+    pub tag_name: String,
+    pub asset_name: String,
+    pub full: bool,
+    pub from_version: Option<String>,
+    pub started_at: i64,
+}
+
+impl Extraction {
+    pub fn new(tag_name: &str, asset_name: &str, full: bool, from_version: Option<String>) -> Self {
+        Self {
+            tag_name: tag_name.to_string(),
+            asset_name: asset_name.to_string(),
+            full,
+            from_version,
+            started_at: date::now(),
+        }
+    }
+
+    fn same_update(&self, other: &Self) -> bool {
+        self.tag_name == other.tag_name && self.asset_name == other.asset_name
+    }
+}
+
+// Extraction that was cut short, and how many entries it got through
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InterruptedExtraction {
+    pub extraction: Extraction,
+    pub applied: usize,
+}
+
+pub struct Journal {
+    file: File,
+    path: PathBuf,
+    applied: HashSet<String>,
+}
+
+fn path() -> PathBuf {
+    PathBuf::from(sdcard_root())
+        .join(UPDATER_DATA_DIR)
+        .join(JOURNAL_FILE)
+}
+
+fn read(path: &Path) -> Option<(Extraction, HashSet<String>)> {
+    let file = File::open(path).ok()?;
+    let mut lines = BufReader::new(file).lines().map_while(io::Result::ok);
+    let header = lines.next()?;
+    match serde_json::from_str(&header) {
+        Ok(extraction) => Some((extraction, lines.collect())),
+        Err(err) => {
+            warn!("Ignoring invalid extraction journal: {err}");
+            None
+        }
+    }
+}
+
+impl Journal {
+    // Picks up where an interrupted extraction of the same update left off,
+    // otherwise starts over
+    pub fn open(extraction: &Extraction) -> Result<Self> {
+        Self::open_at(path(), extraction)
+    }
+
+    fn open_at(path: PathBuf, extraction: &Extraction) -> Result<Self> {
+        if let Some((previous, applied)) = read(&path) {
+            if previous.same_update(extraction) {
+                info!(
+                    "Resuming extraction of {}, {} entries already written",
+                    extraction.tag_name,
+                    applied.len()
+                );
+                let file = File::options().append(true).open(&path)?;
+                return Ok(Self {
+                    file,
+                    path,
+                    applied,
+                });
+            }
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = File::create(&path)?;
+        writeln!(file, "{}", serde_json::to_string(extraction)?)?;
+        file.sync_data()?;
+        Ok(Self {
+            file,
+            path,
+            applied: HashSet::new(),
+        })
+    }
+
+    pub fn applied(&self, name: &str) -> bool {
+        self.applied.contains(name)
+    }
+
+    // Called once the entry is safely on the SD card
+    pub fn record(&mut self, name: &str) -> io::Result<()> {
+        writeln!(self.file, "{name}")?;
+        self.file.sync_data()?;
+        self.applied.insert(name.to_string());
+        Ok(())
+    }
+
+    // Every entry was written, so there's nothing left to resume
+    pub fn finish(self) {
+        if let Err(err) = fs::remove_file(&self.path) {
+            warn!("Failed to remove extraction journal: {err}");
+        }
+    }
+}
+
+pub fn interrupted() -> Option<InterruptedExtraction> {
+    let (extraction, applied) = read(&path())?;
+    Some(InterruptedExtraction {
+        extraction,
+        applied: applied.len(),
+    })
+}
+
+// The interrupted extraction of `tag_name`, if installing it again would
+// resume one
+pub fn resuming(tag_name: &str) -> Option<Extraction> {
+    let (extraction, _) = read(&path())?;
+    (extraction.tag_name == tag_name).then_some(extraction)
+}
+
+pub fn discard() {
+    let _ = fs::remove_file(path());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn extraction(tag_name: &str) -> Extraction {
+        Extraction::new(
+            tag_name,
+            "NextUI-base.zip",
+            false,
+            Some("v6.0.0".to_string()),
+        )
+    }
+
+    #[test]
+    fn reopening_resumes_the_same_update() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(JOURNAL_FILE);
+        let mut journal = Journal::open_at(path.clone(), &extraction("v6.1.0")).unwrap();
+        journal.record("MinUI.zip").unwrap();
+        journal.record("trimui/app/launch.sh").unwrap();
+        drop(journal);
+
+        let journal = Journal::open_at(path.clone(), &extraction("v6.1.0")).unwrap();
+        assert!(journal.applied("MinUI.zip"));
+        assert!(journal.applied("trimui/app/launch.sh"));
+        assert!(!journal.applied(".system/tg5040/bin/minui.elf"));
+
+        let (header, applied) = read(&path).unwrap();
+        assert_eq!(header.from_version.as_deref(), Some("v6.0.0"));
+        assert_eq!(applied.len(), 2);
+    }
+
+    #[test]
+    fn another_update_starts_over() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(JOURNAL_FILE);
+        let mut journal = Journal::open_at(path.clone(), &extraction("v6.1.0")).unwrap();
+        journal.record("MinUI.zip").unwrap();
+        drop(journal);
+
+        let journal = Journal::open_at(path.clone(), &extraction("v6.2.0")).unwrap();
+        assert!(!journal.applied("MinUI.zip"));
+        assert_eq!(read(&path).unwrap().0.tag_name, "v6.2.0");
+    }
+
+    #[test]
+    fn finished_journals_are_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(JOURNAL_FILE);
+        let journal = Journal::open_at(path.clone(), &extraction("v6.1.0")).unwrap();
+        journal.finish();
+        assert!(read(&path).is_none());
+    }
+}
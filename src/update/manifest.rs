@@ -0,0 +1,214 @@
+use std::path::Path;
+
+use bytes::Bytes;
+use serde::Deserialize;
+
+use super::fetching::{self, api_get};
+use super::source::ReleaseSource;
+use crate::app_state::Transfer;
+use crate::cancel::CancellationToken;
+use crate::error::{Result, UpdaterError};
+use crate::github::{Asset, Commit, Release, Tag};
+
+// A static JSON file hosted anywhere, listing releases newest first:
+//
+// {"releases": [{"version": "v6.2.0", "commit": "1a2b3c4", "date": "...",
+//   "assets": [{"name": "...", "url": "https://...", "sha256": "..."}]}]}
+//
+// The repository in the source's config isn't used to fetch anything.
+pub struct JsonManifest {
+    pub url: String,
+}
+
+#[derive(Deserialize)]
+struct Manifest {
+    releases: Vec<ManifestRelease>,
+}
+
+#[derive(Deserialize)]
+struct ManifestRelease {
+    version: String,
+    // The commit NextUI reports in version.txt, to tell which release is installed
+    commit: Option<String>,
+    // RFC 3339
+    date: Option<String>,
+    notes: Option<String>,
+    #[serde(default)]
+    prerelease: bool,
+    assets: Vec<ManifestAsset>,
+}
+
+#[derive(Deserialize)]
+struct ManifestAsset {
+    name: String,
+    url: String,
+    #[serde(default)]
+    size: u64,
+    sha256: Option<String>,
+}
+
+impl From<ManifestRelease> for Release {
+    fn from(release: ManifestRelease) -> Self {
+        Release {
+            tag_name: release.version,
+            published_at: release.date,
+            body: release.notes,
+            assets: release
+                .assets
+                .into_iter()
+                .map(|asset| Asset {
+                    name: asset.name,
+                    url: asset.url,
+                    size: asset.size,
+                    download_count: 0,
+                    digest: asset.sha256.map(|sha256| format!("sha256:{sha256}")),
+                })
+                .collect(),
+            draft: false,
+            prerelease: release.prerelease,
+        }
+    }
+}
+
+impl ManifestRelease {
+    fn tag(&self) -> Option<Tag> {
+        Some(Tag {
+            name: self.version.clone(),
+            commit: Commit {
+                sha: self.commit.clone()?,
+            },
+        })
+    }
+}
+
+fn check_https(url: &str) -> Result<()> {
+    let https = url
+        .get(..8)
+        .is_some_and(|scheme| scheme.eq_ignore_ascii_case("https://"));
+    if https {
+        Ok(())
+    } else {
+        Err(UpdaterError::InsecureUrl(url.to_string()))
+    }
+}
+
+// The manifest and every asset it lists must come over HTTPS, since a
+// plain HTTP one could be swapped on the way
+fn check_urls(manifest_url: &str, releases: &[ManifestRelease]) -> Result<()> {
+    check_https(manifest_url)?;
+    releases
+        .iter()
+        .flat_map(|release| &release.assets)
+        .try_for_each(|asset| check_https(&asset.url))
+}
+
+impl JsonManifest {
+    fn fetch(&self) -> Result<Vec<ManifestRelease>> {
+        check_https(&self.url)?;
+        let releases = api_get::<Manifest>(&self.url)?.releases;
+        check_urls(&self.url, &releases)?;
+        Ok(releases)
+    }
+
+    fn fetch_one(&self, version: &str) -> Result<ManifestRelease> {
+        self.fetch()?
+            .into_iter()
+            .find(|release| release.version == version)
+            .ok_or(UpdaterError::NotFound("Version not in the manifest"))
+    }
+}
+
+// The whole manifest is fetched every time, it's one small file
+impl ReleaseSource for JsonManifest {
+    fn fetch_releases(&self, _repo: &str, _max_pages: usize) -> Result<Vec<Release>> {
+        Ok(self.fetch()?.into_iter().map(Release::from).collect())
+    }
+
+    fn fetch_release(&self, _repo: &str, tag_name: &str) -> Result<Release> {
+        Ok(self.fetch_one(tag_name)?.into())
+    }
+
+    fn fetch_tags(&self, _repo: &str, _max_pages: usize) -> Result<Vec<Tag>> {
+        Ok(self
+            .fetch()?
+            .iter()
+            .filter_map(ManifestRelease::tag)
+            .collect())
+    }
+
+    fn fetch_tag(&self, _repo: &str, tag_name: &str) -> Result<Tag> {
+        self.fetch_one(tag_name)?
+            .tag()
+            .ok_or(UpdaterError::NotFound("No commit in the manifest"))
+    }
+
+    fn download(
+        &self,
+        url: &str,
+        token: &CancellationToken,
+        progress_cb: &dyn Fn(Transfer),
+    ) -> Result<Bytes> {
+        fetching::download(url, token, progress_cb)
+    }
+
+    fn download_to_file(
+        &self,
+        url: &str,
+        path: &Path,
+        token: &CancellationToken,
+        progress_cb: &dyn Fn(Transfer),
+    ) -> Result<()> {
+        fetching::download_to_file(url, path, token, progress_cb)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manifest_releases_carry_their_commit_and_digest() {
+        let json = r#"{"releases": [{
+            "version": "v6.2.0",
+            "commit": "1a2b3c4",
+            "date": "2025-03-01T00:00:00Z",
+            "assets": [{
+                "name": "NextUI-20250301-base.zip",
+                "url": "https://builds.example.com/NextUI-20250301-base.zip",
+                "size": 1234,
+                "sha256": "ab12"
+            }]
+        }]}"#;
+
+        let manifest: Manifest = serde_json::from_str(json).unwrap();
+        let tag = manifest.releases[0].tag().unwrap();
+        let release = Release::from(manifest.releases.into_iter().next().unwrap());
+
+        assert_eq!(tag.name, "v6.2.0");
+        assert_eq!(tag.commit.sha, "1a2b3c4");
+        assert!(!release.prerelease);
+        assert_eq!(release.assets[0].digest.as_deref(), Some("sha256:ab12"));
+    }
+
+    #[test]
+    fn plain_http_urls_are_rejected() {
+        let json = r#"{"releases": [{
+            "version": "v6.2.0",
+            "assets": [{"name": "NextUI.zip", "url": "http://builds.example.com/NextUI.zip"}]
+        }]}"#;
+        let manifest: Manifest = serde_json::from_str(json).unwrap();
+
+        assert!(check_https("HTTPS://builds.example.com/manifest.json").is_ok());
+        assert!(matches!(
+            check_https("http://builds.example.com/manifest.json"),
+            Err(UpdaterError::InsecureUrl(_))
+        ));
+        assert!(matches!(
+            check_urls(
+                "https://builds.example.com/manifest.json",
+                &manifest.releases
+            ),
+            Err(UpdaterError::InsecureUrl(_))
+        ));
+    }
+}
@@ -0,0 +1,190 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, Cursor, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use bytes::Bytes;
+use log::info;
+
+use super::atomic::{self, AtomicFile};
+use crate::device;
+use crate::error::{Result, UpdaterError};
+use crate::{sdcard_root, UPDATER_DATA_DIR};
+
+// Updates are downloaded into memory and extracted from there, which needs
+// about as much free memory as the zip is big. When there isn't that much,
+// the zip goes to a file on the SD card instead and is read from there as
+// it's needed. A read error on a bad card is then an I/O error like any
+// other, where a memory mapping would have crashed the updater.
+
+// Left for everything else: decompressing, the UI, and the rest of the system
+const HEADROOM: u64 = 48 * 1024 * 1024;
+const SPILL_FILE: &str = "download.zip";
+
+// Whether something this big can be held in memory
+pub fn fits(size: u64) -> bool {
+    device::available_memory().is_none_or(|available| size.saturating_add(HEADROOM) <= available)
+}
+
+// Even a download read from the SD card needs some memory to be extracted
+pub fn check_headroom() -> Result<()> {
+    match device::available_memory() {
+        Some(available) if available < HEADROOM => Err(UpdaterError::LowMemory),
+        _ => Ok(()),
+    }
+}
+
+// Where a download too big for memory goes. It's removed once the download
+// is no longer needed, so it's only left behind if the updater stops.
+pub fn spill_path() -> PathBuf {
+    PathBuf::from(sdcard_root())
+        .join(UPDATER_DATA_DIR)
+        .join(SPILL_FILE)
+}
+
+// A downloaded zip, in memory or in a file on the SD card
+#[derive(Clone)]
+pub enum Archive {
+    Memory(Bytes),
+    File(Arc<ArchiveFile>),
+}
+
+pub struct ArchiveFile {
+    path: PathBuf,
+    len: u64,
+    // Removed once the last Archive referring to it is dropped
+    temporary: bool,
+}
+
+impl Drop for ArchiveFile {
+    fn drop(&mut self) {
+        if self.temporary {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+impl From<Bytes> for Archive {
+    fn from(bytes: Bytes) -> Self {
+        Self::Memory(bytes)
+    }
+}
+
+impl Archive {
+    // A file read as it's needed, removed when it's no longer needed
+    pub fn spilled(path: &Path) -> io::Result<Self> {
+        let len = fs::metadata(path)?.len();
+        Ok(Self::File(Arc::new(ArchiveFile {
+            path: path.to_path_buf(),
+            len,
+            temporary: true,
+        })))
+    }
+
+    pub fn size(&self) -> u64 {
+        match self {
+            Self::Memory(bytes) => bytes.len() as u64,
+            Self::File(file) => file.len,
+        }
+    }
+
+    pub fn reader(&self) -> io::Result<ArchiveReader> {
+        Ok(match self {
+            Self::Memory(bytes) => ArchiveReader::Memory(Cursor::new(bytes.clone())),
+            Self::File(file) => ArchiveReader::File(BufReader::new(File::open(&file.path)?)),
+        })
+    }
+
+    // Copy the archive to `path` atomically, without reading a file into
+    // memory
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        match self {
+            Self::Memory(bytes) => atomic::write(path, bytes),
+            Self::File(file) => {
+                let mut copy = AtomicFile::create(path)?;
+                io::copy(&mut File::open(&file.path)?, &mut copy)?;
+                copy.commit()
+            }
+        }
+    }
+
+    #[cfg(test)]
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut contents = vec![];
+        self.reader().unwrap().read_to_end(&mut contents).unwrap();
+        contents
+    }
+}
+
+pub enum ArchiveReader {
+    Memory(Cursor<Bytes>),
+    File(BufReader<File>),
+}
+
+impl Read for ArchiveReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::Memory(cursor) => cursor.read(buf),
+            Self::File(file) => file.read(buf),
+        }
+    }
+}
+
+impl Seek for ArchiveReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            Self::Memory(cursor) => cursor.seek(pos),
+            Self::File(file) => file.seek(pos),
+        }
+    }
+}
+
+// A file read into memory, or read from the SD card as it's needed if it
+// doesn't fit. Unlike a spilled download, it's left in place.
+pub fn read_file(path: &Path) -> io::Result<Archive> {
+    let len = fs::metadata(path)?.len();
+    if fits(len) {
+        Ok(Archive::Memory(fs::read(path)?.into()))
+    } else {
+        info!("Reading {} from the SD card as it's needed", path.display());
+        Ok(Archive::File(Arc::new(ArchiveFile {
+            path: path.to_path_buf(),
+            len,
+            temporary: false,
+        })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spilled_archives_are_read_from_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(SPILL_FILE);
+        fs::write(&path, b"archive").unwrap();
+        let archive = Archive::spilled(&path).unwrap();
+        assert_eq!(archive.size(), 7);
+
+        let mut reader = archive.reader().unwrap();
+        reader.seek(SeekFrom::Start(2)).unwrap();
+        let mut middle = [0; 2];
+        reader.read_exact(&mut middle).unwrap();
+        assert_eq!(&middle, b"ch");
+
+        drop(reader);
+        drop(archive);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn unreadable_archives_are_io_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(SPILL_FILE);
+        fs::write(&path, b"archive").unwrap();
+        let archive = Archive::spilled(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(archive.reader().is_err());
+    }
+}
@@ -1,206 +1,855 @@
 use crate::{
-    app_state::{AppStateManager, Progress},
-    Result, SDCARD_ROOT,
-    github::{ReleaseAndTag},
+    app_state::{AppStateManager, Progress, Submenu},
+    args,
+    cancel::CancellationToken,
+    config::Source,
+    date, device,
+    error::{Result, UpdaterError},
+    fault::{self, CrashPoint, Phase},
+    github::{Asset, Release, ReleaseAndTag, ReleaseDetails},
+    history::{self, HistoryEntry},
+    i18n::tr,
+    network::has_network,
+    notify::{self, Notification},
+    power, rollout, sdcard_overridden, sdcard_root, simulate, status, storage,
+    version::NextUIVersion,
 };
-use bytes::Bytes;
-use fetching::{download, fetch_latest_release, fetch_releases, fetch_tags};
+use atomic::AtomicFile;
+use fetching::{download, fetch_latest_release};
+use journal::{Extraction, Journal};
+use log::{debug, error, info, warn};
+use memory::Archive;
 use regex::Regex;
+use sha2::{Digest, Sha256};
 
 use std::{
     fs::File,
-    io::{Cursor, Read, Write},
-    path::PathBuf,
+    io::{Read, Write},
+    path::Path,
     process::exit,
+    sync::mpsc,
     thread,
 };
 
+pub mod atomic;
+mod cache;
+mod cleanup;
+mod delta;
 mod fetching;
+mod gitea;
+mod integrity;
+pub mod journal;
+mod manifest;
+mod memory;
+mod phase;
+mod prefetch;
+mod preview;
+pub mod rollback;
+mod signature;
+mod source;
+mod staging;
+mod verification;
 
+pub use cache::size as cache_size;
+pub use fetching::{configure_client, post_json, set_download_limit};
+pub use integrity::{IntegrityReport, Problem};
+pub use journal::InterruptedExtraction;
+pub use phase::UpdatePhase;
+pub use prefetch::{do_prefetch, Prefetch};
+pub use preview::FolderSummary;
+pub use source::{for_forge, ReleaseSource};
+pub use staging::StagedUpdate;
+pub use verification::{PendingInstall, BOOT_ARCHIVE};
+
+// Read every entry once so their CRC32s are checked, before anything is
+// written. A truncated or damaged download fails here instead of being
+// half-applied.
+fn verify_zip(archive: &Archive, progress_cb: impl Fn(f32)) -> Result<()> {
+    let mut archive = zip::ZipArchive::new(archive.reader()?).map_err(|err| {
+        warn!("Not a valid zip file: {err}");
+        UpdaterError::CorruptDownload
+    })?;
+    let archive_len = archive.len();
+
+    for file_number in 0..archive_len {
+        let mut entry = archive.by_index(file_number).map_err(|err| {
+            warn!("Unreadable zip entry {file_number}: {err}");
+            UpdaterError::CorruptDownload
+        })?;
+        if let Err(err) = std::io::copy(&mut entry, &mut std::io::sink()) {
+            warn!("Zip entry {} failed verification: {err}", entry.name());
+            return Err(UpdaterError::CorruptDownload);
+        }
+
+        progress_cb(file_number as f32 / archive_len as f32);
+    }
+
+    Ok(())
+}
+
+// Decompressed in pieces this big, reporting progress after each
+const EXTRACT_CHUNK_SIZE: usize = 64 * 1024;
+
+// Whether the file on the SD card already has an entry's contents, going by
+// the size and the CRC-32 the archive keeps for every entry. Reading it back is
+// a lot quicker than writing it again, and spares the SD card.
+fn unchanged(path: &Path, size: u64, crc32: u32, buffer: &mut [u8]) -> bool {
+    let Ok(mut file) = File::open(path) else {
+        return false;
+    };
+    file.metadata().is_ok_and(|metadata| metadata.len() == size)
+        && file_crc32(&mut file, buffer).is_ok_and(|file_crc32| file_crc32 == crc32)
+}
+
+fn file_crc32(file: &mut File, buffer: &mut [u8]) -> std::io::Result<u32> {
+    let mut hasher = crc32fast::Hasher::new();
+    loop {
+        match file.read(buffer)? {
+            0 => return Ok(hasher.finalize()),
+            bytes_read => hasher.update(&buffer[..bytes_read]),
+        }
+    }
+}
+
+// With a journal, each file written is recorded in it, and files it already
+// lists are left alone, so an interrupted extraction can be resumed. It stops
+// between files once `token` is cancelled, never halfway through one.
 fn extract_zip<T: Fn(&str) -> bool>(
-    bytes: Bytes,
+    archive: &Archive,
+    target_directory: &Path,
     filter: T,
+    mut journal: Option<&mut Journal>,
+    token: &CancellationToken,
     progress_cb: impl Fn(f32),
 ) -> Result<()> {
-    pub fn file_write_all_bytes(path: &PathBuf, bytes: &[u8]) -> Result<usize> {
-        let mut file = File::create(path)?;
-        file.set_len(0)?;
-        Ok(file.write(bytes)?)
-    }
-
     // Extract the update package
-    let mut archive = zip::ZipArchive::new(Cursor::new(bytes))?;
-    let target_directory = PathBuf::from(SDCARD_ROOT);
+    let mut archive = zip::ZipArchive::new(archive.reader()?)?;
     let archive_len = archive.len();
 
+    // Progress is weighted by size and updated while large entries are
+    // decompressed, so one huge MinUI.zip doesn't stall the bar
+    let total_size: u64 = (0..archive_len)
+        .filter_map(|file_number| {
+            let file = archive.by_index_raw(file_number).ok()?;
+            let name = file.mangled_name();
+            (file.is_file() && filter(name.as_os_str().to_string_lossy().as_ref()))
+                .then(|| file.size())
+        })
+        .sum();
+    let mut extracted_size: u64 = 0;
+    let mut buffer = vec![0; EXTRACT_CHUNK_SIZE];
+    let (mut skipped_files, mut skipped_size) = (0, 0);
+
     for file_number in 0..archive_len {
+        token.check()?;
+        if file_number == archive_len / 2 {
+            fault::inject(Phase::Extract)?;
+        }
         let mut next = archive.by_index(file_number)?;
 
         let sanitized_name = next.mangled_name();
+        let name = sanitized_name.to_string_lossy().into_owned();
 
-        if !filter(sanitized_name.as_os_str().to_string_lossy().as_ref()) {
-            println!("Skipping file: {sanitized_name:#?}");
+        if !filter(&name) {
+            debug!("Skipping file: {}", sanitized_name.display());
             continue;
         }
 
         if next.is_dir() {
             let extracted_folder_path = target_directory.join(sanitized_name);
             std::fs::create_dir_all(&extracted_folder_path)?;
-            println!("Created directory: {}", extracted_folder_path.display());
+            debug!("Created directory: {}", extracted_folder_path.display());
         } else if next.is_file() {
-            let mut buffer: Vec<u8> = Vec::new();
-            let _bytes_read = next.read_to_end(&mut buffer)?;
             let extracted_file_path = target_directory.join(sanitized_name);
-            file_write_all_bytes(&extracted_file_path, buffer.as_ref())?;
-            println!("Extracted file: {}", extracted_file_path.display());
+            let journaled = journal
+                .as_deref()
+                .is_some_and(|journal| journal.applied(&name));
+            if journaled || unchanged(&extracted_file_path, next.size(), next.crc32(), &mut buffer)
+            {
+                debug!("Unchanged file: {}", extracted_file_path.display());
+                skipped_files += 1;
+                skipped_size += next.size();
+                extracted_size += next.size();
+                if total_size > 0 {
+                    progress_cb(extracted_size as f32 / total_size as f32);
+                }
+                continue;
+            }
+            // The file being replaced stays intact until the new one is complete
+            let mut file = AtomicFile::create(&extracted_file_path)?;
+            loop {
+                let bytes_read = next.read(&mut buffer)?;
+                if bytes_read == 0 {
+                    break;
+                }
+                file.write_all(&buffer[..bytes_read])?;
+                fault::crash_point(CrashPoint::PartialFile);
+                extracted_size += bytes_read as u64;
+                if total_size > 0 {
+                    progress_cb(extracted_size as f32 / total_size as f32);
+                }
+            }
+            file.commit()?;
+            if let Some(journal) = journal.as_deref_mut() {
+                journal.record(&name)?;
+            }
+            debug!("Extracted file: {}", extracted_file_path.display());
+            fault::crash_point(CrashPoint::ExtractedFile);
         }
 
-        progress_cb(file_number as f32 / (archive_len - 1) as f32);
+        // Nothing to weigh by, e.g. an archive of empty files
+        if total_size == 0 {
+            progress_cb(file_number as f32 / (archive_len - 1) as f32);
+        }
     }
 
+    if skipped_files > 0 {
+        info!("Left {skipped_files} unchanged files ({skipped_size} bytes) alone");
+    }
     Ok(())
 }
 
+const SELF_UPDATE_REPO: &str = "LanderN/nextui-updater-pak";
+// Updater releases also contain "Updater.pak.zip" for pak stores, which has a
+// different layout and must not be extracted to the SD card root
+const SELF_UPDATE_ASSET_PREFIX: &str = "nextui-updater-pak";
+// Architecture and device names that may appear in updater asset names
+const BUILD_TARGETS: [&str; 7] = [
+    "aarch64", "arm64", "armhf", "arm", "x86_64", "tg5040", "tg3040",
+];
+
+// Pick the updater build for this device: one naming our architecture or
+// platform, otherwise one that doesn't name any
+fn self_update_asset(assets: &[Asset]) -> Option<&Asset> {
+    let platform = std::env::var("PLATFORM").unwrap_or_else(|_| "tg5040".to_string());
+    let ours = [std::env::consts::ARCH, platform.as_str()];
+
+    let candidates: Vec<&Asset> = assets
+        .iter()
+        .filter(|asset| {
+            asset.name.starts_with(SELF_UPDATE_ASSET_PREFIX)
+                && Path::new(&asset.name)
+                    .extension()
+                    .is_some_and(|extension| extension == "zip")
+        })
+        .collect();
+    let targets = |asset: &Asset| -> Vec<&str> {
+        let name = asset.name.to_ascii_lowercase();
+        BUILD_TARGETS
+            .into_iter()
+            .filter(|target| {
+                name.split(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                    .any(|part| part == *target)
+            })
+            .collect()
+    };
+
+    candidates
+        .iter()
+        .find(|asset| targets(asset).iter().any(|target| ours.contains(target)))
+        .or_else(|| candidates.iter().find(|asset| targets(asset).is_empty()))
+        .copied()
+}
+
 pub fn self_update(app_state: &AppStateManager) -> Result<()> {
     // Fetch latest release information
-    app_state.start_operation("Fetching latest updater release...");
+    app_state.advance(
+        UpdatePhase::CheckingSelf,
+        tr!("Fetching latest updater release..."),
+    )?;
+    let token = &app_state.operation_token();
 
-    println!("Fetching latest updater release...");
+    info!("Fetching latest updater release...");
 
-    let release = fetch_latest_release("LanderN/nextui-updater-pak")?;
+    let release = fetch_latest_release(SELF_UPDATE_REPO);
+    token.check()?;
+    let release = release?;
 
-    println!("Latest updater release: {release:?}");
+    debug!("Latest updater release: {release:?}");
 
     let available = semver::Version::parse(&release.tag_name)?;
     let installed = semver::Version::parse(env!("CARGO_PKG_VERSION"))?;
 
+    if rollback::failed_version().as_deref() == Some(release.tag_name.as_str()) {
+        info!("Skipping {available}, it failed to start before");
+        return Ok(());
+    }
+
     if available > installed {
-        println!("New version available: {available} (current: {installed})");
-        app_state.set_current_operation(Some("Downloading updater...".to_string()));
+        info!("New version available: {available} (current: {installed})");
+        app_state.advance(UpdatePhase::Downloading, tr!("Downloading updater..."))?;
     } else {
-        println!("No updates available");
+        info!("No updates available");
         return Ok(());
     }
 
-    let bytes = download(&release.assets[0].url, |pr| {
-        app_state.update_progress(pr);
-    })?;
+    let asset = self_update_asset(&release.assets)
+        .ok_or(UpdaterError::NotFound("No updater build for this device"))?;
+    info!("Updater asset: {}", asset.name);
 
-    app_state
-        .set_current_operation(format!("Extracting NextUI Updater {}...", release.tag_name).into());
-    app_state.set_progress(Some(Progress::Indeterminate));
+    let bytes = download(&asset.url, token, |transfer| {
+        app_state.update_transfer(transfer);
+    });
+    token.check()?;
+    let bytes = Archive::from(bytes?);
+
+    app_state.advance(
+        UpdatePhase::Verifying,
+        &tr!(
+            "Verifying NextUI Updater {version}...",
+            version = release.tag_name
+        ),
+    )?;
+    verify_zip(&bytes, |pr| app_state.update_progress(pr))?;
+
+    app_state.advance(
+        UpdatePhase::Extracting,
+        &tr!(
+            "Extracting NextUI Updater {version}...",
+            version = release.tag_name
+        ),
+    )?;
+
+    check_sd_card()?;
 
     // Move the current binary to a backup location
     let current_binary = std::env::current_exe()?;
     std::fs::rename(&current_binary, current_binary.with_extension("bak"))?;
+    atomic::sync_parent(&current_binary)?;
 
     // Extract the update package
     let result = extract_zip(
-        bytes,
+        &bytes,
+        Path::new(sdcard_root()),
         |_| true,
+        None,
+        token,
         |pr| {
             app_state.update_progress(pr);
         },
     );
 
-    println!("Extraction complete!");
+    info!("Extraction complete!");
     app_state.set_progress(Some(Progress::Indeterminate));
 
-    if result.is_err() {
+    if let Err(err) = result {
         // Move the backup back
-        std::fs::rename(current_binary.with_extension("bak"), current_binary)?;
+        std::fs::rename(current_binary.with_extension("bak"), &current_binary)?;
+        atomic::sync_parent(&current_binary)?;
 
-        return Err("Failed to extract update package".into());
+        return Err(err);
     }
 
-    app_state.set_current_operation(Some(
-        "Self-update success! Restarting updater...".to_string(),
-    ));
+    app_state.advance(
+        UpdatePhase::Finalizing,
+        tr!("Self-update success! Restarting updater..."),
+    )?;
+
+    // launch.sh puts the .bak binary back if the new one doesn't make it to the UI
+    rollback::mark_pending(&release.tag_name)?;
+
+    notify::send(
+        app_state,
+        &Notification::success(tr!(
+            "NextUI Updater {version} installed",
+            version = release.tag_name
+        )),
+    );
 
     // Give the user a moment to see the completion message
     thread::sleep(std::time::Duration::from_secs(1));
 
+    app_state.advance(UpdatePhase::AwaitingReboot, tr!("Restarting updater..."))?;
+
     // "5" is the exit code for "restart required"
     exit(5);
 }
 
+// Sort by parsed version, then publish date. Unparseable tags sort last.
+fn release_sort_key(release: &Release) -> (Option<NextUIVersion>, Option<&str>) {
+    (
+        NextUIVersion::parse(&release.tag_name),
+        release.published_at.as_deref(),
+    )
+}
+
+// Skipping it keeps whatever was fetched before
 pub fn do_nextui_release_check(app_state: &AppStateManager) {
-    // Fetch latest release information
-    app_state.start_operation("Fetching latest NextUI release...");
-    let repo = "LoveRetro/NextUI";
+    let token = app_state.begin_fetch();
+    release_check(app_state);
+    if !token.is_cancelled() {
+        app_state.end_fetch();
+    }
+}
+
+// Once the operation is cancelled, e.g. because the fetch was skipped,
+// whatever is fetched afterwards is dropped without touching the app state
+#[allow(clippy::too_many_lines)]
+fn release_check(app_state: &AppStateManager) {
+    let source = app_state.config().source();
+    let repo = source.repo.as_str();
+    let release_source = app_state.release_source();
 
     // Fetch latest releases information
-    app_state.start_operation("Fetching latest NextUI releases...");
-    let latest_releases = fetch_releases(repo);
-    if latest_releases.is_err() {
-        // Failed connection
-        let err = latest_releases.unwrap_err();
-        println!("Releases fetch failed: {:?}", err.source());
-        app_state.set_operation_failed(&format!("Releases fetch failed: {err}"));
+    if let Err(err) = app_state.advance(
+        UpdatePhase::CheckingRelease,
+        &tr!("Fetching latest {source} releases...", source = source.name),
+    ) {
+        error!("Release check not started: {err}");
         return;
     }
-    let latest_releases = latest_releases.unwrap();
+    let token = app_state.operation_token();
+    let max_pages = app_state.config().max_pages;
+    let latest_releases = release_source.fetch_releases(repo, max_pages);
+    if token.is_cancelled() {
+        return;
+    }
+    let mut latest_releases = match latest_releases {
+        Ok(releases) => releases,
+        Err(err) => {
+            // Failed connection
+            error!("Releases fetch failed: {err:?}");
+            app_state.set_operation_failed(&err.describe(tr!("Releases fetch failed")));
+            return;
+        }
+    };
     if latest_releases.is_empty() {
         // Connected, but no results
-        println!("Releases fetch returned 0 releases");
-        app_state.set_operation_failed("Releases fetch returned 0 releases");
+        error!("Releases fetch returned 0 releases");
+        app_state.set_operation_failed(tr!("Releases fetch returned 0 releases"));
         return;
     }
 
+    // GitHub doesn't guarantee the order, so sort newest first
+    latest_releases.sort_by(|a, b| release_sort_key(b).cmp(&release_sort_key(a)));
+
     // Fetch latest tag information
-    app_state.start_operation("Fetching latest NextUI tags...");
-    let latest_tags = fetch_tags(repo);
-    if latest_tags.is_err() {
-        // Failed connection
-        let err = latest_tags.unwrap_err();
-        println!("Tags fetch failed: {:?}", err.source());
-        app_state.set_operation_failed(&format!("Tags fetch failed: {err}"));
+    app_state.set_current_operation(Some(tr!(
+        "Fetching latest {source} tags...",
+        source = source.name
+    )));
+    let latest_tags = release_source.fetch_tags(repo, max_pages);
+    if token.is_cancelled() {
         return;
     }
-    let mut latest_tags = latest_tags.unwrap();
+    let mut latest_tags = match latest_tags {
+        Ok(tags) => tags,
+        Err(err) => {
+            // Failed connection
+            error!("Tags fetch failed: {err:?}");
+            app_state.set_operation_failed(&err.describe(tr!("Tags fetch failed")));
+            return;
+        }
+    };
     if latest_tags.is_empty() {
         // Connected, but no results
-        println!("Tags fetch returned 0 tags");
-        app_state.set_operation_failed("Tags fetch returned 0 tags");
+        error!("Tags fetch returned 0 tags");
+        app_state.set_operation_failed(tr!("Tags fetch returned 0 tags"));
         return;
     }
 
-    // Build ReleaseAndTag list for app state
-    let mut releases_and_tags: Vec<ReleaseAndTag> = vec![];
-    let mut check_latest_release = true;
-    let current_tag = app_state.current_version().unwrap_or("".to_string());
-    let mut current_tag_found = false;
-    for (_release_index, release) in latest_releases.iter().enumerate() {
-        if let Some(tag_index) = latest_tags.iter().position(|tag| tag.name == release.tag_name) {
-            releases_and_tags.push(ReleaseAndTag { release: (release.clone()), tag: (latest_tags[tag_index].clone()) });
-            if latest_tags[tag_index].commit.sha.starts_with(&current_tag) {
-                // set release selector starting index
-                app_state.set_nextui_releases_and_tags_index(Some(releases_and_tags.len()-1));
-                current_tag_found = true
-            }
-            latest_tags.remove(tag_index);
-            if check_latest_release {
-                check_latest_release = false;
-            }
-            continue;
-        }
-        if check_latest_release {
-            // Failed to find a match for the first release
-            println!("Latest release has no matching tag: {:?}", release.tag_name);
-            app_state.set_operation_failed(&format!("Latest release has no matching tag: {:?}", release.tag_name));
+    // Build ReleaseAndTag list for app state. Only `max_pages` pages of tags
+    // are fetched, so older releases might not have theirs yet; those are
+    // looked up on demand.
+    let mut releases_and_tags: Vec<ReleaseAndTag> = latest_releases
+        .into_iter()
+        .map(|release| {
+            let tag = latest_tags
+                .iter()
+                .position(|tag| tag.name == release.tag_name)
+                .map(|tag_index| latest_tags.swap_remove(tag_index));
+            ReleaseAndTag { release, tag }
+        })
+        .collect();
+
+    // Drafts are never offered, prereleases only if asked for. The installed
+    // version is kept either way so the selector can start on it.
+    let include_prereleases = app_state.config().channel.includes_prereleases();
+    let current_version = app_state.current_version();
+    releases_and_tags.retain(|release_and_tag| {
+        let release = &release_and_tag.release;
+        let installed = current_version
+            .as_ref()
+            .is_some_and(|current_version| release_and_tag.matches_commit(current_version));
+        installed || (!release.draft && (include_prereleases || !release.prerelease))
+    });
+
+    // Releases still being rolled out to other devices can only be picked manually
+    let skip_rollout = app_state.config().skip_rollout;
+    let offered = |release_and_tag: &ReleaseAndTag| {
+        rollout::is_offered(&release_and_tag.release, skip_rollout)
+    };
+
+    // The latest release needs its tag to compare with the installed version
+    if let Some(latest) = releases_and_tags
+        .iter_mut()
+        .find(|release_and_tag| offered(release_and_tag))
+        .filter(|release_and_tag| release_and_tag.tag.is_none())
+    {
+        let tag_name = latest.release.tag_name.clone();
+        let tag = release_source.fetch_tag(repo, &tag_name);
+        if token.is_cancelled() {
             return;
         }
+        match tag {
+            Ok(tag) => latest.tag = Some(tag),
+            Err(err) => warn!("Latest release tag lookup failed: {tag_name:?}: {err}"),
+        }
     }
 
+    // Releases without a tag are skipped, so the newest release with a tag is the latest
+    let Some(latest) = releases_and_tags
+        .iter()
+        .find(|release_and_tag| release_and_tag.tag.is_some() && offered(release_and_tag))
+        .cloned()
+    else {
+        error!("No release has a matching tag");
+        app_state.set_operation_failed(tr!("No release has a matching tag"));
+        return;
+    };
+
+    // Set release selector starting index to the installed version
+    let installed_index = app_state.current_version().and_then(|current_version| {
+        releases_and_tags
+            .iter()
+            .position(|release_and_tag| release_and_tag.matches_commit(&current_version))
+    });
+    app_state.set_nextui_releases_and_tags_index(Some(installed_index.unwrap_or(0)));
+
     // Save collected values to app state
-    app_state.set_nextui_release(Some(releases_and_tags[0].release.clone()));
-    app_state.set_nextui_tag(Some(releases_and_tags[0].tag.clone()));
+    app_state.set_nextui_release(Some(latest.release));
+    app_state.set_nextui_tag(latest.tag);
     app_state.set_nextui_releases_and_tags(Some(releases_and_tags));
-    if !current_tag_found {
-        app_state.set_nextui_releases_and_tags_index(Some(0 as usize));
+
+    app_state.finish_operation();
+    status::record_check(app_state);
+
+    // Offer to finish or go back if the last install was cut short or
+    // didn't boot cleanly
+    if app_state.submenu() == Submenu::NextUI {
+        if let Some(interrupted) = journal::interrupted() {
+            warn!(
+                "Install of {} was interrupted",
+                interrupted.extraction.tag_name
+            );
+            app_state.set_interrupted_install(Some(interrupted));
+            app_state.enter_submenu(Submenu::InterruptedInstall);
+        } else if let Some(failed) =
+            verification::check(|tag_name| installed_is(app_state, tag_name))
+        {
+            app_state.set_failed_install(Some(failed));
+            app_state.enter_submenu(Submenu::FailedInstall);
+        }
+    }
+}
+
+// Whether the running NextUI is the given release, if its tag is known
+fn installed_is(app_state: &AppStateManager, tag_name: &str) -> Option<bool> {
+    let current_version = app_state.current_version()?;
+    app_state
+        .nextui_releases_and_tags()?
+        .iter()
+        .find(|release_and_tag| {
+            release_and_tag.release.tag_name == tag_name && release_and_tag.tag.is_some()
+        })
+        .map(|release_and_tag| release_and_tag.matches_commit(&current_version))
+}
+
+// Look up a release's tag in the background. Leaving the screen discards the result.
+pub fn do_tag_lookup(app_state: &'static AppStateManager, tag_name: &str) {
+    if !app_state.begin_tag_lookup(tag_name) {
+        return;
     }
 
+    let repo = app_state.config().source().repo;
+    let release_source = app_state.release_source();
+    let token = app_state.screen_token();
+    let tag_name = tag_name.to_string();
+    thread::spawn(move || {
+        let tag = release_source
+            .fetch_tag(&repo, &tag_name)
+            .inspect_err(|err| warn!("Tag lookup failed: {tag_name:?}: {err}"))
+            .ok();
+        if token.is_cancelled() {
+            // The screen that wanted it is gone, allow looking it up again later
+            debug!("Tag lookup cancelled: {tag_name:?}");
+            app_state.finish_tag_lookup(&tag_name, None);
+            return;
+        }
+        app_state.finish_tag_lookup(&tag_name, tag.as_ref());
+    });
+}
+
+fn fetch_release_details(
+    release_source: &dyn ReleaseSource,
+    repo: &str,
+    tag_name: &str,
+    installed: Option<&str>,
+    token: &CancellationToken,
+) -> Result<ReleaseDetails> {
+    let release = release_source.fetch_release(repo, tag_name)?;
+    token.check()?;
+
+    let comparison = installed.and_then(|installed| {
+        release_source
+            .fetch_comparison(repo, installed, tag_name)
+            .inspect_err(|err| warn!("Comparison with {tag_name:?} failed: {err}"))
+            .ok()
+    });
+
+    Ok(ReleaseDetails {
+        release,
+        comparison,
+    })
+}
+
+// Fetch a release's notes, asset sizes and comparison with the installed
+// version in the background. Fetching another release cancels this one.
+pub fn do_release_details_fetch(app_state: &'static AppStateManager, tag_name: &str) {
+    let Some(token) = app_state.begin_details_fetch(tag_name) else {
+        return;
+    };
+
+    let repo = app_state.config().source().repo;
+    let release_source = app_state.release_source();
+    let installed = app_state.current_version();
+    let tag_name = tag_name.to_string();
+    thread::spawn(move || {
+        let details = fetch_release_details(
+            release_source.as_ref(),
+            &repo,
+            &tag_name,
+            installed.as_deref(),
+            &token,
+        );
+        if token.is_cancelled() {
+            debug!("Details fetch cancelled: {tag_name:?}");
+            return;
+        }
+        let details = details
+            .inspect_err(|err| warn!("Details fetch failed: {tag_name:?}: {err}"))
+            .ok();
+        app_state.finish_details_fetch(&tag_name, details);
+    });
+}
+
+// Check the installed files against the release they came from, for the
+// integrity screen. Leaving the screen stops it.
+pub fn do_integrity_check(app_state: &'static AppStateManager) {
+    app_state.set_integrity_report(None);
+    let token = app_state.screen_token();
+    thread::spawn(move || match check_integrity(app_state, &token) {
+        Ok(report) => app_state.set_integrity_report(Some(report)),
+        Err(UpdaterError::Cancelled) => {}
+        Err(err) => app_state.set_error(Some(err.describe(tr!("Installation check failed")))),
+    });
+}
+
+fn check_integrity(
+    app_state: &AppStateManager,
+    token: &CancellationToken,
+) -> Result<IntegrityReport> {
+    let release = app_state.installed_release().ok_or(UpdaterError::NotFound(
+        "Installed version isn't a known release",
+    ))?;
+    let tag_name = release.tag_name.clone();
+    let source = app_state.config().source();
+    let asset = release
+        .asset_for(&source.asset_pattern(true)?)
+        .ok_or(UpdaterError::NotFound("No full update asset found"))?;
+
+    app_state.advance(
+        UpdatePhase::Verifying,
+        &tr!("Checking files against {asset}...", asset = asset.name),
+    )?;
+    let result = integrity::check(
+        &asset_urls(&source, &tag_name, asset),
+        &tag_name,
+        // MinUI.zip is unpacked and removed on boot, and Roms folders are the
+        // user's to remove
+        |file| file != verification::BOOT_ARCHIVE && !file.starts_with("Roms/"),
+        token,
+        |pr| app_state.update_progress(pr),
+    );
     app_state.finish_operation();
+    result
+}
+
+// Remove leftovers for the storage screen, then add the folders up again
+pub fn do_cleanup(app_state: &'static AppStateManager) {
+    let token = app_state.screen_token();
+    thread::spawn(move || match cleanup::clean_up(&token) {
+        Ok(cleanup) if cleanup.removed == 0 => {
+            app_state.set_notice(Some(tr!("Nothing to clean up").to_string()));
+        }
+        Ok(cleanup) => {
+            app_state.set_notice(Some(tr!(
+                "Removed {removed} leftovers, freeing {size}",
+                removed = cleanup.removed,
+                size = storage::format_size(cleanup.reclaimed)
+            )));
+            storage::do_storage_scan(app_state);
+        }
+        Err(UpdaterError::Cancelled) => {}
+        Err(err) => app_state.set_error(Some(err.describe(tr!("Cleanup failed")))),
+    });
+}
+
+// Delete every cached download, e.g. to make room on the SD card
+pub fn do_clear_cache(app_state: &'static AppStateManager) {
+    thread::spawn(move || match cache::clear() {
+        Ok(cleared) if cleared.removed == 0 => {
+            app_state.set_notice(Some(tr!("The download cache is empty").to_string()));
+        }
+        Ok(cleared) => {
+            app_state.set_notice(Some(tr!(
+                "Removed {removed} cached downloads, freeing {size}",
+                removed = cleared.removed,
+                size = storage::format_size(cleared.reclaimed)
+            )));
+            storage::do_storage_scan(app_state);
+        }
+        Err(err) => app_state.set_error(Some(
+            UpdaterError::from(err).describe(tr!("Clearing the cache failed")),
+        )),
+    });
+}
+
+// Self-update and release check, or the offline screen if there's no network
+pub fn do_startup_checks(app_state: &AppStateManager) {
+    // A staged update doesn't need the network. On success this reboots.
+    if let Some((staged, bytes)) = staging::load() {
+        if app_state.limited_mode() {
+            warn!(
+                "Limited mode, not applying staged update {}",
+                staged.tag_name
+            );
+        } else {
+            apply_staged_update(app_state, &staged, &bytes);
+        }
+    }
+
+    if !has_network() {
+        warn!("No network connection");
+        app_state.reset_submenu(Submenu::Offline);
+        return;
+    }
+
+    app_state.reset_submenu(Submenu::NextUI);
+    if let Some(screen) = args::take_screen() {
+        screen.open(app_state);
+    }
+
+    // The user can give up on a slow network and continue offline
+    let token = app_state.begin_fetch();
+    if app_state.limited_mode() {
+        info!("Limited mode, skipping self-update");
+    } else if sdcard_overridden() {
+        info!("Not using the SD card, skipping self-update");
+    } else if !app_state.config().self_update || args::get().no_self_update {
+        info!("Self-update disabled, skipping");
+    } else {
+        do_self_update(app_state);
+    }
+    if !token.is_cancelled() {
+        release_check(app_state);
+    }
+    if token.is_cancelled() {
+        return;
+    }
+    app_state.end_fetch();
+    if !app_state.limited_mode() {
+        auto_update(app_state);
+    }
+}
+
+// Whether `source` publishes releases the updater can install, with both a
+// quick and a full update asset
+fn check_source(app_state: &AppStateManager, source: &Source) -> Result<()> {
+    let quick_asset = source.asset_pattern(false)?;
+    let full_asset = source.asset_pattern(true)?;
+    let releases = app_state
+        .release_source_for(source)
+        .fetch_releases(&source.repo, 1)?;
+    let compatible = releases
+        .iter()
+        .filter(|release| !release.draft)
+        .any(|release| {
+            let has = |asset_re: &Regex| {
+                release
+                    .assets
+                    .iter()
+                    .any(|asset| asset_re.is_match(&asset.name))
+            };
+            has(&quick_asset) && has(&full_asset)
+        });
+    if compatible {
+        Ok(())
+    } else {
+        Err(UpdaterError::NotFound("No NextUI releases found"))
+    }
+}
+
+// Track another repository, e.g. a fork, once it's known to publish releases
+// the updater can install. An empty name goes back to the official one. It's
+// checked as an operation of its own, so it's ignored while something else,
+// or another repository change, is running.
+pub fn do_repository_change(app_state: &'static AppStateManager, repo: String) {
+    if let Err(err) = app_state.advance(
+        UpdatePhase::CheckingRelease,
+        &tr!("Checking {repo}...", repo),
+    ) {
+        warn!("Repository change not started: {err}");
+        return;
+    }
+
+    thread::spawn(move || {
+        let mut config = app_state.config();
+        let result = if repo.is_empty() {
+            config.select_repo(&Source::default().repo);
+            Ok(())
+        } else if has_network() {
+            config.select_repo(&repo);
+            check_source(app_state, &config.source()).map_err(|err| {
+                warn!("Can't use {repo}: {err}");
+                err.describe(&tr!("Can't use {repo}", repo))
+            })
+        } else {
+            Err(tr!("No network connection").to_string())
+        };
+        let result = result.and_then(|()| {
+            config
+                .save()
+                .map_err(|err| tr!("Failed to save settings: {err}", err))
+        });
+        if let Err(message) = result {
+            app_state.set_operation_failed(&message);
+            return;
+        }
+
+        info!("Tracking {}", config.source().repo);
+        app_state.set_config(config);
+        app_state.finish_operation();
+        do_nextui_release_check(app_state);
+    });
+}
+
+// Self-update asked for from the settings, so say so when there's nothing new
+pub fn do_manual_self_update(app_state: &'static AppStateManager) {
+    thread::spawn(move || {
+        if !has_network() {
+            app_state.set_error(Some(tr!("No network connection").to_string()));
+            return;
+        }
+
+        do_self_update(app_state);
+        if app_state.update_phase() == UpdatePhase::Idle {
+            app_state.set_notice(Some(tr!(
+                "NextUI Updater {version} is up to date",
+                version = env!("CARGO_PKG_VERSION")
+            )));
+        }
+    });
 }
 
 pub fn do_self_update(app_state: &AppStateManager) {
@@ -210,34 +859,141 @@ pub fn do_self_update(app_state: &AppStateManager) {
         Ok(()) => {
             app_state.finish_operation();
         }
+        Err(_) if app_state.operation_token().is_cancelled() => {
+            info!("Self-update skipped");
+        }
         Err(err) => {
-            println!("Self-update failed: {:?}", err.source());
-            app_state.set_operation_failed(&format!("Self-update failed: {err}"));
+            error!("Self-update failed: {err:?}");
+            let message = err.describe(tr!("Self-update failed"));
+            app_state.set_operation_failed(&message);
+            notify::send(app_state, &Notification::failure(message));
         }
     }
 }
 
 pub fn do_update(app_state: &'static AppStateManager, full: bool) {
-    thread::spawn(move || {
-        if let Err(err) = update_nextui(app_state, full) {
-            println!("Update failed: {:?}", err.source());
+    // Before the confirmation screen is left, which would drop it
+    app_state.claim_prefetch();
+    spawn_update(app_state, move || update_nextui(app_state, full));
+}
 
-            app_state.set_operation_failed(&format!("Update failed: {err}"));
+// Quick update to the version that's already installed, e.g. to repair it
+pub fn do_reinstall(app_state: &'static AppStateManager) {
+    spawn_update(app_state, move || reinstall_nextui(app_state));
+}
+
+// Quick update to the pinned version, whatever the latest one is
+pub fn do_pinned_install(app_state: &'static AppStateManager) {
+    spawn_update(app_state, move || install_pinned(app_state));
+}
+
+fn spawn_update(
+    app_state: &'static AppStateManager,
+    update: impl FnOnce() -> Result<()> + Send + 'static,
+) {
+    thread::spawn(move || run_update(app_state, update));
+}
+
+fn run_update(app_state: &AppStateManager, update: impl FnOnce() -> Result<()>) {
+    if !has_network() {
+        warn!("No network connection");
+        app_state.reset_submenu(Submenu::Offline);
+        return;
+    }
+
+    match update() {
+        Ok(()) => {}
+        Err(UpdaterError::Cancelled) => {
+            info!("Update cancelled");
+            // Reported as failed if it was already extracting
+            app_state.finish_operation();
+        }
+        Err(err) => {
+            error!("Update failed: {err:?}");
+
+            let message = err.describe(tr!("Update failed"));
+            app_state.set_operation_failed(&message);
+            notify::send(app_state, &Notification::failure(message));
 
             // Try to fetch latest release information again
             do_nextui_release_check(app_state);
         }
-    });
+    }
+}
+
+// Automatic updates wait for a charger below this battery level
+const AUTO_UPDATE_MIN_BATTERY: u8 = 30;
+
+// Why the automatic update after startup can't go ahead, if it can't
+fn auto_update_blocked(app_state: &AppStateManager) -> Option<String> {
+    let release = app_state.nextui_release()?;
+    if release.prerelease {
+        return Some(tr!("{version} is a prerelease", version = release.tag_name));
+    }
+    if let Some(battery) = power::battery().filter(|battery| !battery.charging) {
+        if battery.percent < AUTO_UPDATE_MIN_BATTERY {
+            return Some(tr!("battery at {percent}%", percent = battery.percent));
+        }
+    }
+    // The download is held in memory, but unpacked files and NextUI's own
+    // unpacking of MinUI.zip need room
+    let Ok(asset) = pick_asset(app_state, &release, false) else {
+        return Some(tr!("no matching update asset").to_string());
+    };
+    let needed = asset.size * 2;
+    if device::disk_space().is_some_and(|space| space.free < needed) {
+        return Some(tr!("not enough space on the SD card").to_string());
+    }
+    None
+}
+
+// Automatic updates only ever go forward, e.g. a beta newer than the latest
+// stable release is left alone. Not when either version is unknown.
+fn auto_update_is_upgrade(app_state: &AppStateManager) -> bool {
+    let (Some(installed), Some(latest)) =
+        (app_state.installed_tag_name(), app_state.nextui_release())
+    else {
+        return false;
+    };
+    match (
+        NextUIVersion::parse(&installed),
+        NextUIVersion::parse(&latest.tag_name),
+    ) {
+        (Some(installed), Some(latest)) => latest > installed,
+        _ => false,
+    }
+}
+
+// Quick update to the latest stable release without asking, if the
+// automatic update setting is on
+fn auto_update(app_state: &AppStateManager) {
+    if !app_state.config().auto_update
+        || app_state.update_available() != Some(true)
+        || !auto_update_is_upgrade(app_state)
+    {
+        return;
+    }
+    if let Some(reason) = auto_update_blocked(app_state) {
+        info!("Not updating automatically: {reason}");
+        app_state.set_notice(Some(tr!("Not updating automatically: {reason}", reason)));
+        return;
+    }
+    info!("Updating automatically");
+    run_update(app_state, || update_nextui(app_state, false));
 }
 
 pub fn update_nextui(app_state: &AppStateManager, full: bool) -> Result<()> {
+    if let Some(limitation) = app_state.limitations().first() {
+        return Err(UpdaterError::Limited(*limitation));
+    }
+
     let mut release = {
-        app_state.start_operation("Downloading update...");
+        app_state.advance(UpdatePhase::CheckingRelease, tr!("Preparing update..."))?;
 
         app_state
             .nextui_release()
             .clone()
-            .ok_or("No release found")?
+            .ok_or(UpdaterError::NotFound("No release found"))?
     };
     if app_state.release_selection_menu() {
         let index = app_state.nextui_releases_and_tags_index().unwrap_or(0);
@@ -245,81 +1001,1121 @@ pub fn update_nextui(app_state: &AppStateManager, full: bool) -> Result<()> {
         release = relase_and_tag_vector[index].release.clone();
     }
 
-    let assets = release.assets;
-    let asset = assets
+    install_release(app_state, &release, full)
+}
+
+// Install a release picked outside the UI, e.g. by the install command
+pub fn install(app_state: &AppStateManager, release: &Release, full: bool) -> Result<()> {
+    if let Some(limitation) = app_state.limitations().first() {
+        return Err(UpdaterError::Limited(*limitation));
+    }
+
+    app_state.advance(UpdatePhase::CheckingRelease, tr!("Preparing update..."))?;
+    install_release(app_state, release, full)
+}
+
+fn reinstall_nextui(app_state: &AppStateManager) -> Result<()> {
+    if let Some(limitation) = app_state.limitations().first() {
+        return Err(UpdaterError::Limited(*limitation));
+    }
+
+    app_state.advance(UpdatePhase::CheckingRelease, tr!("Preparing reinstall..."))?;
+    let release = app_state.installed_release().ok_or(UpdaterError::NotFound(
+        "Installed version isn't a known release",
+    ))?;
+    install_release(app_state, &release, false)
+}
+
+// Install an update that was cut short again. Files it already wrote are
+// left alone.
+pub fn do_resume_install(app_state: &'static AppStateManager) {
+    spawn_update(app_state, move || resume_install(app_state));
+}
+
+fn resume_install(app_state: &AppStateManager) -> Result<()> {
+    if let Some(limitation) = app_state.limitations().first() {
+        return Err(UpdaterError::Limited(*limitation));
+    }
+
+    app_state.advance(UpdatePhase::CheckingRelease, tr!("Preparing to resume..."))?;
+    let interrupted =
+        journal::interrupted().ok_or(UpdaterError::NotFound("No install to resume"))?;
+    let release = app_state
+        .nextui_releases_and_tags()
+        .unwrap_or_default()
+        .into_iter()
+        .find(|release_and_tag| release_and_tag.release.tag_name == interrupted.extraction.tag_name)
+        .map(|release_and_tag| release_and_tag.release)
+        .ok_or(UpdaterError::NotFound(
+            "Interrupted version isn't a known release",
+        ))?;
+    install_release(app_state, &release, interrupted.extraction.full)
+}
+
+fn install_pinned(app_state: &AppStateManager) -> Result<()> {
+    if let Some(limitation) = app_state.limitations().first() {
+        return Err(UpdaterError::Limited(*limitation));
+    }
+
+    app_state.advance(UpdatePhase::CheckingRelease, tr!("Preparing update..."))?;
+    let pinned = app_state
+        .config()
+        .pinned_version
+        .ok_or(UpdaterError::NotFound("No version is pinned"))?;
+    let release = app_state
+        .nextui_releases_and_tags()
+        .unwrap_or_default()
+        .into_iter()
+        .find(|release_and_tag| release_and_tag.release.tag_name == pinned)
+        .map(|release_and_tag| release_and_tag.release)
+        .ok_or(UpdaterError::NotFound(
+            "Pinned version isn't a known release",
+        ))?;
+    install_release(app_state, &release, false)
+}
+
+// The asset a quick or full update of `release` downloads: the one picked
+// in the selector, or the first matching the source's pattern
+pub fn pick_asset<'a>(
+    app_state: &AppStateManager,
+    release: &'a Release,
+    full: bool,
+) -> Result<&'a Asset> {
+    let asset_re = app_state.config().source().asset_pattern(full)?;
+
+    let chosen = app_state.chosen_asset(&release.tag_name);
+    let assets = &release.assets;
+    assets
         .iter()
-        .find(|a| a.name.contains(if full { "all" } else { "base" }))
+        .find(|a| chosen.as_ref() == Some(&a.name))
+        .or_else(|| assets.iter().find(|a| asset_re.is_match(&a.name)))
         .or(assets.first())
-        .ok_or("No assets found")?;
+        .ok_or(UpdaterError::NotFound("No assets found"))
+}
+
+// A read-only or failing SD card would otherwise only show up as an I/O
+// error halfway through an update
+fn check_sd_card() -> Result<()> {
+    device::sd_write_test().map_err(|err| {
+        warn!("SD card write test failed: {err}");
+        UpdaterError::SdCard
+    })
+}
+
+// Download a release and install it, or save it to install later
+fn install_release(app_state: &AppStateManager, release: &Release, full: bool) -> Result<()> {
+    check_sd_card()?;
+    let source = app_state.config().source();
+    let asset = pick_asset(app_state, release, full)?;
+    let assets = &release.assets;
+
+    // Detached signature published next to the asset, if any
+    let signature_name = format!("{}.minisig", asset.name);
+    let signature = assets.iter().find(|a| a.name == signature_name);
+    // Resuming an interrupted install, the version it started from rather
+    // than the half-installed one
+    let from_version = match journal::resuming(&release.tag_name) {
+        Some(extraction) => extraction.from_version,
+        None => app_state
+            .installed_tag_name()
+            .or(app_state.current_version()),
+    };
+
+    if app_state.config().defer_updates {
+        let bytes = fetch_asset(app_state, &release.tag_name, asset, signature, &source)?;
+        app_state.advance(
+            UpdatePhase::Finalizing,
+            &tr!("Saving {asset} for later...", asset = asset.name),
+        )?;
+        staging::stage(
+            &bytes,
+            &StagedUpdate {
+                tag_name: release.tag_name.clone(),
+                asset_name: asset.name.clone(),
+                full,
+                from_version,
+                staged_at: date::now(),
+            },
+        )?;
+        app_state.advance(UpdatePhase::AwaitingReboot, tr!("Update staged"))?;
+        app_state.finish_operation();
+        notify::send(
+            app_state,
+            &Notification::success(tr!(
+                "{version} will be installed the next time the updater starts",
+                version = release.tag_name
+            )),
+        );
+        return Ok(());
+    }
+
+    // Download and extract, keeping a record of the attempt. A prefetched or
+    // cached asset is already here, so there's nothing to save with a delta
+    // update.
+    let prefetched = app_state.prefetching(&release.tag_name, &asset.name)
+        || (app_state.config().download_cache
+            && cache::contains(&source, &release.tag_name, asset));
+    let plan = if prefetched {
+        None
+    } else {
+        plan_delta(
+            app_state,
+            &release.tag_name,
+            asset,
+            signature,
+            &source,
+            full,
+        )
+    };
+    let extraction = Extraction::new(&release.tag_name, &asset.name, full, from_version.clone());
+    let result = match plan {
+        Some(plan) => apply_delta(app_state, &plan, &extraction),
+        None => {
+            fetch_asset(app_state, &release.tag_name, asset, signature, &source).and_then(|bytes| {
+                if full {
+                    confirm_extraction(
+                        app_state,
+                        preview::summarize(&bytes, full_update_filter(Path::new(sdcard_root())))?,
+                    )?;
+                }
+                apply_asset(app_state, &bytes, &extraction, &source)
+            })
+        }
+    };
+    // Cancelled before extracting, so nothing was written and there's
+    // nothing to record. Once extraction has started, the partial install is.
+    if matches!(result, Err(UpdaterError::Cancelled)) && !app_state.update_phase().is_writing() {
+        return result;
+    }
+    let entry = HistoryEntry::new(
+        from_version.clone(),
+        &release.tag_name,
+        &asset.name,
+        &result,
+    );
+    if let Err(err) = history::record(entry) {
+        warn!("Failed to record update history: {err}");
+    }
+    result?;
+
+    if let Err(err) = verification::mark(from_version, &release.tag_name) {
+        warn!("Failed to write install marker: {err}");
+    }
 
+    reboot_after_update(app_state, &release.tag_name)
+}
+
+fn reboot_after_update(app_state: &AppStateManager, tag_name: &str) -> Result<()> {
+    app_state.advance(
+        UpdatePhase::Finalizing,
+        tr!("Update complete, preparing to reboot..."),
+    )?;
+    notify::send(
+        app_state,
+        &Notification::success(tr!("NextUI {version} installed", version = tag_name)),
+    );
+
+    // Give the user a moment to see the completion message
+    thread::sleep(std::time::Duration::from_secs(2));
+
+    app_state.set_current_operation(Some(tr!("Rebooting system...").to_string()));
+
+    // Reboot the system, unless it's not its SD card that was updated
+    if simulate::active() {
+        app_state.advance(UpdatePhase::AwaitingReboot, tr!("Rebooting system..."))?;
+        simulate::reboot(app_state);
+        return Ok(());
+    } else if args::headless() {
+        // Left to whoever ran the command
+        info!("Not rebooting, a reboot is needed to finish the update");
+        return app_state.advance(UpdatePhase::AwaitingReboot, tr!("Reboot needed"));
+    } else if sdcard_overridden() {
+        info!("Not rebooting, {} isn't the SD card", sdcard_root());
+    } else {
+        std::process::Command::new("reboot").output()?;
+    }
+    app_state.advance(UpdatePhase::AwaitingReboot, tr!("Rebooting system..."))
+}
+
+// Install an update staged by an earlier run. It's discarded either way, so
+// a broken one doesn't keep failing at every start.
+fn apply_staged_update(app_state: &AppStateManager, staged: &StagedUpdate, bytes: &Archive) {
+    info!("Applying staged update {}", staged.tag_name);
+    let source = app_state.config().source();
+
+    let result = app_state
+        .advance(
+            UpdatePhase::Verifying,
+            &tr!("Verifying staged {asset}...", asset = staged.asset_name),
+        )
+        .and_then(|()| verify_zip(bytes, |pr| app_state.update_progress(pr)))
+        .and_then(|()| {
+            let extraction = Extraction::new(
+                &staged.tag_name,
+                &staged.asset_name,
+                staged.full,
+                staged.from_version.clone(),
+            );
+            apply_asset(app_state, bytes, &extraction, &source)
+        });
+    staging::discard();
+
+    let entry = HistoryEntry::new(
+        staged.from_version.clone(),
+        &staged.tag_name,
+        &staged.asset_name,
+        &result,
+    );
+    if let Err(err) = history::record(entry) {
+        warn!("Failed to record update history: {err}");
+    }
+    if result.is_ok() {
+        if let Err(err) = verification::mark(staged.from_version.clone(), &staged.tag_name) {
+            warn!("Failed to write install marker: {err}");
+        }
+    }
+
+    if let Err(err) = result.and_then(|()| reboot_after_update(app_state, &staged.tag_name)) {
+        error!("Staged update failed: {err:?}");
+        let message = err.describe(tr!("Staged update failed"));
+        app_state.set_operation_failed(&message);
+        notify::send(app_state, &Notification::failure(message));
+    }
+}
+
+// Where an asset can be downloaded from: the source's mirrors in order, then the forge
+fn asset_urls(source: &Source, tag_name: &str, asset: &Asset) -> Vec<String> {
+    source
+        .mirrors
+        .iter()
+        .map(|mirror| {
+            mirror
+                .replace("{repo}", &source.repo)
+                .replace("{tag}", tag_name)
+                .replace("{asset}", &asset.name)
+        })
+        .chain([asset.url.clone()])
+        .collect()
+}
+
+// Check the download against the digest published with it, if any
+fn check_digest(bytes: &Archive, asset: &Asset) -> Result<()> {
+    let Some(digest) = &asset.digest else {
+        return Ok(());
+    };
+    let Some(expected) = digest.strip_prefix("sha256:") else {
+        warn!("Unsupported digest for {}: {digest}", asset.name);
+        return Ok(());
+    };
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut bytes.reader()?, &mut hasher)?;
+    let actual = format!("{:x}", hasher.finalize());
+    if !actual.eq_ignore_ascii_case(expected) {
+        warn!("{} doesn't match its digest", asset.name);
+        return Err(UpdaterError::CorruptDownload);
+    }
+    debug!("{} matches its digest", asset.name);
+    Ok(())
+}
+
+fn check_signature(
+    release_source: &dyn ReleaseSource,
+    bytes: &Archive,
+    tag_name: &str,
+    signature: Option<&Asset>,
+    source: &Source,
+    required: bool,
+    token: &CancellationToken,
+) -> Result<()> {
+    match (signature, &source.public_key) {
+        (Some(signature), Some(public_key)) => {
+            let signature_bytes = release_source.download_any(
+                &asset_urls(source, tag_name, signature),
+                token,
+                &|_| {},
+            )?;
+            signature::verify(bytes.reader()?, &signature_bytes, public_key)
+        }
+        _ if required => Err(UpdaterError::MissingSignature),
+        (None, _) => {
+            warn!("No signature published, skipping signature check");
+            Ok(())
+        }
+        (Some(_), None) => {
+            warn!(
+                "No public key for {}, skipping signature check",
+                source.name
+            );
+            Ok(())
+        }
+    }
+}
+
+// Into memory if there's room for it, otherwise through a file on the SD card
+fn download_asset(
+    app_state: &AppStateManager,
+    release_source: &dyn ReleaseSource,
+    tag_name: &str,
+    asset: &Asset,
+    source: &Source,
+) -> Result<Archive> {
+    let urls = asset_urls(source, tag_name, asset);
+    let token = app_state.operation_token();
+    let progress_cb = |transfer| app_state.update_transfer(transfer);
+    if memory::fits(asset.size) {
+        return Ok(release_source
+            .download_any(&urls, &token, &progress_cb)?
+            .into());
+    }
+
+    memory::check_headroom()?;
+    info!(
+        "Not enough free memory for {}, downloading it to the SD card",
+        asset.name
+    );
+    let path = memory::spill_path();
+    if let Err(err) = release_source.download_any_to_file(&urls, &path, &token, &progress_cb) {
+        let _ = std::fs::remove_file(&path);
+        return Err(err);
+    }
+    Ok(Archive::spilled(&path)?)
+}
+
+// Download an asset and check it's intact, without changing anything on the SD card
+fn fetch_asset(
+    app_state: &AppStateManager,
+    tag_name: &str,
+    asset: &Asset,
+    signature: Option<&Asset>,
+    source: &Source,
+) -> Result<Archive> {
     // Download the asset
-    app_state.start_determinate_operation(&format!("Downloading {}...", asset.name));
-    println!("Downloading from {}", asset.url);
+    app_state.advance(
+        UpdatePhase::Downloading,
+        &tr!("Downloading {asset}...", asset = asset.name),
+    )?;
 
-    let bytes = download(&asset.url, |pr| app_state.update_progress(pr))?;
+    let release_source = app_state.release_source();
+    let use_cache = app_state.config().download_cache;
+    let cached = use_cache
+        .then(|| cache::load(source, tag_name, asset))
+        .flatten();
+    let was_cached = cached.is_some();
+    let prefetched = || prefetch::take(app_state, tag_name, &asset.name).map(Archive::from);
+    let bytes = match cached.or_else(prefetched) {
+        Some(bytes) => bytes,
+        None => download_asset(app_state, release_source.as_ref(), tag_name, asset, source)?,
+    };
 
-    app_state.set_current_operation(format!("Extracting {}...\nPlease wait...", asset.name).into());
-    app_state.set_progress(Some(Progress::Indeterminate));
+    app_state.advance(
+        UpdatePhase::Verifying,
+        &tr!("Verifying {asset}...", asset = asset.name),
+    )?;
+    let verified = check_digest(&bytes, asset)
+        .and_then(|()| {
+            check_signature(
+                release_source.as_ref(),
+                &bytes,
+                tag_name,
+                signature,
+                source,
+                app_state.config().require_signatures,
+                &app_state.operation_token(),
+            )
+        })
+        .and_then(|()| verify_zip(&bytes, |pr| app_state.update_progress(pr)));
+    // A cached asset that fails is removed, so retrying downloads it again
+    match verified {
+        Err(err) if was_cached => {
+            cache::remove(source, tag_name, asset);
+            return Err(err);
+        }
+        Err(err) => return Err(err),
+        Ok(()) if use_cache && !was_cached => cache::store(
+            source,
+            tag_name,
+            asset,
+            &bytes,
+            app_state.config().cache_limit * 1024 * 1024,
+        ),
+        Ok(()) => {}
+    }
 
-    // Extract the update package
-    if full {
-        let emu_tag_re = Regex::new(r"\((?<emu>\w+)\)").expect("Failed to compile regex");
-        // Full update, extract all files, except for Roms folders which already exist
-        extract_zip(
-            bytes,
-            |file| {
-                if file.starts_with("Roms/") {
-                    // Extract the emu tag from the folder name
-                    if let Some(captures) = emu_tag_re.captures(file) {
-                        if let Some(emu) = captures.name("emu").map(|c| c.as_str()) {
-                            // Check if the emu tag already exists in the roms folder
-                            if std::fs::read_dir(PathBuf::from(SDCARD_ROOT).join("Roms"))
-                                .map(|d| {
-                                    d.filter_map(std::result::Result::ok).any(|e| {
-                                        e.file_name()
-                                            .to_string_lossy()
-                                            .contains(format!("({emu})").as_str())
-                                    })
-                                })
-                                .unwrap_or(false)
-                            {
-                                println!("Roms folder for {emu} already exists, skipping");
-                                return false;
-                            }
-                        }
+    Ok(bytes)
+}
+
+// Names of the folders in `target_directory`'s Roms folder
+fn roms_folders(target_directory: &Path) -> Vec<String> {
+    std::fs::read_dir(target_directory.join("Roms"))
+        .map(|d| {
+            d.filter_map(std::result::Result::ok)
+                .map(|e| e.file_name().to_string_lossy().into_owned())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// Full update, extract all files, except for Roms folders which already
+// exist in `target_directory`
+fn full_update_filter(target_directory: &Path) -> impl Fn(&str) -> bool {
+    let emu_tag_re = Regex::new(r"\((?<emu>\w+)\)").expect("Failed to compile regex");
+    let target_directory = target_directory.to_path_buf();
+    move |file| {
+        if file.starts_with("Roms/") {
+            // Extract the emu tag from the folder name
+            if let Some(captures) = emu_tag_re.captures(file) {
+                if let Some(emu) = captures.name("emu").map(|c| c.as_str()) {
+                    // Check if the emu tag already exists in the roms folder
+                    let tag = format!("({emu})");
+                    if roms_folders(&target_directory)
+                        .iter()
+                        .any(|folder| folder.contains(&tag))
+                    {
+                        debug!("Roms folder for {emu} already exists, skipping");
+                        return false;
                     }
                 }
+            }
+        }
 
-                true
-            },
-            |pr| app_state.update_progress(pr),
-        )?;
+        true
+    }
+}
+
+// What a full update writes, going by the full update filter, for
+// confirming it before anything is downloaded
+pub fn full_update_categories(source: &Source) -> Vec<String> {
+    let emu_tag_re = Regex::new(r"\(\w+\)").expect("Failed to compile regex");
+    let kept_roms_folders = roms_folders(Path::new(sdcard_root()))
+        .iter()
+        .filter(|folder| emu_tag_re.is_match(folder))
+        .count();
+    vec![
+        tr!("NextUI system files in .system").to_string(),
+        tr!("Boot files: {paths}", paths = source.quick_paths.join(", ")),
+        tr!("Paks in Tools and Emus, replacing your copies").to_string(),
+        tr!("Emulator and pak settings the release ships").to_string(),
+        tr!("Bios folders").to_string(),
+        if kept_roms_folders > 0 {
+            tr!(
+                "Roms folders for new systems only, {count} existing ones are left alone",
+                count = kept_roms_folders
+            )
+        } else {
+            tr!("Roms folders").to_string()
+        },
+    ]
+}
+
+// Files extracted by a quick or full update to `target_directory`
+fn update_filter<'a>(
+    source: &'a Source,
+    full: bool,
+    target_directory: &Path,
+) -> Box<dyn Fn(&str) -> bool + 'a> {
+    if full {
+        Box::new(full_update_filter(target_directory))
     } else {
-        // "Quick" update, just extract MinUI.zip and trimui folder
-        extract_zip(
-            bytes,
-            |file| {
-                ["MinUI.zip", "trimui"]
-                    .iter()
-                    .any(|prefix| file.starts_with(prefix))
-            },
-            |pr| app_state.update_progress(pr),
-        )?;
+        // "Quick" update, just extract the source's quick paths (MinUI.zip and trimui folder)
+        Box::new(|file| {
+            source
+                .quick_paths
+                .iter()
+                .any(|prefix| file.starts_with(prefix.as_str()))
+        })
+    }
+}
+
+// Plan downloading only the files that changed, if that's allowed and worth
+// it. Signatures cover the whole asset, so checking one rules it out.
+fn plan_delta(
+    app_state: &AppStateManager,
+    tag_name: &str,
+    asset: &Asset,
+    signature: Option<&Asset>,
+    source: &Source,
+    full: bool,
+) -> Option<delta::Plan> {
+    let config = app_state.config();
+    // Signatures and digests are for the whole zip, so it has to be downloaded
+    let signed = signature.is_some() && source.public_key.is_some();
+    if !config.delta_updates || signed || config.require_signatures || asset.digest.is_some() {
+        return None;
+    }
+
+    app_state.set_current_operation(Some(tr!(
+        "Checking which files changed in {asset}...",
+        asset = asset.name
+    )));
+    let plan = delta::plan(
+        &asset_urls(source, tag_name, asset),
+        update_filter(source, full, Path::new(sdcard_root())),
+    )?;
+    if !plan.worth_it() {
+        info!("Most of {} changed, downloading all of it", asset.name);
+        return None;
+    }
+    // The changed files are held in memory, a full download doesn't have to be
+    if !memory::fits(plan.download_size()) {
+        info!(
+            "Not enough free memory for a delta update of {}",
+            asset.name
+        );
+        return None;
+    }
+    info!(
+        "Delta update: {} changed files, {} bytes",
+        plan.files(),
+        plan.download_size()
+    );
+    Some(plan)
+}
+
+fn apply_delta(
+    app_state: &AppStateManager,
+    plan: &delta::Plan,
+    extraction: &Extraction,
+) -> Result<()> {
+    let asset_name = &extraction.asset_name;
+    app_state.advance(
+        UpdatePhase::Downloading,
+        &tr!(
+            "Downloading {count} changed files from {asset}...",
+            count = plan.files(),
+            asset = asset_name
+        ),
+    )?;
+    let token = app_state.operation_token();
+    let parts = plan.download(&token, |transfer| app_state.update_transfer(transfer))?;
+
+    app_state.advance(
+        UpdatePhase::Verifying,
+        &tr!("Verifying {asset}...", asset = asset_name),
+    )?;
+    plan.check(&parts)?;
+    if extraction.full {
+        confirm_extraction(app_state, plan.folders().to_vec())?;
     }
 
-    println!("Extraction complete!");
+    app_state.advance(
+        UpdatePhase::Extracting,
+        &tr!("Extracting {asset}...\nPlease wait...", asset = asset_name),
+    )?;
+    // Files the plan finds out of date are written again anyway, so a
+    // resumed delta update only uses the journal to record its progress
+    let mut journal = Journal::open(extraction)?;
+    plan.extract(parts, &mut journal, &token, |pr| {
+        app_state.update_progress(pr);
+    })?;
+    journal.finish();
+
+    info!("Extraction complete!");
     app_state.set_progress(Some(Progress::Indeterminate));
+    Ok(())
+}
 
-    app_state.set_current_operation(Some("Update complete, preparing to reboot...".to_string()));
+// Show what a full update is about to write and wait for the user to go ahead
+fn confirm_extraction(app_state: &AppStateManager, folders: Vec<FolderSummary>) -> Result<()> {
+    // Asking for it on the command line is confirmation enough
+    if args::headless() {
+        return Ok(());
+    }
+    let (reply, response) = mpsc::channel();
+    app_state.set_extraction_preview(Some((folders, reply)));
+    let confirmed = response.recv().unwrap_or(false);
+    app_state.set_extraction_preview(None);
+    if confirmed {
+        Ok(())
+    } else {
+        Err(UpdaterError::Cancelled)
+    }
+}
 
-    // Give the user a moment to see the completion message
-    thread::sleep(std::time::Duration::from_secs(2));
+fn apply_asset(
+    app_state: &AppStateManager,
+    bytes: &Archive,
+    extraction: &Extraction,
+    source: &Source,
+) -> Result<()> {
+    app_state.advance(
+        UpdatePhase::Extracting,
+        &tr!(
+            "Extracting {asset}...\nPlease wait...",
+            asset = extraction.asset_name
+        ),
+    )?;
+
+    // Extract the update package, journaling it so the next start can tell
+    // if it was cut short
+    let target_directory = Path::new(sdcard_root());
+    let mut journal = Journal::open(extraction)?;
+    extract_zip(
+        bytes,
+        target_directory,
+        update_filter(source, extraction.full, target_directory),
+        Some(&mut journal),
+        &app_state.operation_token(),
+        |pr| {
+            app_state.update_progress(pr);
+        },
+    )?;
+    journal.finish();
+
+    info!("Extraction complete!");
+    app_state.set_progress(Some(Progress::Indeterminate));
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+    use std::sync::Arc;
+
+    use bytes::Bytes;
+    use zip::write::SimpleFileOptions;
+
+    use super::source::mock::{release, tag, MockSource};
+    use super::*;
+    use crate::config::Channel;
+    use crate::fault::testing::{child_dir, crash_in_child};
+
+    const DIRECTORIES: [&str; 7] = [
+        "trimui/",
+        "trimui/app/",
+        ".system/",
+        ".system/tg5040/",
+        ".system/tg5040/bin/",
+        "Tools/",
+        "Tools/tg5040/Clock.pak/",
+    ];
+    const FILES: [(&str, usize); 4] = [
+        ("MinUI.zip", 300_000),
+        ("trimui/app/launch.sh", 1_000),
+        (".system/tg5040/bin/minui.elf", 200_000),
+        ("Tools/tg5040/Clock.pak/launch.sh", 500),
+    ];
+
+    fn contents(index: usize, size: usize) -> Vec<u8> {
+        (0..size).map(|byte| (byte * 31 + index) as u8).collect()
+    }
+
+    // Roms folders as in a release, for systems the user might already have
+    // under another name
+    const ROMS_DIRECTORIES: [&str; 3] =
+        ["Roms/", "Roms/Game Boy (GB)/", "Roms/Super Nintendo (SFC)/"];
+    const ROMS_FILES: [(&str, usize); 2] = [
+        ("Roms/Game Boy (GB)/.keep", 10),
+        ("Roms/Super Nintendo (SFC)/.keep", 10),
+    ];
+
+    fn fixture(directories: &[&str], files: &[(&str, usize)]) -> Archive {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for directory in directories {
+            writer
+                .add_directory(*directory, SimpleFileOptions::default())
+                .unwrap();
+        }
+        for (index, (name, size)) in files.iter().enumerate() {
+            writer
+                .start_file(*name, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(&contents(index, *size)).unwrap();
+        }
+        Bytes::from(writer.finish().unwrap().into_inner()).into()
+    }
+
+    fn archive() -> Archive {
+        fixture(&DIRECTORIES, &FILES)
+    }
+
+    // The release fixture with Roms folders added
+    fn archive_with_roms() -> Archive {
+        fixture(
+            &[&DIRECTORIES[..], &ROMS_DIRECTORIES[..]].concat(),
+            &[&FILES[..], &ROMS_FILES[..]].concat(),
+        )
+    }
+
+    fn extract(dir: &Path) -> Result<()> {
+        extract_zip(
+            &archive(),
+            dir,
+            |_| true,
+            None,
+            &CancellationToken::new(),
+            |_| {},
+        )
+    }
+
+    fn all_extracted(dir: &Path) -> bool {
+        FILES.into_iter().enumerate().all(|(index, (name, size))| {
+            std::fs::read(dir.join(name)).is_ok_and(|file| file == contents(index, size))
+        })
+    }
+
+    // What the child does, or checks that extracting again after it crashed
+    // at `crash_at` puts every file in place
+    fn recovers_from(test: &str, crash_at: &str) {
+        if let Some(dir) = child_dir() {
+            extract(&dir).unwrap();
+            return;
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        assert!(crash_in_child(module_path!(), test, crash_at, dir.path()));
+        assert!(!all_extracted(dir.path()));
+
+        extract(dir.path()).unwrap();
+        assert!(all_extracted(dir.path()));
+    }
+
+    #[test]
+    fn extraction_writes_every_file() {
+        let dir = tempfile::tempdir().unwrap();
+        extract(dir.path()).unwrap();
+        assert!(all_extracted(dir.path()));
+    }
+
+    #[test]
+    fn cancelled_extraction_stops_between_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let token = CancellationToken::new();
+        token.cancel();
+        let result = extract_zip(&archive(), dir.path(), |_| true, None, &token, |_| {});
+        assert!(matches!(result, Err(UpdaterError::Cancelled)));
+        assert!(!all_extracted(dir.path()));
+    }
+
+    #[test]
+    fn power_loss_between_files_recovers() {
+        recovers_from("power_loss_between_files_recovers", "extracted-file:2");
+    }
+
+    #[test]
+    fn power_loss_within_a_file_recovers() {
+        recovers_from("power_loss_within_a_file_recovers", "partial-file:2");
+    }
 
-    app_state.set_current_operation(Some("Rebooting system...".to_string()));
+    #[test]
+    fn power_loss_within_a_file_keeps_the_old_file() {
+        if let Some(dir) = child_dir() {
+            extract(&dir).unwrap();
+            return;
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let minui = dir.path().join(FILES[0].0);
+        std::fs::write(&minui, b"old").unwrap();
+        assert!(crash_in_child(
+            module_path!(),
+            "power_loss_within_a_file_keeps_the_old_file",
+            "partial-file:2",
+            dir.path()
+        ));
+        assert_eq!(std::fs::read(minui).unwrap(), b"old");
+    }
+
+    fn extract_update(dir: &Path, full: bool) {
+        let source = Source::default();
+        extract_zip(
+            &archive_with_roms(),
+            dir,
+            update_filter(&source, full, dir),
+            None,
+            &CancellationToken::new(),
+            |_| {},
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn full_update_creates_missing_roms_folders() {
+        let dir = tempfile::tempdir().unwrap();
+        extract_update(dir.path(), true);
+
+        assert!(all_extracted(dir.path()));
+        for (name, _) in ROMS_FILES {
+            let folder = Path::new(name).parent().unwrap();
+            assert!(dir.path().join(folder).is_dir(), "{name} missing");
+        }
+    }
+
+    #[test]
+    fn full_update_skips_existing_roms_folders() {
+        let dir = tempfile::tempdir().unwrap();
+        let renamed = dir.path().join("Roms/My Game Boy games (GB)");
+        std::fs::create_dir_all(&renamed).unwrap();
+
+        extract_update(dir.path(), true);
+
+        assert!(all_extracted(dir.path()));
+        assert!(!dir.path().join("Roms/Game Boy (GB)").exists());
+        assert!(dir.path().join("Roms/Super Nintendo (SFC)").is_dir());
+        assert_eq!(std::fs::read_dir(renamed).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn quick_update_only_extracts_quick_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        extract_update(dir.path(), false);
+
+        for (name, _) in FILES.into_iter().chain(ROMS_FILES) {
+            let quick = name == "MinUI.zip" || name.starts_with("trimui/");
+            assert_eq!(dir.path().join(name).exists(), quick, "{name}");
+        }
+        assert!(!dir.path().join("Roms").exists());
+    }
+
+    #[test]
+    fn extraction_leaves_unchanged_files_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        extract(dir.path()).unwrap();
+
+        // One file as released, one corrupted without changing its size
+        let unchanged = dir.path().join(FILES[1].0);
+        let modified = std::time::SystemTime::UNIX_EPOCH;
+        File::options()
+            .write(true)
+            .open(&unchanged)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        let corrupted = dir.path().join(FILES[2].0);
+        std::fs::write(&corrupted, vec![0; FILES[2].1]).unwrap();
+
+        extract(dir.path()).unwrap();
+
+        assert!(all_extracted(dir.path()));
+        let metadata = std::fs::metadata(unchanged).unwrap();
+        assert_eq!(metadata.modified().unwrap(), modified);
+    }
+
+    fn with_source(source: &Arc<MockSource>) -> AppStateManager {
+        let app_state = AppStateManager::new();
+        app_state.set_release_source(source.clone());
+        // Tests don't touch the SD card
+        let mut config = app_state.config();
+        config.download_cache = false;
+        app_state.set_config(config);
+        app_state
+    }
+
+    #[test]
+    fn release_check_offers_the_newest_tagged_release() {
+        let mut prerelease = release("v6.3.0", "2025-04-01T00:00:00Z", &[]);
+        prerelease.prerelease = true;
+        let source = Arc::new(MockSource {
+            releases: vec![
+                release("v6.1.0", "2025-02-01T00:00:00Z", &[]),
+                prerelease,
+                release("v6.0.0", "2025-01-01T00:00:00Z", &[]),
+                release("v6.2.0", "2025-03-01T00:00:00Z", &[]),
+            ],
+            // v6.2.0 has no tag, so it can't be compared with what's installed
+            tags: vec![tag("v6.0.0", "aaaaaaaa"), tag("v6.1.0", "bbbbbbbb")],
+            ..MockSource::default()
+        });
+        let app_state = with_source(&source);
+        app_state.set_current_version(Some("aaaaaaa".to_string()));
+
+        do_nextui_release_check(&app_state);
+
+        assert_eq!(app_state.update_phase(), UpdatePhase::Idle);
+        assert_eq!(app_state.nextui_release().unwrap().tag_name, "v6.1.0");
+        let tag_names: Vec<_> = app_state
+            .nextui_releases_and_tags()
+            .unwrap()
+            .into_iter()
+            .map(|release_and_tag| release_and_tag.release.tag_name)
+            .collect();
+        assert_eq!(tag_names, ["v6.2.0", "v6.1.0", "v6.0.0"]);
+        // The selector starts on the installed version
+        assert_eq!(app_state.nextui_releases_and_tags_index(), Some(2));
+    }
+
+    #[test]
+    fn beta_channel_offers_prereleases() {
+        let mut prerelease = release("v6.1.0", "2025-02-01T00:00:00Z", &[]);
+        prerelease.prerelease = true;
+        let source = Arc::new(MockSource {
+            releases: vec![prerelease, release("v6.0.0", "2025-01-01T00:00:00Z", &[])],
+            tags: vec![tag("v6.0.0", "aaaaaaaa"), tag("v6.1.0", "bbbbbbbb")],
+            ..MockSource::default()
+        });
+        let app_state = with_source(&source);
+        let mut config = app_state.config();
+        config.channel = Channel::Beta;
+        app_state.set_config(config);
+
+        do_nextui_release_check(&app_state);
+
+        assert_eq!(app_state.nextui_release().unwrap().tag_name, "v6.1.0");
+    }
+
+    #[test]
+    fn auto_update_is_blocked_without_an_asset() {
+        let app_state = AppStateManager::new();
+        app_state.set_nextui_release(Some(release("v6.1.0", "2025-02-01T00:00:00Z", &[])));
+        assert_eq!(
+            auto_update_blocked(&app_state).as_deref(),
+            Some("no matching update asset")
+        );
+    }
+
+    #[test]
+    fn auto_update_never_downgrades_a_newer_version() {
+        // Installed by hand, but not rolled out to this device yet
+        let mut rolling_out = release("v6.2.0", "2025-03-01T00:00:00Z", &[]);
+        rolling_out.body = Some("Rollout: 0%".to_string());
+        let source = Arc::new(MockSource {
+            releases: vec![
+                rolling_out,
+                release("v6.1.0", "2025-02-01T00:00:00Z", &[]),
+                release("v6.0.0", "2025-01-01T00:00:00Z", &[]),
+            ],
+            tags: vec![
+                tag("v6.0.0", "aaaaaaaa"),
+                tag("v6.1.0", "bbbbbbbb"),
+                tag("v6.2.0", "cccccccc"),
+            ],
+            ..MockSource::default()
+        });
+        let check = |current_version: &str| {
+            let app_state = with_source(&source);
+            app_state.set_current_version(Some(current_version.to_string()));
+            do_nextui_release_check(&app_state);
+            assert_eq!(app_state.nextui_release().unwrap().tag_name, "v6.1.0");
+            assert_eq!(app_state.update_available(), Some(true));
+            auto_update_is_upgrade(&app_state)
+        };
+
+        assert!(check("aaaaaaa"));
+        assert!(!check("ccccccc"));
+        // A beta build that isn't among the releases at all
+        assert!(!check("ddddddd"));
+    }
+
+    #[test]
+    fn prefetched_assets_are_only_downloaded_once() {
+        let mut source = MockSource {
+            releases: vec![release(
+                "v1.0.0",
+                "2025-01-01T00:00:00Z",
+                &["NextUI-base.zip"],
+            )],
+            ..MockSource::default()
+        };
+        source.files.insert(
+            "https://api.example.com/v1.0.0/NextUI-base.zip".to_string(),
+            Bytes::from("zip"),
+        );
+        let source = Arc::new(source);
+        let app_state: &'static AppStateManager = Box::leak(Box::new(with_source(&source)));
+        let mut config = app_state.config();
+        config.prefetch = true;
+        app_state.set_config(config);
+
+        do_prefetch(app_state, &source.releases[0], false);
+        do_prefetch(app_state, &source.releases[0], false);
+        assert!(app_state.prefetching("v1.0.0", "NextUI-base.zip"));
+        let bytes = prefetch::take(app_state, "v1.0.0", "NextUI-base.zip");
+
+        assert_eq!(bytes.unwrap(), "zip");
+        assert_eq!(source.downloaded.lock().len(), 1);
+    }
+
+    #[test]
+    fn downloads_must_match_their_digest() {
+        let mut asset = release("v1.0.0", "2025-01-01T00:00:00Z", &["base.zip"]).assets[0].clone();
+        asset.digest = Some(
+            "sha256:2CF24DBA5FB0A30E26E83B2AC5B9E29E1B161E5C1FA7425E73043362938B9824".to_string(),
+        );
+
+        assert!(check_digest(&Bytes::from("hello").into(), &asset).is_ok());
+        assert!(matches!(
+            check_digest(&Bytes::from("hellO").into(), &asset),
+            Err(UpdaterError::CorruptDownload)
+        ));
+    }
+
+    #[test]
+    fn forks_need_quick_and_full_update_assets() {
+        let source = Source {
+            repo: "someone/NextUI".to_string(),
+            ..Source::default()
+        };
+        let app_state = with_source(&Arc::new(MockSource {
+            releases: vec![release(
+                "v1.0.0",
+                "2025-01-01T00:00:00Z",
+                &["NextUI-20250101-base.zip", "NextUI-20250101-all.zip"],
+            )],
+            ..MockSource::default()
+        }));
+        assert!(check_source(&app_state, &source).is_ok());
+
+        let app_state = with_source(&Arc::new(MockSource {
+            releases: vec![release(
+                "v1.0.0",
+                "2025-01-01T00:00:00Z",
+                &["NextUI-20250101-base.zip"],
+            )],
+            ..MockSource::default()
+        }));
+        assert!(check_source(&app_state, &source).is_err());
+    }
+
+    #[test]
+    fn release_check_fails_without_releases() {
+        let app_state = with_source(&Arc::new(MockSource {
+            offline: true,
+            ..MockSource::default()
+        }));
+        do_nextui_release_check(&app_state);
+        assert!(matches!(
+            app_state.update_phase(),
+            UpdatePhase::Failed { .. }
+        ));
+        assert!(app_state.nextui_release().is_none());
+
+        let app_state = with_source(&Arc::new(MockSource::default()));
+        do_nextui_release_check(&app_state);
+        assert_eq!(
+            app_state.update_phase(),
+            UpdatePhase::Failed {
+                error: "Releases fetch returned 0 releases".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn assets_download_from_mirrors_first() {
+        let release = release("v6.2.0", "2025-03-01T00:00:00Z", &["NextUI.zip"]);
+        let asset = &release.assets[0];
+        // Built once, zips record when they were written
+        let archive = archive();
+        let source = Arc::new(MockSource {
+            files: [(asset.url.clone(), archive.to_vec().into())].into(),
+            ..MockSource::default()
+        });
+        let app_state = with_source(&source);
+        let mut config_source = app_state.config().source();
+        config_source.mirrors = vec!["https://mirror.example.com/{tag}/{asset}".to_string()];
+
+        app_state
+            .advance(UpdatePhase::CheckingRelease, "Fetching...")
+            .unwrap();
+        let bytes = fetch_asset(&app_state, &release.tag_name, asset, None, &config_source);
 
-    // Reboot the system
-    match std::process::Command::new("reboot").output() {
-        Ok(_) => Ok(()),
-        Err(e) => Err(Box::new(e)),
+        assert_eq!(bytes.unwrap().to_vec(), archive.to_vec());
+        assert_eq!(
+            *source.downloaded.lock(),
+            [
+                "https://mirror.example.com/v6.2.0/NextUI.zip".to_string(),
+                asset.url.clone()
+            ]
+        );
+        assert_eq!(app_state.update_phase(), UpdatePhase::Verifying);
     }
 }
@@ -0,0 +1,133 @@
+use crate::i18n::tr;
+
+// Where the update pipeline is. Every self-update, release check and update
+// moves through these in order, and only the transitions below are allowed.
+// The operation and progress shown in the UI only exist while a phase is
+// active, and a failure's error is part of the phase, so the UI can't show an
+// error next to a running spinner.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UpdatePhase {
+    Idle,
+    CheckingSelf,
+    CheckingRelease,
+    Downloading,
+    Verifying,
+    Extracting,
+    Finalizing,
+    // Installed, and waiting for the updater or the device to restart
+    AwaitingReboot,
+    Failed { error: String },
+}
+
+impl UpdatePhase {
+    // Something is running, so the UI shouldn't start anything else
+    pub fn is_active(&self) -> bool {
+        matches!(
+            self,
+            Self::CheckingSelf
+                | Self::CheckingRelease
+                | Self::Downloading
+                | Self::Verifying
+                | Self::Extracting
+                | Self::Finalizing
+                | Self::AwaitingReboot
+        )
+    }
+
+    // Files are being written to the SD card, so stopping now leaves a
+    // partial install behind
+    pub fn is_writing(&self) -> bool {
+        matches!(self, Self::Extracting | Self::Finalizing)
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        match self {
+            Self::Failed { error } => Some(error),
+            _ => None,
+        }
+    }
+
+    // What's keeping other updates from starting, for hints on disabled buttons
+    pub fn busy_reason(&self) -> Option<&'static str> {
+        match self {
+            Self::CheckingSelf => Some(tr!("checking for updater updates")),
+            Self::CheckingRelease => Some(tr!("checking for updates")),
+            Self::Downloading => Some(tr!("a download is running")),
+            Self::Verifying => Some(tr!("a download is being verified")),
+            Self::Extracting => Some(tr!("an update is being installed")),
+            Self::Finalizing | Self::AwaitingReboot => Some(tr!("an update is finishing")),
+            Self::Idle | Self::Failed { .. } => None,
+        }
+    }
+
+    pub fn can_transition_to(&self, next: &Self) -> bool {
+        use UpdatePhase::{
+            AwaitingReboot, CheckingRelease, CheckingSelf, Downloading, Extracting, Failed,
+            Finalizing, Idle, Verifying,
+        };
+
+        matches!(
+            (self, next),
+            (Idle | AwaitingReboot | Failed { .. }, CheckingSelf | CheckingRelease)
+                | (CheckingSelf | CheckingRelease, Downloading)
+                // Straight from Idle when applying a staged update at startup
+                | (Idle | Downloading, Verifying)
+                // Straight to Finalizing when staging an update instead of applying it
+                | (Verifying, Extracting | Finalizing)
+                | (Extracting, Finalizing)
+                | (Finalizing, AwaitingReboot)
+                | (
+                    CheckingSelf
+                        | CheckingRelease
+                        | Downloading
+                        | Verifying
+                        | Extracting
+                        | Finalizing,
+                    Failed { .. }
+                )
+                // Downloading and Verifying when cancelled before anything was written
+                | (
+                    CheckingSelf
+                        | CheckingRelease
+                        | Downloading
+                        | Verifying
+                        | AwaitingReboot
+                        | Failed { .. },
+                    Idle
+                )
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failures_only_come_from_running_phases() {
+        let failed = UpdatePhase::Failed {
+            error: "Download failed".to_string(),
+        };
+        assert!(!UpdatePhase::Idle.can_transition_to(&failed));
+        assert!(UpdatePhase::Downloading.can_transition_to(&failed));
+        assert!(!failed.is_active());
+        assert_eq!(failed.error(), Some("Download failed"));
+    }
+
+    #[test]
+    fn installs_go_through_every_phase() {
+        let phases = [
+            UpdatePhase::Idle,
+            UpdatePhase::CheckingRelease,
+            UpdatePhase::Downloading,
+            UpdatePhase::Verifying,
+            UpdatePhase::Extracting,
+            UpdatePhase::Finalizing,
+            UpdatePhase::AwaitingReboot,
+        ];
+        for pair in phases.windows(2) {
+            assert!(pair[0].can_transition_to(&pair[1]), "{pair:?}");
+        }
+        assert!(!UpdatePhase::Idle.can_transition_to(&UpdatePhase::Extracting));
+    }
+}
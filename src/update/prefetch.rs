@@ -0,0 +1,124 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use bytes::Bytes;
+use log::{debug, info, warn};
+
+use super::{asset_urls, cache, memory, pick_asset};
+use crate::app_state::AppStateManager;
+use crate::cancel::CancellationToken;
+use crate::error::{Result, UpdaterError};
+use crate::github::Release;
+
+// How often a finished prefetch checks whether it's still wanted
+const HOLD_INTERVAL: Duration = Duration::from_millis(100);
+
+// An asset downloading in the background while the user decides whether to
+// install it. It's dropped when the screen it was started from is left,
+// unless an update claimed it first.
+pub struct Prefetch {
+    tag_name: String,
+    asset_name: String,
+    // Stops the download, e.g. when another asset is prefetched instead
+    token: CancellationToken,
+    // Set once an update is waiting on it, which then gets its progress
+    claimed: Arc<AtomicBool>,
+    handle: JoinHandle<Result<Bytes>>,
+}
+
+impl Prefetch {
+    pub fn is_for(&self, tag_name: &str, asset_name: &str) -> bool {
+        self.tag_name == tag_name && self.asset_name == asset_name && !self.token.is_cancelled()
+    }
+
+    pub fn claim(&self) {
+        self.claimed.store(true, Ordering::Relaxed);
+    }
+
+    pub fn cancel(&self) {
+        self.token.cancel();
+    }
+}
+
+// Start downloading what an update to `release` would, unless it's already
+// being downloaded
+pub fn do_prefetch(app_state: &'static AppStateManager, release: &Release, full: bool) {
+    if !app_state.config().prefetch || !app_state.limitations().is_empty() {
+        return;
+    }
+    let Ok(asset) = pick_asset(app_state, release, full) else {
+        return;
+    };
+    let source = app_state.config().source();
+    // Held in memory until it's installed, so it has to fit
+    if app_state.prefetching(&release.tag_name, &asset.name)
+        || !memory::fits(asset.size)
+        || (app_state.config().download_cache && cache::contains(&source, &release.tag_name, asset))
+    {
+        return;
+    }
+
+    info!("Prefetching {}", asset.name);
+    let urls = asset_urls(&source, &release.tag_name, asset);
+    let release_source = app_state.release_source();
+    let keep_while = app_state.screen_token();
+    let token = CancellationToken::new();
+    let claimed = Arc::new(AtomicBool::new(false));
+    let handle = thread::spawn({
+        let token = token.clone();
+        let claimed = Arc::clone(&claimed);
+        let asset_name = asset.name.clone();
+        move || {
+            let bytes = release_source.download_any(&urls, &token, &|transfer| {
+                if claimed.load(Ordering::Relaxed) {
+                    app_state.update_transfer(transfer);
+                } else if keep_while.is_cancelled() {
+                    token.cancel();
+                }
+            });
+            // Kept until an update claims it, or nothing will
+            while bytes.is_ok() && !claimed.load(Ordering::Relaxed) {
+                if keep_while.is_cancelled() || token.is_cancelled() {
+                    debug!("Dropping prefetched {asset_name}");
+                    return Err(UpdaterError::Cancelled);
+                }
+                thread::sleep(HOLD_INTERVAL);
+            }
+            bytes
+        }
+    });
+
+    app_state.set_prefetch(Prefetch {
+        tag_name: release.tag_name.clone(),
+        asset_name: asset.name.clone(),
+        token,
+        claimed,
+        handle,
+    });
+}
+
+// The asset, if it was prefetched, after waiting for the download to finish.
+// Any other prefetch is cancelled.
+pub fn take(app_state: &AppStateManager, tag_name: &str, asset_name: &str) -> Option<Bytes> {
+    let prefetch = app_state.take_prefetch()?;
+    if !prefetch.is_for(tag_name, asset_name) {
+        prefetch.cancel();
+        return None;
+    }
+
+    prefetch.claim();
+    info!("Using prefetched {asset_name}");
+    match prefetch.handle.join() {
+        Ok(Ok(bytes)) => Some(bytes),
+        Ok(Err(err)) => {
+            warn!("Prefetching {asset_name} failed: {err}");
+            None
+        }
+        Err(_) => {
+            warn!("Prefetching {asset_name} panicked");
+            None
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use std::collections::BTreeMap;
+use std::path::{Component, PathBuf};
+
+use super::memory::Archive;
+use crate::error::Result;
+use crate::sdcard_root;
+
+// Shown for files at the top of the archive
+const ROOT_FOLDER: &str = "SD card root";
+
+// What extracting an archive would do to one top-level folder
+#[derive(Clone, Debug, Default)]
+pub struct FolderSummary {
+    pub name: String,
+    // Files that would be written
+    pub files: usize,
+    // Of those, files already on the SD card that would be replaced
+    pub existing: usize,
+    // Files the filter leaves out
+    pub skipped: usize,
+}
+
+// Summarize an archive per top-level folder from its central directory,
+// without decompressing anything
+pub fn summarize(archive: &Archive, filter: impl Fn(&str) -> bool) -> Result<Vec<FolderSummary>> {
+    let mut archive = zip::ZipArchive::new(archive.reader()?)?;
+    let mut names = vec![];
+    for file_number in 0..archive.len() {
+        let file = archive.by_index_raw(file_number)?;
+        if !file.is_dir() {
+            names.push(file.mangled_name());
+        }
+    }
+    Ok(summarize_files(names, filter))
+}
+
+// Same from the sanitized names of the files in an archive
+pub fn summarize_files(
+    names: impl IntoIterator<Item = PathBuf>,
+    filter: impl Fn(&str) -> bool,
+) -> Vec<FolderSummary> {
+    let target_directory = PathBuf::from(sdcard_root());
+    let mut folders: BTreeMap<String, FolderSummary> = BTreeMap::new();
+
+    for sanitized_name in names {
+        let mut components = sanitized_name.components();
+        let top_level = match (components.next(), components.next()) {
+            (Some(Component::Normal(folder)), Some(_)) => folder.to_string_lossy().into_owned(),
+            _ => ROOT_FOLDER.to_string(),
+        };
+        let folder = folders
+            .entry(top_level.clone())
+            .or_insert_with(|| FolderSummary {
+                name: top_level,
+                ..FolderSummary::default()
+            });
+
+        if filter(sanitized_name.as_os_str().to_string_lossy().as_ref()) {
+            folder.files += 1;
+            if target_directory.join(&sanitized_name).exists() {
+                folder.existing += 1;
+            }
+        } else {
+            folder.skipped += 1;
+        }
+    }
+
+    folders.into_values().collect()
+}
@@ -0,0 +1,62 @@
+use std::fs;
+use std::path::PathBuf;
+
+use log::{info, warn};
+
+use crate::error::Result;
+
+// Flags shared with launch.sh, kept next to the binary. After a self-update
+// the script creates STARTED before running the new binary, which removes
+// both flags once it reaches the UI. If it exits with STARTED still there,
+// the script restores the .bak binary and records the version in FAILED.
+const PENDING: &str = "self-update-pending";
+const STARTED: &str = "self-update-started";
+const FAILED: &str = "self-update-failed";
+
+fn flag_path(name: &str) -> Option<PathBuf> {
+    Some(std::env::current_exe().ok()?.parent()?.join(name))
+}
+
+// Where the previous binary is kept during and after a self-update
+pub fn backup_path() -> Option<PathBuf> {
+    Some(std::env::current_exe().ok()?.with_extension("bak"))
+}
+
+// A self-update hasn't been confirmed yet, so the backup is still needed
+pub fn pending() -> bool {
+    [PENDING, STARTED]
+        .into_iter()
+        .filter_map(flag_path)
+        .any(|path| path.exists())
+}
+
+// The new binary is in place, and needs to prove it can start
+pub fn mark_pending(version: &str) -> Result<()> {
+    if let Some(path) = flag_path(PENDING) {
+        fs::write(path, version)?;
+    }
+    Ok(())
+}
+
+// Called once the UI is up, so the new binary is kept
+pub fn confirm() {
+    let Some(pending) = flag_path(PENDING) else {
+        return;
+    };
+    if let Ok(version) = fs::read_to_string(&pending) {
+        info!("Self-update to {} confirmed", version.trim());
+        let _ = fs::remove_file(pending);
+    }
+    if let Some(started) = flag_path(STARTED) {
+        let _ = fs::remove_file(started);
+    }
+}
+
+// A version that failed to start after a self-update and was rolled back,
+// so it isn't installed again
+pub fn failed_version() -> Option<String> {
+    let version = fs::read_to_string(flag_path(FAILED)?).ok()?;
+    let version = version.trim().to_string();
+    warn!("Self-update to {version} was rolled back");
+    Some(version)
+}
@@ -0,0 +1,61 @@
+use std::io::Read;
+
+use log::{info, warn};
+use minisign_verify::{PublicKey, Signature};
+
+use crate::error::{Result, UpdaterError};
+
+// Check a detached minisign signature for a download, reading it in chunks
+// so one too big for memory doesn't have to be held there
+pub fn verify(mut data: impl Read, signature: &[u8], public_key: &str) -> Result<()> {
+    let fail = |err: minisign_verify::Error| {
+        warn!("Signature verification failed: {err}");
+        UpdaterError::BadSignature
+    };
+
+    let public_key = PublicKey::from_base64(public_key.trim()).map_err(fail)?;
+    let signature = std::str::from_utf8(signature).map_err(|_| UpdaterError::BadSignature)?;
+    let signature = Signature::decode(signature).map_err(fail)?;
+    let mut verifier = public_key.verify_stream(&signature).map_err(fail)?;
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        match data.read(&mut buffer)? {
+            0 => break,
+            bytes_read => verifier.update(&buffer[..bytes_read]),
+        }
+    }
+    verifier.finalize().map_err(fail)?;
+
+    info!("Signature verified: {}", signature.trusted_comment());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Only signs the fixture below. Not a key releases are signed with.
+    const PUBLIC_KEY_FILE: &str = include_str!("testdata/signed.txt.pub");
+    const FIXTURE: &[u8] = include_bytes!("testdata/signed.txt");
+    const FIXTURE_SIGNATURE: &[u8] = include_bytes!("testdata/signed.txt.minisig");
+
+    // As written by `minisign -G`: a comment line, then the key
+    fn public_key() -> &'static str {
+        PUBLIC_KEY_FILE.lines().last().unwrap()
+    }
+
+    #[test]
+    fn signed_fixture_is_verified() {
+        verify(FIXTURE, FIXTURE_SIGNATURE, public_key()).unwrap();
+    }
+
+    #[test]
+    fn tampered_fixture_is_rejected() {
+        let mut tampered = FIXTURE.to_vec();
+        tampered[0] ^= 1;
+        assert!(matches!(
+            verify(&tampered[..], FIXTURE_SIGNATURE, public_key()),
+            Err(UpdaterError::BadSignature)
+        ));
+    }
+}
@@ -0,0 +1,282 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use bytes::Bytes;
+use log::{info, warn};
+
+use super::fetching;
+use super::gitea::Gitea;
+use super::manifest::JsonManifest;
+use crate::app_state::Transfer;
+use crate::cancel::CancellationToken;
+use crate::config::Forge;
+use crate::error::{Result, UpdaterError};
+use crate::github::{Comparison, Release, Tag};
+
+// Where release information and assets come from: the forge hosting the
+// source's repository. Tests put a mock in the app state instead.
+pub trait ReleaseSource: Send + Sync {
+    fn fetch_releases(&self, repo: &str, max_pages: usize) -> Result<Vec<Release>>;
+
+    fn fetch_release(&self, repo: &str, tag_name: &str) -> Result<Release>;
+
+    fn fetch_tags(&self, repo: &str, max_pages: usize) -> Result<Vec<Tag>>;
+
+    fn fetch_tag(&self, repo: &str, tag_name: &str) -> Result<Tag>;
+
+    fn download(
+        &self,
+        url: &str,
+        token: &CancellationToken,
+        progress_cb: &dyn Fn(Transfer),
+    ) -> Result<Bytes>;
+
+    // Streams to a file instead of memory, for downloads too big to hold there
+    fn download_to_file(
+        &self,
+        url: &str,
+        path: &Path,
+        token: &CancellationToken,
+        progress_cb: &dyn Fn(Transfer),
+    ) -> Result<()>;
+
+    // Commits between two versions, for the release details screen. Only
+    // GitHub has an API for it.
+    fn fetch_comparison(&self, _repo: &str, _base: &str, _head: &str) -> Result<Comparison> {
+        Err(UpdaterError::NotFound("Comparing versions isn't supported"))
+    }
+
+    // Download from the first URL that works, e.g. mirrors before GitHub
+    fn download_any(
+        &self,
+        urls: &[String],
+        token: &CancellationToken,
+        progress_cb: &dyn Fn(Transfer),
+    ) -> Result<Bytes> {
+        first_working(urls, |url| self.download(url, token, progress_cb))
+    }
+
+    fn download_any_to_file(
+        &self,
+        urls: &[String],
+        path: &Path,
+        token: &CancellationToken,
+        progress_cb: &dyn Fn(Transfer),
+    ) -> Result<()> {
+        first_working(urls, |url| {
+            self.download_to_file(url, path, token, progress_cb)
+        })
+    }
+}
+
+fn first_working<T>(urls: &[String], mut download: impl FnMut(&str) -> Result<T>) -> Result<T> {
+    let mut last_err = None;
+    for url in urls {
+        info!("Downloading from {url}");
+        match download(url) {
+            Ok(downloaded) => return Ok(downloaded),
+            Err(err @ UpdaterError::Cancelled) => return Err(err),
+            Err(err) => {
+                warn!("Download from {url} failed: {err}");
+                last_err = Some(err);
+            }
+        }
+    }
+    Err(last_err.unwrap_or(UpdaterError::NotFound("No download URL")))
+}
+
+pub fn for_forge(forge: &Forge) -> Arc<dyn ReleaseSource> {
+    match forge {
+        Forge::GitHub => Arc::new(GitHub),
+        Forge::Gitea { url } => Arc::new(Gitea { url: url.clone() }),
+        Forge::Manifest { url } => Arc::new(JsonManifest { url: url.clone() }),
+    }
+}
+
+// The GitHub API, through reqwest
+pub struct GitHub;
+
+impl ReleaseSource for GitHub {
+    fn fetch_releases(&self, repo: &str, max_pages: usize) -> Result<Vec<Release>> {
+        fetching::fetch_releases(repo, max_pages)
+    }
+
+    fn fetch_release(&self, repo: &str, tag_name: &str) -> Result<Release> {
+        fetching::fetch_release_by_tag(repo, tag_name)
+    }
+
+    fn fetch_tags(&self, repo: &str, max_pages: usize) -> Result<Vec<Tag>> {
+        fetching::fetch_tags(repo, max_pages)
+    }
+
+    fn fetch_tag(&self, repo: &str, tag_name: &str) -> Result<Tag> {
+        fetching::fetch_tag(repo, tag_name)
+    }
+
+    fn download(
+        &self,
+        url: &str,
+        token: &CancellationToken,
+        progress_cb: &dyn Fn(Transfer),
+    ) -> Result<Bytes> {
+        fetching::download(url, token, progress_cb)
+    }
+
+    fn download_to_file(
+        &self,
+        url: &str,
+        path: &Path,
+        token: &CancellationToken,
+        progress_cb: &dyn Fn(Transfer),
+    ) -> Result<()> {
+        fetching::download_to_file(url, path, token, progress_cb)
+    }
+
+    fn fetch_comparison(&self, repo: &str, base: &str, head: &str) -> Result<Comparison> {
+        fetching::fetch_comparison(repo, base, head)
+    }
+}
+
+#[cfg(test)]
+pub mod mock {
+    use std::collections::HashMap;
+
+    use parking_lot::Mutex;
+
+    use super::*;
+    use crate::github::{Asset, Commit};
+
+    // Serves whatever it's given, and remembers which URLs were downloaded
+    #[derive(Default)]
+    pub struct MockSource {
+        pub releases: Vec<Release>,
+        pub tags: Vec<Tag>,
+        pub files: HashMap<String, Bytes>,
+        // Makes fetching releases fail, as if offline
+        pub offline: bool,
+        pub downloaded: Mutex<Vec<String>>,
+    }
+
+    pub fn release(tag_name: &str, published_at: &str, assets: &[&str]) -> Release {
+        Release {
+            tag_name: tag_name.to_string(),
+            published_at: Some(published_at.to_string()),
+            body: None,
+            assets: assets
+                .iter()
+                .map(|name| Asset {
+                    name: (*name).to_string(),
+                    url: format!("https://api.example.com/{tag_name}/{name}"),
+                    size: 0,
+                    download_count: 0,
+                    digest: None,
+                })
+                .collect(),
+            draft: false,
+            prerelease: false,
+        }
+    }
+
+    pub fn tag(name: &str, sha: &str) -> Tag {
+        Tag {
+            name: name.to_string(),
+            commit: Commit {
+                sha: sha.to_string(),
+            },
+        }
+    }
+
+    impl ReleaseSource for MockSource {
+        fn fetch_releases(&self, _repo: &str, _max_pages: usize) -> Result<Vec<Release>> {
+            if self.offline {
+                return Err(UpdaterError::NotFound("Offline"));
+            }
+            Ok(self.releases.clone())
+        }
+
+        fn fetch_release(&self, _repo: &str, tag_name: &str) -> Result<Release> {
+            self.releases
+                .iter()
+                .find(|release| release.tag_name == tag_name)
+                .cloned()
+                .ok_or(UpdaterError::NotFound("Release"))
+        }
+
+        fn fetch_tags(&self, _repo: &str, _max_pages: usize) -> Result<Vec<Tag>> {
+            Ok(self.tags.clone())
+        }
+
+        fn fetch_tag(&self, _repo: &str, tag_name: &str) -> Result<Tag> {
+            self.tags
+                .iter()
+                .find(|tag| tag.name == tag_name)
+                .cloned()
+                .ok_or(UpdaterError::NotFound("Tag"))
+        }
+
+        fn download(
+            &self,
+            url: &str,
+            token: &CancellationToken,
+            progress_cb: &dyn Fn(Transfer),
+        ) -> Result<Bytes> {
+            token.check()?;
+            self.downloaded.lock().push(url.to_string());
+            let bytes = self
+                .files
+                .get(url)
+                .cloned()
+                .ok_or(UpdaterError::NotFound("File"))?;
+            progress_cb(Transfer {
+                downloaded: bytes.len() as u64,
+                total: bytes.len() as u64,
+                speed: 0.0,
+            });
+            Ok(bytes)
+        }
+
+        fn download_to_file(
+            &self,
+            url: &str,
+            path: &Path,
+            token: &CancellationToken,
+            progress_cb: &dyn Fn(Transfer),
+        ) -> Result<()> {
+            let bytes = self.download(url, token, progress_cb)?;
+            std::fs::write(path, bytes)?;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::mock::MockSource;
+    use super::*;
+
+    #[test]
+    fn download_any_falls_back_to_the_next_url() {
+        let mut source = MockSource::default();
+        source
+            .files
+            .insert("https://github.example.com/a".to_string(), Bytes::from("a"));
+        let urls = [
+            "https://mirror.example.com/a".to_string(),
+            "https://github.example.com/a".to_string(),
+        ];
+
+        let token = CancellationToken::new();
+        let bytes = source.download_any(&urls, &token, &|_| {}).unwrap();
+        assert_eq!(bytes, "a");
+        assert_eq!(*source.downloaded.lock(), urls);
+    }
+
+    #[test]
+    fn download_any_fails_when_every_url_does() {
+        let source = MockSource::default();
+        let urls = ["https://mirror.example.com/a".to_string()];
+        let token = CancellationToken::new();
+        assert!(source.download_any(&urls, &token, &|_| {}).is_err());
+        assert!(source.download_any(&[], &token, &|_| {}).is_err());
+    }
+}
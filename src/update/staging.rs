@@ -0,0 +1,155 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use super::memory::{self, Archive};
+use crate::error::Result;
+use crate::fault::{self, CrashPoint};
+use crate::{sdcard_root, UPDATER_DATA_DIR};
+
+const STAGED_DIR: &str = "staged";
+const MARKER_FILE: &str = "staged.json";
+const ARCHIVE_FILE: &str = "update.zip";
+
+// An update that was downloaded and verified, to be applied the next time
+// the updater starts
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StagedUpdate {
+    pub tag_name: String,
+    pub asset_name: String,
+    pub full: bool,
+    pub from_version: Option<String>,
+    pub staged_at: i64,
+}
+
+fn dir() -> PathBuf {
+    PathBuf::from(sdcard_root())
+        .join(UPDATER_DATA_DIR)
+        .join(STAGED_DIR)
+}
+
+// The marker is written last, so a half-written archive is never applied
+pub fn stage(bytes: &Archive, update: &StagedUpdate) -> Result<()> {
+    stage_in(&dir(), bytes, update)
+}
+
+fn stage_in(dir: &Path, bytes: &Archive, update: &StagedUpdate) -> Result<()> {
+    let _ = fs::remove_dir_all(dir);
+    fs::create_dir_all(dir)?;
+    bytes.save(&dir.join(ARCHIVE_FILE))?;
+    fault::crash_point(CrashPoint::StagedArchive);
+    fs::write(dir.join(MARKER_FILE), serde_json::to_vec_pretty(update)?)?;
+    info!("Staged {} ({})", update.tag_name, update.asset_name);
+    Ok(())
+}
+
+pub fn load() -> Option<(StagedUpdate, Archive)> {
+    load_from(&dir())
+}
+
+fn load_from(dir: &Path) -> Option<(StagedUpdate, Archive)> {
+    let marker = fs::read(dir.join(MARKER_FILE)).ok()?;
+    let update = match serde_json::from_slice(&marker) {
+        Ok(update) => update,
+        Err(err) => {
+            warn!("Ignoring invalid staged update: {err}");
+            let _ = fs::remove_dir_all(dir);
+            return None;
+        }
+    };
+    let bytes = memory::read_file(&dir.join(ARCHIVE_FILE)).ok()?;
+    Some((update, bytes))
+}
+
+pub fn staged() -> Option<StagedUpdate> {
+    let marker = fs::read(dir().join(MARKER_FILE)).ok()?;
+    serde_json::from_slice(&marker).ok()
+}
+
+// The staging directory, if it's left over from staging that never
+// finished and so holds nothing to apply
+pub fn stale() -> Option<PathBuf> {
+    let dir = dir();
+    (dir.exists() && staged().is_none()).then_some(dir)
+}
+
+pub fn discard() {
+    let _ = fs::remove_dir_all(dir());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fault::testing::{child_dir, crash_in_child};
+    use bytes::Bytes;
+
+    fn archive(bytes: &'static [u8]) -> Archive {
+        Bytes::from_static(bytes).into()
+    }
+
+    fn update() -> StagedUpdate {
+        StagedUpdate {
+            tag_name: "v6.0.0".to_string(),
+            asset_name: "NextUI-base.zip".to_string(),
+            full: false,
+            from_version: None,
+            staged_at: 0,
+        }
+    }
+
+    #[test]
+    fn staged_update_loads() {
+        let dir = tempfile::tempdir().unwrap();
+        stage_in(dir.path(), &archive(b"archive"), &update()).unwrap();
+        let (staged, bytes) = load_from(dir.path()).unwrap();
+        assert_eq!(staged.tag_name, "v6.0.0");
+        assert_eq!(bytes.to_vec(), b"archive");
+    }
+
+    #[test]
+    fn staging_again_replaces_the_staged_update() {
+        let dir = tempfile::tempdir().unwrap();
+        stage_in(dir.path(), &archive(b"archive"), &update()).unwrap();
+        let newer = StagedUpdate {
+            tag_name: "v6.1.0".to_string(),
+            full: true,
+            ..update()
+        };
+        stage_in(dir.path(), &archive(b"newer archive"), &newer).unwrap();
+
+        let (staged, bytes) = load_from(dir.path()).unwrap();
+        assert_eq!(staged.tag_name, "v6.1.0");
+        assert!(staged.full);
+        assert_eq!(bytes.to_vec(), b"newer archive");
+    }
+
+    #[test]
+    fn invalid_staged_update_is_discarded() {
+        let dir = tempfile::tempdir().unwrap();
+        stage_in(dir.path(), &archive(b"archive"), &update()).unwrap();
+        fs::write(dir.path().join(MARKER_FILE), b"{").unwrap();
+
+        assert!(load_from(dir.path()).is_none());
+        assert!(!dir.path().exists());
+    }
+
+    #[test]
+    fn power_loss_while_staging_leaves_nothing_to_apply() {
+        if let Some(dir) = child_dir() {
+            stage_in(&dir, &archive(b"archive"), &update()).unwrap();
+            return;
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        assert!(crash_in_child(
+            module_path!(),
+            "power_loss_while_staging_leaves_nothing_to_apply",
+            "staged-archive",
+            dir.path(),
+        ));
+        assert!(dir.path().join(ARCHIVE_FILE).exists());
+        assert!(load_from(dir.path()).is_none());
+    }
+}
@@ -0,0 +1,103 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::UNIX_EPOCH;
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+use crate::{date, sdcard_root, UPDATER_DATA_DIR};
+
+// Written when an update is installed, checked on the next launch
+const PENDING_FILE: &str = "install-pending.json";
+// NextUI (or a boot script) can create this once it has started, which
+// counts as a clean boot regardless of what the heuristics say
+const BOOT_OK_FILE: &str = "boot-ok";
+
+// NextUI unpacks this on boot and deletes it, so it being left over means
+// the boot didn't get that far
+pub const BOOT_ARCHIVE: &str = "MinUI.zip";
+
+// An installed update that hasn't been seen booting yet
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PendingInstall {
+    pub from_version: Option<String>,
+    pub to_version: String,
+    pub installed_at: i64,
+}
+
+fn path(name: &str) -> PathBuf {
+    PathBuf::from(sdcard_root())
+        .join(UPDATER_DATA_DIR)
+        .join(name)
+}
+
+pub fn mark(from_version: Option<String>, to_version: &str) -> Result<()> {
+    let pending = PendingInstall {
+        from_version,
+        to_version: to_version.to_string(),
+        installed_at: date::now(),
+    };
+    let _ = fs::remove_file(path(BOOT_OK_FILE));
+    fs::write(path(PENDING_FILE), serde_json::to_vec_pretty(&pending)?)?;
+    Ok(())
+}
+
+pub fn pending() -> Option<PendingInstall> {
+    let contents = fs::read(path(PENDING_FILE)).ok()?;
+    match serde_json::from_slice(&contents) {
+        Ok(pending) => Some(pending),
+        Err(err) => {
+            warn!("Ignoring invalid install marker: {err}");
+            clear();
+            None
+        }
+    }
+}
+
+pub fn clear() {
+    let _ = fs::remove_file(path(PENDING_FILE));
+    let _ = fs::remove_file(path(BOOT_OK_FILE));
+}
+
+fn boot_ok_since(timestamp: i64) -> bool {
+    fs::metadata(path(BOOT_OK_FILE))
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .is_some_and(|modified| i64::try_from(modified.as_secs()).unwrap_or(0) >= timestamp)
+}
+
+// When the system last booted, from the uptime
+fn boot_time() -> Option<i64> {
+    let uptime = fs::read_to_string("/proc/uptime").ok()?;
+    let seconds: f64 = uptime.split_whitespace().next()?.parse().ok()?;
+    Some(date::now() - seconds as i64)
+}
+
+// Decide whether the last install booted cleanly, once the device has
+// rebooted since. `installed` tells whether the running NextUI is the
+// version that was installed, if that's known. Returns the install if it
+// looks like it failed; the marker is cleared either way.
+pub fn check(installed: impl Fn(&str) -> Option<bool>) -> Option<PendingInstall> {
+    let pending = pending()?;
+
+    if boot_ok_since(pending.installed_at) {
+        info!("Install of {} confirmed by NextUI", pending.to_version);
+        clear();
+        return None;
+    }
+    // Still the same boot, e.g. the reboot after installing didn't happen
+    if boot_time().is_some_and(|booted| booted < pending.installed_at) {
+        return None;
+    }
+
+    clear();
+    let unpacked = !PathBuf::from(sdcard_root()).join(BOOT_ARCHIVE).exists();
+    if unpacked && installed(&pending.to_version) != Some(false) {
+        info!("Install of {} booted", pending.to_version);
+        return None;
+    }
+    warn!("Install of {} didn't boot cleanly", pending.to_version);
+    Some(pending)
+}
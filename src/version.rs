@@ -0,0 +1,303 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use crate::config::MigrationNote;
+use crate::i18n::tr;
+
+// Parsed NextUI release tag, e.g. "v6.2.1", "v6.3.0-hotfix2" or "v7.0.0-beta.1"
+#[derive(Clone, Debug)]
+pub struct NextUIVersion {
+    pub numbers: Vec<u64>,
+    pub suffix: Suffix,
+}
+
+// Ordered so that prereleases sort before their release and hotfixes after it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Suffix {
+    Prerelease(Prerelease, u64),
+    Release,
+    Hotfix(u64),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Prerelease {
+    Dev,
+    Alpha,
+    Beta,
+    ReleaseCandidate,
+}
+
+// How far apart two versions are
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VersionDelta {
+    Same,
+    Major,
+    Minor,
+    Patch,
+    Hotfix,
+    Prerelease,
+}
+
+impl NextUIVersion {
+    pub fn parse(tag: &str) -> Option<Self> {
+        let tag = tag.trim();
+        let tag = tag.strip_prefix("NextUI-").unwrap_or(tag);
+        let tag = tag
+            .strip_prefix('v')
+            .or_else(|| tag.strip_prefix('V'))
+            .unwrap_or(tag);
+
+        let (numbers, suffix) = match tag.find(['-', '+', '_']) {
+            Some(index) => (&tag[..index], Some(&tag[index + 1..])),
+            None => (tag, None),
+        };
+
+        let numbers = numbers
+            .split('.')
+            .map(str::parse)
+            .collect::<std::result::Result<Vec<u64>, _>>()
+            .ok()?;
+
+        let suffix = match suffix {
+            Some(suffix) => Self::parse_suffix(suffix)?,
+            None => Suffix::Release,
+        };
+
+        Some(Self { numbers, suffix })
+    }
+
+    fn parse_suffix(suffix: &str) -> Option<Suffix> {
+        let suffix = suffix.to_ascii_lowercase();
+        let split = suffix
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(suffix.len());
+        let (name, number) = suffix.split_at(split);
+        let name = name.trim_end_matches(['.', '-', '_']);
+        let number = if number.is_empty() {
+            0
+        } else {
+            number.parse().ok()?
+        };
+
+        let suffix = match name {
+            "hotfix" | "fix" | "h" | "patch" => Suffix::Hotfix(number.max(1)),
+            "rc" | "pre" => Suffix::Prerelease(Prerelease::ReleaseCandidate, number),
+            "beta" | "b" => Suffix::Prerelease(Prerelease::Beta, number),
+            "alpha" | "a" => Suffix::Prerelease(Prerelease::Alpha, number),
+            "dev" | "nightly" | "snapshot" => Suffix::Prerelease(Prerelease::Dev, number),
+            _ => return None,
+        };
+        Some(suffix)
+    }
+
+    fn number(&self, index: usize) -> u64 {
+        self.numbers.get(index).copied().unwrap_or(0)
+    }
+
+    pub fn is_prerelease(&self) -> bool {
+        matches!(self.suffix, Suffix::Prerelease(..))
+    }
+
+    // The most significant component that differs between the two versions
+    pub fn delta(&self, other: &Self) -> VersionDelta {
+        let len = self.numbers.len().max(other.numbers.len()).max(3);
+        match (0..len).find(|&i| self.number(i) != other.number(i)) {
+            Some(0) => VersionDelta::Major,
+            Some(1) => VersionDelta::Minor,
+            Some(_) => VersionDelta::Patch,
+            None => match (self.suffix, other.suffix) {
+                (a, b) if a == b => VersionDelta::Same,
+                (Suffix::Hotfix(_), _) | (_, Suffix::Hotfix(_)) => VersionDelta::Hotfix,
+                _ => VersionDelta::Prerelease,
+            },
+        }
+    }
+}
+
+impl Ord for NextUIVersion {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let len = self.numbers.len().max(other.numbers.len());
+        (0..len)
+            .map(|i| self.number(i).cmp(&other.number(i)))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+            .then(self.suffix.cmp(&other.suffix))
+    }
+}
+
+impl PartialOrd for NextUIVersion {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for NextUIVersion {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for NextUIVersion {}
+
+impl fmt::Display for VersionDelta {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            VersionDelta::Same => tr!("same"),
+            VersionDelta::Major => tr!("major"),
+            VersionDelta::Minor => tr!("minor"),
+            VersionDelta::Patch => tr!("patch"),
+            VersionDelta::Hotfix => tr!("hotfix"),
+            VersionDelta::Prerelease => tr!("prerelease"),
+        })
+    }
+}
+
+// Notes for the versions a downgrade from `installed` to `target` goes back
+// past, oldest first. None if either tag isn't a version.
+pub fn downgrade_notes<'a>(
+    notes: &'a [MigrationNote],
+    installed: &str,
+    target: &str,
+) -> Option<Vec<&'a MigrationNote>> {
+    let installed = NextUIVersion::parse(installed)?;
+    let target = NextUIVersion::parse(target)?;
+    let mut crossed: Vec<(NextUIVersion, &MigrationNote)> = notes
+        .iter()
+        .filter_map(|note| Some((NextUIVersion::parse(&note.version)?, note)))
+        .filter(|(version, _)| target < *version && *version <= installed)
+        .collect();
+    crossed.sort_by(|(a, _), (b, _)| a.cmp(b));
+    Some(crossed.into_iter().map(|(_, note)| note).collect())
+}
+
+// "newer (minor)" / "older (major)" compared to the installed version
+pub fn compare_label(candidate: &str, installed: &str) -> Option<String> {
+    let candidate = NextUIVersion::parse(candidate)?;
+    let installed = NextUIVersion::parse(installed)?;
+    let direction = match candidate.cmp(&installed) {
+        Ordering::Greater => tr!("newer"),
+        Ordering::Less => tr!("older"),
+        Ordering::Equal => return None,
+    };
+    Some(format!("{direction} ({})", candidate.delta(&installed)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(version: &str) -> MigrationNote {
+        MigrationNote {
+            version: version.to_string(),
+            note: format!("Changed in {version}"),
+        }
+    }
+
+    fn versions(notes: &[&MigrationNote]) -> Vec<String> {
+        notes.iter().map(|note| note.version.clone()).collect()
+    }
+
+    #[test]
+    fn downgrades_get_the_notes_they_go_back_past() {
+        let notes = [
+            note("v6.3.0"),
+            note("v6.1.0"),
+            note("v6.2.0"),
+            note("v5.0.0"),
+        ];
+        let crossed = downgrade_notes(&notes, "v6.3.0", "v6.0.0").unwrap();
+        assert_eq!(versions(&crossed), ["v6.1.0", "v6.2.0", "v6.3.0"]);
+
+        // Going back to a version keeps its own changes
+        let crossed = downgrade_notes(&notes, "v6.3.0", "v6.2.0").unwrap();
+        assert_eq!(versions(&crossed), ["v6.3.0"]);
+    }
+
+    #[test]
+    fn upgrades_and_unknown_versions_get_no_notes() {
+        let notes = [note("v6.1.0"), note("not a version")];
+        assert!(downgrade_notes(&notes, "v6.0.0", "v6.2.0")
+            .unwrap()
+            .is_empty());
+        assert!(
+            downgrade_notes(&notes, "v6.2.0", "v6.0.0").is_some_and(|crossed| crossed.len() == 1)
+        );
+        assert!(downgrade_notes(&notes, "abcdef0", "v6.0.0").is_none());
+    }
+
+    fn version(tag: &str) -> NextUIVersion {
+        NextUIVersion::parse(tag).unwrap()
+    }
+
+    #[test]
+    fn tags_are_parsed() {
+        let parsed = version("NextUI-v6.2.1");
+        assert_eq!(parsed.numbers, [6, 2, 1]);
+        assert_eq!(parsed.suffix, Suffix::Release);
+        assert_eq!(version("v6.3.0-hotfix2").suffix, Suffix::Hotfix(2));
+        assert_eq!(version("V6.3.0_fix").suffix, Suffix::Hotfix(1));
+        assert_eq!(
+            version("v7.0.0-beta.1").suffix,
+            Suffix::Prerelease(Prerelease::Beta, 1)
+        );
+        assert_eq!(
+            version("7.0.0-RC3").suffix,
+            Suffix::Prerelease(Prerelease::ReleaseCandidate, 3)
+        );
+        assert!(version("v7.0.0-nightly").is_prerelease());
+        assert!(NextUIVersion::parse("abcdef0").is_none());
+        assert!(NextUIVersion::parse("v6.2.x").is_none());
+        assert!(NextUIVersion::parse("v6.2.0-unknown").is_none());
+    }
+
+    #[test]
+    fn versions_are_ordered() {
+        let ordered = [
+            "v6.2",
+            "v6.2.1-dev",
+            "v6.2.1-alpha",
+            "v6.2.1-beta.1",
+            "v6.2.1-beta.2",
+            "v6.2.1-rc1",
+            "v6.2.1",
+            "v6.2.1-hotfix1",
+            "v6.2.1-hotfix2",
+            "v6.10.0",
+            "v7.0.0",
+        ];
+        for pair in ordered.windows(2) {
+            assert!(version(pair[0]) < version(pair[1]), "{pair:?}");
+        }
+        // Missing components count as zero
+        assert_eq!(version("v6.2"), version("v6.2.0"));
+    }
+
+    #[test]
+    fn deltas_name_the_most_significant_change() {
+        let deltas = [
+            ("v7.0.0", "v6.2.1", VersionDelta::Major),
+            ("v6.3.0", "v6.2.1", VersionDelta::Minor),
+            ("v6.2.2", "v6.2.1", VersionDelta::Patch),
+            ("v6.2.1-hotfix1", "v6.2.1", VersionDelta::Hotfix),
+            ("v6.2.1-rc1", "v6.2.1", VersionDelta::Prerelease),
+            ("v6.2", "v6.2.0", VersionDelta::Same),
+        ];
+        for (a, b, delta) in deltas {
+            assert_eq!(version(a).delta(&version(b)), delta, "{a} {b}");
+        }
+    }
+
+    #[test]
+    fn labels_compare_with_the_installed_version() {
+        assert_eq!(
+            compare_label("v6.3.0", "v6.2.1").as_deref(),
+            Some("newer (minor)")
+        );
+        assert_eq!(
+            compare_label("v5.0.0", "v6.2.1").as_deref(),
+            Some("older (major)")
+        );
+        assert_eq!(compare_label("v6.2.1", "v6.2.1"), None);
+        assert_eq!(compare_label("v6.2.1", "abcdef0"), None);
+    }
+}